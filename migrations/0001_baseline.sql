@@ -0,0 +1,107 @@
+-- Tables that predate versioned migrations. Existing databases already have them, hence the
+-- IF NOT EXISTS so the baseline can be recorded against them without touching any data.
+
+CREATE TABLE IF NOT EXISTS payments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    account_id UUID NOT NULL,
+    address TEXT NOT NULL,
+    amount FLOAT8 NOT NULL,
+    received FLOAT8 NOT NULL DEFAULT 0,
+    initiated BOOLEAN NOT NULL DEFAULT FALSE,
+    completed BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS payments_address_idx ON payments (address);
+
+CREATE OR REPLACE FUNCTION set_updated_at() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.updated_at = NOW();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS payments_set_updated_at ON payments;
+CREATE TRIGGER payments_set_updated_at
+    BEFORE UPDATE ON payments
+    FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+CREATE TABLE IF NOT EXISTS payment_transactions (
+    payment_id UUID NOT NULL REFERENCES payments (id) ON DELETE CASCADE,
+    transaction_id TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (payment_id, transaction_id)
+);
+
+CREATE INDEX IF NOT EXISTS payment_transactions_transaction_id_idx
+    ON payment_transactions (transaction_id);
+
+CREATE TABLE IF NOT EXISTS payment_inscription_contents (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    payment_id UUID NOT NULL REFERENCES payments (id) ON DELETE CASCADE,
+    target TEXT NOT NULL,
+    content TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS payment_inscription_contents_payment_id_idx
+    ON payment_inscription_contents (payment_id);
+
+-- Filled in by the inscriber once the reveal transaction of a content is broadcast.
+CREATE TABLE IF NOT EXISTS payment_inscriptions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    content UUID NOT NULL UNIQUE REFERENCES payment_inscription_contents (id) ON DELETE CASCADE,
+    reveal_tx TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS private_keys (
+    payment_inscription_content_id UUID PRIMARY KEY
+        REFERENCES payment_inscription_contents (id) ON DELETE CASCADE,
+    account_id UUID NOT NULL,
+    domain TEXT NOT NULL,
+    encryption_method INT2 NOT NULL,
+    private_key TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS private_keys_account_id_domain_idx ON private_keys (account_id, domain);
+CREATE INDEX IF NOT EXISTS private_keys_domain_idx ON private_keys (domain);
+
+CREATE TABLE IF NOT EXISTS logs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    account_id UUID NOT NULL,
+    action TEXT NOT NULL,
+    data TEXT,
+    encryption_method INT2,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS logs_account_id_created_at_idx ON logs (account_id, created_at);
+
+CREATE TABLE IF NOT EXISTS loyalty_discounts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    collection_id TEXT NOT NULL,
+    collection_type INT2 NOT NULL,
+    collection_minimum_owned FLOAT8,
+    amount NUMERIC NOT NULL,
+    currency TEXT NOT NULL,
+    message TEXT NOT NULL,
+    stackable BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+-- Shared with the account service, which owns their contents.
+CREATE TABLE IF NOT EXISTS addresses (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    account_id UUID NOT NULL,
+    address TEXT NOT NULL,
+    encryption_method INT2 NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS addresses_account_id_idx ON addresses (account_id);
+
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    account_id UUID NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
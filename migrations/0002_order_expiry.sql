@@ -0,0 +1,10 @@
+ALTER TABLE payments ADD COLUMN IF NOT EXISTS expired BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE payments ADD COLUMN IF NOT EXISTS refund_required BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Orders used to be deleted 35 minutes after they were created.
+ALTER TABLE payments ADD COLUMN IF NOT EXISTS expires_at TIMESTAMP;
+UPDATE payments SET expires_at = created_at + INTERVAL '35 minutes' WHERE expires_at IS NULL;
+ALTER TABLE payments ALTER COLUMN expires_at SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS payments_account_id_created_at_id_idx
+    ON payments (account_id, created_at, id);
@@ -0,0 +1,31 @@
+-- Every address handed out, so funds arriving after an order is deleted can still be attributed.
+CREATE TABLE issued_addresses (
+    address TEXT PRIMARY KEY,
+    account_id UUID NOT NULL,
+    payment_id UUID REFERENCES payments (id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO issued_addresses (address, account_id, payment_id, created_at)
+SELECT DISTINCT ON (address) address, account_id, id, created_at
+FROM payments
+ORDER BY address, created_at DESC
+ON CONFLICT (address) DO NOTHING;
+
+CREATE TABLE late_payments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    account_id UUID NOT NULL,
+    payment_id UUID REFERENCES payments (id) ON DELETE SET NULL,
+    address TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    amount FLOAT8 NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (transaction_id, address)
+);
+
+CREATE TABLE admins (
+    account_id UUID PRIMARY KEY,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,18 @@
+CREATE TABLE refunds (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    account_id UUID NOT NULL,
+    payment_id UUID REFERENCES payments (id) ON DELETE SET NULL,
+    late_payment_id UUID REFERENCES late_payments (id),
+    amount FLOAT8 NOT NULL,
+    -- Encrypted with `encryption_method`.
+    address TEXT NOT NULL,
+    encryption_method INT2 NOT NULL,
+    status TEXT NOT NULL DEFAULT 'requested',
+    reason TEXT,
+    fee_rate FLOAT8,
+    transaction_id TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX refunds_account_id_idx ON refunds (account_id);
@@ -0,0 +1,2 @@
+ALTER TABLE payments ADD COLUMN lightning_invoice TEXT;
+ALTER TABLE payments ADD COLUMN lightning_payment_hash TEXT UNIQUE;
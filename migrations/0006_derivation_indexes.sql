@@ -0,0 +1,5 @@
+-- Next unused child index per receive descriptor.
+CREATE TABLE derivation_indexes (
+    descriptor TEXT PRIMARY KEY,
+    next_index INT4 NOT NULL
+);
@@ -0,0 +1,17 @@
+CREATE TABLE sweeps (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    transaction_id TEXT NOT NULL UNIQUE,
+    address TEXT NOT NULL,
+    amount FLOAT8 NOT NULL,
+    fee FLOAT8 NOT NULL,
+    fee_rate FLOAT8 NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE sweep_inputs (
+    sweep_id UUID NOT NULL REFERENCES sweeps (id),
+    transaction_id TEXT NOT NULL,
+    vout INT4 NOT NULL,
+    payment_id UUID REFERENCES payments (id) ON DELETE SET NULL,
+    PRIMARY KEY (transaction_id, vout)
+);
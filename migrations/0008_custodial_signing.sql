@@ -0,0 +1 @@
+ALTER TABLE private_keys ADD COLUMN custodial_signing BOOLEAN NOT NULL DEFAULT FALSE;
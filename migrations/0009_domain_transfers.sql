@@ -0,0 +1,16 @@
+CREATE TABLE domain_transfers (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    domain TEXT NOT NULL,
+    from_account_id UUID NOT NULL,
+    to_account_id UUID,
+    to_address TEXT,
+    status TEXT NOT NULL DEFAULT 'pending',
+    inscribe BOOLEAN NOT NULL DEFAULT FALSE,
+    payment_id UUID REFERENCES payments (id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX domain_transfers_domain_idx ON domain_transfers (domain);
+CREATE INDEX domain_transfers_from_account_id_idx ON domain_transfers (from_account_id);
+CREATE INDEX domain_transfers_to_account_id_idx ON domain_transfers (to_account_id);
@@ -0,0 +1,9 @@
+CREATE TABLE domain_rules (
+    domain TEXT PRIMARY KEY,
+    list TEXT NOT NULL CHECK (list IN ('reserved', 'blocked', 'trademark_hold')),
+    reason TEXT NOT NULL,
+    allocated_account_id UUID,
+    release_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,19 @@
+-- One row per domain with a live order, the primary key is what keeps two orders from claiming
+-- the same domain.
+CREATE TABLE domain_reservations (
+    domain TEXT PRIMARY KEY,
+    payment_id UUID NOT NULL REFERENCES payments (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX domain_reservations_payment_id_idx ON domain_reservations (payment_id);
+
+-- Initiated orders win over pending ones, then the earliest order.
+INSERT INTO domain_reservations (domain, payment_id)
+SELECT DISTINCT ON (private_keys.domain) private_keys.domain, payments.id
+FROM private_keys
+INNER JOIN payment_inscription_contents
+    ON payment_inscription_contents.id = private_keys.payment_inscription_content_id
+INNER JOIN payments ON payments.id = payment_inscription_contents.payment_id
+WHERE payments.expired = FALSE
+ORDER BY private_keys.domain, payments.initiated DESC, payments.created_at ASC;
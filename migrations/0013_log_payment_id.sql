@@ -0,0 +1,5 @@
+-- The payment a log entry is about, kept in plaintext next to the encrypted data. Entries written
+-- before this column existed hold an encrypted sentence and are left without one.
+ALTER TABLE logs ADD COLUMN payment_id UUID;
+
+CREATE INDEX logs_payment_id_created_at_idx ON logs (payment_id, created_at);
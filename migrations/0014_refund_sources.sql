@@ -0,0 +1,46 @@
+-- Duplicate refund requests that slipped in before are rejected. Refunds that are already being
+-- processed or broadcast are kept over requested ones, then the earliest request. Payments could
+-- only be refunded once so far, later requests for the same payment are duplicates as well.
+UPDATE refunds SET status = 'rejected', reason = 'Duplicate refund request', updated_at = NOW()
+WHERE status = 'requested' AND id IN (
+    SELECT id FROM (
+        SELECT id, ROW_NUMBER() OVER (
+            PARTITION BY payment_id ORDER BY status = 'requested', created_at
+        ) AS position
+        FROM refunds
+        WHERE payment_id IS NOT NULL AND status <> 'rejected'
+    ) payment_refunds
+    WHERE position > 1
+    UNION
+    SELECT id FROM (
+        SELECT id, ROW_NUMBER() OVER (
+            PARTITION BY late_payment_id ORDER BY status = 'requested', created_at
+        ) AS position
+        FROM refunds
+        WHERE late_payment_id IS NOT NULL AND status <> 'rejected'
+    ) late_payment_refunds
+    WHERE position > 1
+);
+
+-- Duplicates that are already being processed or broadcast can't be rejected here, they have to be
+-- looked into by hand before the migration can run.
+DO $$
+DECLARE
+    duplicates TEXT;
+BEGIN
+    SELECT string_agg(late_payment_id::TEXT, ', ') INTO duplicates FROM (
+        SELECT late_payment_id FROM refunds
+        WHERE late_payment_id IS NOT NULL AND status <> 'rejected'
+        GROUP BY late_payment_id
+        HAVING COUNT(*) > 1
+    ) duplicated;
+
+    IF duplicates IS NOT NULL THEN
+        RAISE EXCEPTION 'Late payments with more than one processing or broadcast refund: %', duplicates;
+    END IF;
+END $$;
+
+-- Late payments are refunded in full, so at most once. Payments may be refunded more than once,
+-- e.g. the overpayment of an order and the rest once it gets cancelled.
+CREATE UNIQUE INDEX refunds_late_payment_id_open_idx ON refunds (late_payment_id)
+    WHERE status <> 'rejected';
@@ -0,0 +1,15 @@
+-- Links record update and transfer inscriptions to the domain they update, the way private_keys
+-- does for the inscription registering it.
+CREATE TABLE domain_updates (
+    payment_inscription_content_id UUID PRIMARY KEY
+        REFERENCES payment_inscription_contents (id) ON DELETE CASCADE,
+    domain TEXT NOT NULL
+);
+
+CREATE INDEX domain_updates_domain_idx ON domain_updates (domain);
+
+-- The first line of an update inscription is `DOMAIN-UPDATE <name> <epoch>`.
+INSERT INTO domain_updates (payment_inscription_content_id, domain)
+SELECT id, split_part(split_part(content, E'\n', 1), ' ', 2)
+FROM payment_inscription_contents
+WHERE content LIKE 'DOMAIN-UPDATE %';
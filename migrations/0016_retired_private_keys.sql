@@ -0,0 +1,3 @@
+-- Set once the inscription of an external transfer completed. The row still registers the domain,
+-- but the sender no longer owns it.
+ALTER TABLE private_keys ADD COLUMN retired_at TIMESTAMP;
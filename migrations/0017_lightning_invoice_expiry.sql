@@ -0,0 +1,6 @@
+-- Invoices are watched until they expire, even once their order was paid on chain, completed or
+-- cancelled, so a late settlement still gets recorded.
+ALTER TABLE payments ADD COLUMN lightning_expires_at TIMESTAMP;
+
+-- Invoices were created with the expiry of their order.
+UPDATE payments SET lightning_expires_at = expires_at WHERE lightning_payment_hash IS NOT NULL;
@@ -0,0 +1,10 @@
+-- A domain has at most one pending transfer. Cancel the newer ones of any duplicates first, the
+-- oldest pending transfer is the one its recipient was told about first.
+UPDATE domain_transfers SET status = 'cancelled', updated_at = NOW()
+WHERE status = 'pending' AND id NOT IN (
+    SELECT DISTINCT ON (domain) id FROM domain_transfers
+    WHERE status = 'pending'
+    ORDER BY domain, created_at, id
+);
+
+CREATE UNIQUE INDEX domain_transfers_pending_domain_idx ON domain_transfers (domain) WHERE status = 'pending';
@@ -0,0 +1,144 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use bitcoincore_rpc::bitcoin::{address::NetworkChecked, Address};
+use bitcoincore_rpc::json::{ImportDescriptors, Timestamp};
+use bitcoincore_rpc::{Client, RpcApi};
+use tracing::info;
+
+use crate::bitcoin::wallet::{RpcWallet, Wallet, WalletBackend};
+use crate::db::PaymentRepository;
+use crate::CHAIN;
+
+/// How many addresses past the current index are imported into the watch-only wallet at once, so
+/// the descriptor only has to be re-imported every so many orders.
+const DESCRIPTOR_LOOKAHEAD: u32 = 1000;
+
+/// Validates a public, ranged descriptor and returns it in canonical form with its checksum.
+pub fn canonical_descriptor(rpc: &Client, descriptor: &str) -> Result<String, String> {
+    let info = rpc
+        .get_descriptor_info(descriptor)
+        .map_err(|e| format!("Invalid descriptor: {}", e))?;
+
+    if info.has_private_keys {
+        return Err("Descriptor must not contain private keys".to_string());
+    }
+
+    if !info.is_range {
+        return Err("Descriptor must be ranged (end in /*)".to_string());
+    }
+
+    Ok(match info.descriptor.contains('#') {
+        false => format!("{}#{}", info.descriptor, info.checksum),
+        true => info.descriptor,
+    })
+}
+
+/// Hands out order receive addresses.
+///
+/// Without a descriptor addresses come from the wallet itself (`getnewaddress` on the hot wallet).
+/// With a descriptor they are derived from the configured xpub at a persisted index and imported
+/// into a watch-only wallet, so the keys controlling customer funds never live on this host.
+#[derive(Clone)]
+pub struct AddressProvider {
+    wallet: Wallet,
+    /// The watch-only wallet's RPC client and the descriptor it derives from.
+    descriptor: Option<(Arc<Client>, String)>,
+    /// First index that has not been imported into the watch-only wallet yet.
+    imported_until: Arc<AtomicU32>,
+}
+
+impl AddressProvider {
+    pub fn wallet_generated(wallet: Wallet) -> Self {
+        Self {
+            wallet,
+            descriptor: None,
+            imported_until: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// `rpc` has to point at a wallet with private keys disabled, `descriptor` has to be a `tr()`
+    /// descriptor so orders are paid to Bech32m addresses.
+    pub fn watch_only(rpc: Arc<Client>, descriptor: &str) -> Result<Self, String> {
+        let descriptor = canonical_descriptor(&rpc, descriptor)?;
+
+        if !descriptor.starts_with("tr(") {
+            return Err("Descriptor must be a taproot (tr) descriptor".to_string());
+        }
+
+        info!("Deriving receive addresses from {}", descriptor);
+
+        Ok(Self {
+            wallet: Wallet::Rpc(RpcWallet::new(rpc.clone())),
+            descriptor: Some((rpc, descriptor)),
+            imported_until: Arc::new(AtomicU32::new(0)),
+        })
+    }
+
+    /// The wallet the receive addresses live in, used to detect incoming payments.
+    pub fn wallet(&self) -> &Wallet {
+        &self.wallet
+    }
+
+    pub async fn new_address<R: PaymentRepository>(
+        &self,
+        pool: &R,
+    ) -> Result<Address<NetworkChecked>, String> {
+        let (rpc, descriptor) = match &self.descriptor {
+            Some((rpc, descriptor)) => (rpc, descriptor),
+            None => {
+                return self
+                    .wallet
+                    .new_address()
+                    .map_err(|e| format!("Failed to get new address: {}", e))
+            }
+        };
+
+        let index = pool
+            .next_derivation_index(descriptor)
+            .await
+            .map_err(|e| format!("Failed to reserve derivation index: {}", e))?;
+
+        let address = rpc
+            .derive_addresses(descriptor, Some([index, index]))
+            .map_err(|e| format!("Failed to derive address {}: {}", index, e))?
+            .pop()
+            .ok_or_else(|| format!("No address derived at index {}", index))?
+            .require_network(CHAIN.network())
+            .map_err(|e| format!("Address is on the wrong network: {}", e))?;
+
+        if index >= self.imported_until.load(Ordering::SeqCst) {
+            self.import_descriptor(rpc, descriptor, index + DESCRIPTOR_LOOKAHEAD)?;
+        }
+
+        Ok(address)
+    }
+
+    /// Imports `descriptor` up to and including `end` into the watch-only wallet, bitcoind only
+    /// accepts a range that covers the previously imported one so it always starts at 0.
+    fn import_descriptor(&self, rpc: &Client, descriptor: &str, end: u32) -> Result<(), String> {
+        // A timestamp of now skips the rescan as these addresses have never been handed out.
+        let res = rpc
+            .import_descriptors(ImportDescriptors {
+                descriptor: descriptor.to_string(),
+                timestamp: Timestamp::Now,
+                active: None,
+                range: Some((0, end as usize)),
+                next_index: None,
+                internal: None,
+                label: None,
+            })
+            .map_err(|e| format!("Failed to import descriptor: {}", e))?;
+
+        if let Some(failure) = res.into_iter().find(|r| !r.success) {
+            return Err(format!(
+                "Failed to import descriptor up to {}: {:?}",
+                end, failure.error
+            ));
+        }
+
+        self.imported_until.fetch_max(end + 1, Ordering::SeqCst);
+
+        Ok(())
+    }
+}
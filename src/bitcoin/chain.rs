@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
 use bitcoincore_rpc::bitcoin::Network;
 
@@ -39,12 +39,12 @@ impl FromStr for Chain {
     }
 }
 
-impl ToString for Chain {
-    fn to_string(&self) -> String {
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Mainnet => "mainnet".to_string(),
-            Self::Testnet => "testnet".to_string(),
-            Self::Regtest => "regtest".to_string(),
+            Self::Mainnet => write!(f, "mainnet"),
+            Self::Testnet => write!(f, "testnet"),
+            Self::Regtest => write!(f, "regtest"),
         }
     }
 }
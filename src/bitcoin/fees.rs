@@ -0,0 +1,189 @@
+use std::{
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tracing::warn;
+
+use crate::bitcoin::wallet::{Wallet, WalletBackend};
+
+const DEFAULT_FEE_CONF_TARGET: u16 = 6;
+const DEFAULT_FEE_CACHE_SECS: u64 = 60;
+const DEFAULT_FALLBACK_FEE_RATE: f64 = 10.0;
+const DEFAULT_NETWORK_FEE_CAP_BTC: f64 = 0.0005;
+
+/// One P2TR key-path input, the P2TR commit output and a change output.
+const COMMIT_TX_VBYTES: u64 = 154;
+/// Non-witness part of the reveal: one P2TR input and the P2TR output holding the inscription.
+const REVEAL_TX_BASE_VBYTES: u64 = 95;
+const INSCRIPTION_CONTENT_TYPE: &str = "text/plain;charset=utf-8";
+const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+
+fn push_len(data_len: usize) -> usize {
+    let opcode_len = match data_len {
+        0..=75 => 1,
+        76..=255 => 2,
+        _ => 3,
+    };
+
+    opcode_len + data_len
+}
+
+fn var_int_len(value: usize) -> usize {
+    match value {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        _ => 5,
+    }
+}
+
+/// Expected virtual size of the commit and reveal transaction inscribing `payload`.
+pub fn inscription_vbytes(payload: &[u8]) -> u64 {
+    let body_len = payload
+        .chunks(MAX_SCRIPT_ELEMENT_SIZE)
+        .map(|chunk| push_len(chunk.len()))
+        .sum::<usize>();
+
+    // <pubkey> OP_CHECKSIG OP_FALSE OP_IF "ord" 1 <content type> 0 <body> OP_ENDIF
+    let script_len = push_len(32)
+        + 1
+        + 2
+        + push_len(3)
+        + push_len(1)
+        + push_len(INSCRIPTION_CONTENT_TYPE.len())
+        + 1
+        + body_len
+        + 1;
+
+    // item count, schnorr signature, tapscript and the control block
+    let witness_len = 1 + push_len(64) + var_int_len(script_len) + script_len + push_len(33);
+
+    // The segwit marker and flag count as witness data as well.
+    let reveal_vbytes = REVEAL_TX_BASE_VBYTES + (witness_len as u64 + 2).div_ceil(4);
+
+    COMMIT_TX_VBYTES + reveal_vbytes
+}
+
+#[derive(Debug, Clone)]
+pub struct NetworkFeeConfig {
+    pub conf_target: u16,
+    pub cache_ttl: Duration,
+    /// Used when bitcoind has not gathered enough data to estimate, in sat/vB.
+    pub fallback_fee_rate: f64,
+    /// Upper bound of the network fee charged per domain, in BTC.
+    pub cap: f64,
+}
+
+impl NetworkFeeConfig {
+    /// Returns `None` unless `NETWORK_FEE_ENABLED` is `true`.
+    pub fn from_env() -> Option<Self> {
+        if env::var("NETWORK_FEE_ENABLED").ok().as_deref() != Some("true") {
+            return None;
+        }
+
+        let parse = |key: &str| env::var(key).ok().and_then(|v| v.parse::<f64>().ok());
+
+        Some(Self {
+            conf_target: env::var("FEE_CONF_TARGET")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_FEE_CONF_TARGET),
+            cache_ttl: Duration::from_secs(
+                env::var("FEE_CACHE_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(DEFAULT_FEE_CACHE_SECS),
+            ),
+            fallback_fee_rate: parse("FALLBACK_FEE_RATE").unwrap_or(DEFAULT_FALLBACK_FEE_RATE),
+            cap: parse("NETWORK_FEE_CAP_BTC").unwrap_or(DEFAULT_NETWORK_FEE_CAP_BTC),
+        })
+    }
+}
+
+/// Prices the network fee of inscribing orders from the wallet's fee estimate, cached for
+/// `cache_ttl` so pricing requests do not hammer bitcoind.
+#[derive(Clone)]
+pub struct FeeEstimator {
+    wallet: Wallet,
+    config: Option<NetworkFeeConfig>,
+    cache: Arc<Mutex<Option<(Instant, f64)>>>,
+}
+
+impl FeeEstimator {
+    pub fn new(wallet: Wallet, config: Option<NetworkFeeConfig>) -> Self {
+        Self {
+            wallet,
+            config,
+            cache: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    /// Current fee rate in sat/vB.
+    pub fn fee_rate(&self) -> Result<f64, String> {
+        let config = self
+            .config
+            .as_ref()
+            .ok_or_else(|| "Network fee estimation is disabled".to_string())?;
+
+        if let Some((fetched_at, fee_rate)) = *self.cache.lock().unwrap() {
+            if fetched_at.elapsed() < config.cache_ttl {
+                return Ok(fee_rate);
+            }
+        }
+
+        let fee_rate = match self.wallet.estimate_fee(config.conf_target) {
+            Ok(Some(fee_rate)) => fee_rate,
+            Ok(None) => {
+                warn!(
+                    "No fee estimate available, falling back to {} sat/vB",
+                    config.fallback_fee_rate
+                );
+                config.fallback_fee_rate
+            }
+            Err(e) => return Err(format!("Failed to estimate fee: {}", e)),
+        };
+
+        *self.cache.lock().unwrap() = Some((Instant::now(), fee_rate));
+
+        Ok(fee_rate)
+    }
+
+    /// Network fee in BTC to inscribe `payloads`, each capped at the configured maximum.
+    /// Always 0 when the dynamic network fee is disabled.
+    pub fn network_fee(&self, payloads: &[String]) -> Result<f64, String> {
+        let config = match &self.config {
+            Some(config) => config,
+            None => return Ok(0f64),
+        };
+
+        let fee_rate = self.fee_rate()?;
+
+        let fee = payloads
+            .iter()
+            .map(|payload| {
+                let fee = inscription_vbytes(payload.as_bytes()) as f64 * fee_rate / 100000000f64;
+                fee.min(config.cap)
+            })
+            .sum::<f64>();
+
+        Ok((fee * 100000000f64).ceil() / 100000000f64)
+    }
+
+    /// Network fee in BTC to inscribe `count` inscriptions of `vbytes` each, for quoting a
+    /// price before the inscriptions exist. Always 0 when the dynamic network fee is disabled.
+    pub fn network_fee_for(&self, vbytes: u64, count: u32) -> Result<f64, String> {
+        let config = match &self.config {
+            Some(config) => config,
+            None => return Ok(0f64),
+        };
+
+        let fee = (vbytes as f64 * self.fee_rate()? / 100000000f64).min(config.cap);
+
+        Ok((fee * count as f64 * 100000000f64).ceil() / 100000000f64)
+    }
+}
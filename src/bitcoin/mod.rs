@@ -1 +1,7 @@
+pub mod address_provider;
 pub mod chain;
+pub mod fees;
+pub mod processor;
+pub mod sweep;
+pub mod transactions;
+pub mod wallet;
@@ -0,0 +1,529 @@
+use bitcoincore_rpc::bitcoin::{address::NetworkChecked, Address};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::bitcoin::wallet::{WalletBackend, WalletError};
+use crate::db::log::LogEvent;
+use crate::db::PaymentRepository;
+use crate::lightning::{msat_to_btc, InvoiceState, LightningBackend};
+use crate::CONFIRMATIONS_REQUIRED;
+
+/// Picks up payments to `addresses`, initiating orders as soon as a transaction shows up and
+/// crediting it once it has `CONFIRMATIONS_REQUIRED` confirmations. Outputs with more than
+/// `max_confirmations` are left alone.
+pub async fn process_onchain_payments<R: PaymentRepository, W: WalletBackend>(
+    pool: &R,
+    wallet: &W,
+    addresses: &[Address<NetworkChecked>],
+    max_confirmations: Option<u32>,
+) -> Result<(), WalletError> {
+    let utxos = wallet.list_unspent(addresses, 0, max_confirmations)?;
+
+    if !utxos.is_empty() {
+        for utxo in &utxos {
+            let address = &utxo.address;
+            let amount = utxo.amount;
+            let txid = &utxo.txid;
+            let confirmations = utxo.confirmations;
+
+            let is_already_processed = pool.is_already_processed(txid, address).await.unwrap()
+                || pool.is_late_payment_recorded(txid, address).await.unwrap();
+
+            if is_already_processed {
+                continue;
+            }
+
+            let payment = match pool.get_payment_by_address(address).await.unwrap() {
+                Some(payment) if !payment.expired => payment,
+                payment => {
+                    // The order expired or was deleted, the funds are still ours so keep
+                    // them attributed to the account that requested the address.
+                    if confirmations < CONFIRMATIONS_REQUIRED {
+                        continue;
+                    }
+
+                    let account_id = match &payment {
+                        Some(payment) => payment.account_id,
+                        None => match pool.get_issued_address_account(address).await {
+                            Ok(Some(account_id)) => account_id,
+                            Ok(None) => continue,
+                            Err(e) => {
+                                error!("Error getting issued address account: {}", e);
+                                continue;
+                            }
+                        },
+                    };
+
+                    if let Some(payment) = &payment {
+                        if let Err(e) = pool.flag_payment_for_refund(&payment.id).await {
+                            error!("Error flagging payment for refund: {}", e);
+                        }
+                    }
+
+                    record_late_payment(
+                        pool,
+                        &account_id,
+                        payment.as_ref().map(|payment| &payment.id),
+                        address,
+                        txid,
+                        amount,
+                    )
+                    .await;
+
+                    continue;
+                }
+            };
+
+            if !payment.initiated {
+                let res = pool.initiate_payment(&payment.id).await;
+
+                if let Err(e) = res {
+                    error!("Error initiating payment: {}", e);
+                    continue;
+                }
+
+                let event = LogEvent::PaymentReceivedUnconfirmed {
+                    payment_id: payment.id,
+                    transaction_id: txid.to_string(),
+                    amount,
+                };
+                let res = pool.add_log(&payment.account_id, &event).await;
+
+                if let Err(e) = res {
+                    error!("Error adding log: {}", e);
+                    continue;
+                }
+            }
+
+            if confirmations < CONFIRMATIONS_REQUIRED {
+                continue;
+            }
+
+            let res = pool.add_payment_received(&payment.id, amount, txid).await;
+
+            if let Err(e) = res {
+                error!("Error adding payment received: {}", e);
+                continue;
+            }
+
+            info!("Payment {} received {}BTC", payment.id, amount);
+
+            let event = LogEvent::PaymentReceivedConfirmed {
+                payment_id: payment.id,
+                transaction_id: txid.to_string(),
+                amount,
+            };
+            let res = pool.add_log(&payment.account_id, &event).await;
+
+            if let Err(e) = res {
+                error!("Error adding log: {}", e);
+                continue;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the invoices of lightning orders. A settled invoice initiates and credits its order
+/// at once, as a settled invoice is final. When the order was initiated by another payment or
+/// expired in the meantime, the invoice is recorded as a late payment instead.
+pub async fn process_lightning_payments<R: PaymentRepository, L: LightningBackend>(
+    pool: &R,
+    lightning: &L,
+) {
+    let pending = match pool.get_pending_lightning_payments().await {
+        Ok(pending) => pending,
+        Err(e) => {
+            error!("Error getting pending lightning payments: {}", e);
+            return;
+        }
+    };
+
+    for payment in pending {
+        let transaction_id = format!("lightning:{}", payment.payment_hash);
+
+        // Invoices stay listed after their order was paid, completed or cancelled.
+        let is_already_processed = pool
+            .is_already_processed(&transaction_id, &payment.address)
+            .await
+            .unwrap()
+            || pool
+                .is_late_payment_recorded(&transaction_id, &payment.address)
+                .await
+                .unwrap();
+
+        if is_already_processed {
+            continue;
+        }
+
+        let amount = match lightning.invoice_state(&payment.payment_hash).await {
+            Ok(InvoiceState::Settled { amount_msat }) => msat_to_btc(amount_msat),
+            Ok(_) => continue,
+            Err(e) => {
+                error!(
+                    "Error getting lightning invoice {}: {}",
+                    payment.payment_hash, e
+                );
+                continue;
+            }
+        };
+
+        if !payment.initiated {
+            match pool
+                .credit_lightning_payment(&payment.payment_id, amount, &transaction_id)
+                .await
+            {
+                Ok(true) => {
+                    info!(
+                        "Payment {} received {}BTC over lightning",
+                        payment.payment_id, amount
+                    );
+
+                    let event = LogEvent::PaymentReceivedConfirmed {
+                        payment_id: payment.payment_id,
+                        transaction_id,
+                        amount,
+                    };
+                    let res = pool.add_log(&payment.account_id, &event).await;
+
+                    if let Err(e) = res {
+                        error!("Error adding log: {}", e);
+                    }
+
+                    continue;
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    error!("Error crediting lightning payment: {}", e);
+                    continue;
+                }
+            }
+
+            // Expired between listing and crediting, treat it like a payment to an expired order.
+            match pool.get_payment(&payment.payment_id).await {
+                Ok(Some(stored)) if stored.expired => {
+                    if let Err(e) = pool.flag_payment_for_refund(&stored.id).await {
+                        error!("Error flagging payment for refund: {}", e);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    error!("Error getting payment: {}", e);
+                    continue;
+                }
+            }
+        }
+
+        record_late_payment(
+            pool,
+            &payment.account_id,
+            Some(&payment.payment_id),
+            &payment.address,
+            &transaction_id,
+            amount,
+        )
+        .await;
+    }
+}
+
+pub async fn record_late_payment<R: PaymentRepository>(
+    pool: &R,
+    account_id: &Uuid,
+    payment_id: Option<&Uuid>,
+    address: &str,
+    txid: &str,
+    amount: f64,
+) {
+    let late_payment_id = match pool
+        .add_late_payment(account_id, payment_id, address, txid, amount)
+        .await
+    {
+        Ok(id) => id,
+        Err(e) => {
+            error!("Error adding late payment: {}", e);
+            return;
+        }
+    };
+
+    info!(
+        "Late payment {} of {}BTC received on {} (payment: {:?})",
+        late_payment_id, amount, address, payment_id
+    );
+
+    let event = LogEvent::LatePaymentReceived {
+        late_payment_id,
+        payment_id: payment_id.copied(),
+        address: address.to_string(),
+        transaction_id: txid.to_string(),
+        amount,
+    };
+    let res = pool.add_log(account_id, &event).await;
+
+    if let Err(e) = res {
+        error!("Error adding log: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::bitcoin::wallet::FakeWallet;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::test_utils::TestServices;
+    use crate::CHAIN;
+
+    fn address(address: &str) -> Address<NetworkChecked> {
+        Address::from_str(address)
+            .unwrap()
+            .require_network(CHAIN.network())
+            .unwrap()
+    }
+
+    async fn process(pool: &InMemoryRepository, wallet: &FakeWallet) {
+        let watched = pool
+            .get_watched_addresses()
+            .await
+            .unwrap()
+            .iter()
+            .map(|watched| address(watched))
+            .collect::<Vec<_>>();
+
+        process_onchain_payments(pool, wallet, &watched, None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn initiates_on_detection_and_credits_once_confirmed() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+
+        process(&pool, &services.wallet).await;
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert!(!payment.initiated);
+
+        let txid = services.wallet.receive(&address(order.address()), 0.0007);
+        process(&pool, &services.wallet).await;
+
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert!(payment.initiated);
+        assert_eq!(payment.received, 0f64);
+
+        services.wallet.mine(CONFIRMATIONS_REQUIRED);
+        process(&pool, &services.wallet).await;
+
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert_eq!(payment.received, 0.0007);
+
+        let logs = pool.logs(&user);
+        assert_eq!(
+            logs[1..],
+            [
+                LogEvent::PaymentReceivedUnconfirmed {
+                    payment_id: order.id(),
+                    transaction_id: txid.clone(),
+                    amount: 0.0007,
+                },
+                LogEvent::PaymentReceivedConfirmed {
+                    payment_id: order.id(),
+                    transaction_id: txid,
+                    amount: 0.0007,
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_already_processed_transactions() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let order = services.order(&pool, &Uuid::new_v4(), &["alice.o"]).await;
+
+        services.wallet.receive(&address(order.address()), 0.0004);
+        services.wallet.mine(CONFIRMATIONS_REQUIRED);
+        process(&pool, &services.wallet).await;
+        process(&pool, &services.wallet).await;
+
+        services.wallet.receive(&address(order.address()), 0.0003);
+        services.wallet.mine(CONFIRMATIONS_REQUIRED);
+        process(&pool, &services.wallet).await;
+        process(&pool, &services.wallet).await;
+
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert_eq!(payment.received, 0.0004 + 0.0003);
+    }
+
+    #[tokio::test]
+    async fn records_payments_to_expired_orders_as_late() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+
+        pool.cancel_payment(&order.id()).await.unwrap();
+
+        let txid = services.wallet.receive(&address(order.address()), 0.0007);
+        process(&pool, &services.wallet).await;
+        assert_eq!(pool.get_late_payments(None).await.unwrap(), vec![]);
+
+        services.wallet.mine(CONFIRMATIONS_REQUIRED);
+        process(&pool, &services.wallet).await;
+        process(&pool, &services.wallet).await;
+
+        let late_payments = pool.get_late_payments(None).await.unwrap();
+        assert_eq!(late_payments.len(), 1);
+        assert_eq!(late_payments[0].account_id, user);
+        assert_eq!(late_payments[0].payment_id, Some(order.id()));
+        assert_eq!(late_payments[0].transaction_id, txid);
+        assert_eq!(late_payments[0].amount, 0.0007);
+
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert!(!payment.initiated);
+        assert_eq!(payment.received, 0f64);
+        assert!(payment.refund_required);
+    }
+
+    #[tokio::test]
+    async fn records_payments_to_deleted_orders_as_late() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+
+        pool.delete_payment(&user, &order.id())
+            .await
+            .unwrap()
+            .unwrap();
+
+        services.wallet.receive(&address(order.address()), 0.0007);
+        services.wallet.mine(CONFIRMATIONS_REQUIRED);
+        process(&pool, &services.wallet).await;
+
+        let late_payments = pool.get_late_payments(None).await.unwrap();
+        assert_eq!(late_payments.len(), 1);
+        assert_eq!(late_payments[0].account_id, user);
+        assert_eq!(late_payments[0].payment_id, None);
+    }
+
+    /// Payment hash of the lightning invoice of `payment_id`.
+    async fn payment_hash(pool: &InMemoryRepository, payment_id: &Uuid) -> String {
+        pool.get_pending_lightning_payments()
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|payment| payment.payment_id == *payment_id)
+            .unwrap()
+            .payment_hash
+    }
+
+    #[tokio::test]
+    async fn credits_settled_invoices_once() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.lightning_order(&pool, &user, &["alice.o"]).await;
+        let payment_hash = payment_hash(&pool, &order.id()).await;
+
+        process_lightning_payments(&pool, &services.lightning).await;
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert!(!payment.initiated);
+
+        assert!(services.lightning.settle(&payment_hash));
+        process_lightning_payments(&pool, &services.lightning).await;
+        process_lightning_payments(&pool, &services.lightning).await;
+
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert!(payment.initiated);
+        assert_eq!(payment.received, 0.0007);
+        assert_eq!(pool.get_late_payments(None).await.unwrap(), vec![]);
+        assert_eq!(
+            pool.logs(&user)[1..],
+            [LogEvent::PaymentReceivedConfirmed {
+                payment_id: order.id(),
+                transaction_id: format!("lightning:{}", payment_hash),
+                amount: 0.0007,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn records_invoices_of_orders_paid_on_chain_as_late() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.lightning_order(&pool, &user, &["alice.o"]).await;
+        let payment_hash = payment_hash(&pool, &order.id()).await;
+
+        services.wallet.receive(&address(order.address()), 0.0007);
+        process(&pool, &services.wallet).await;
+
+        assert!(services.lightning.settle(&payment_hash));
+        process_lightning_payments(&pool, &services.lightning).await;
+        process_lightning_payments(&pool, &services.lightning).await;
+
+        let late_payments = pool.get_late_payments(None).await.unwrap();
+        assert_eq!(late_payments.len(), 1);
+        assert_eq!(late_payments[0].payment_id, Some(order.id()));
+        assert_eq!(
+            late_payments[0].transaction_id,
+            format!("lightning:{}", payment_hash)
+        );
+        assert_eq!(late_payments[0].amount, 0.0007);
+
+        // The order itself is still being fulfilled by the on-chain payment.
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert!(payment.initiated);
+        assert!(!payment.refund_required);
+        assert_eq!(payment.received, 0f64);
+    }
+
+    #[tokio::test]
+    async fn records_invoices_settled_after_the_order_completed_as_late() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.lightning_order(&pool, &user, &["alice.o"]).await;
+        let payment_hash = payment_hash(&pool, &order.id()).await;
+
+        services.wallet.receive(&address(order.address()), 0.0007);
+        process(&pool, &services.wallet).await;
+        pool.complete_payment(&order.id()).await.unwrap();
+
+        assert!(services.lightning.settle(&payment_hash));
+        process_lightning_payments(&pool, &services.lightning).await;
+        process_lightning_payments(&pool, &services.lightning).await;
+
+        let late_payments = pool.get_late_payments(None).await.unwrap();
+        assert_eq!(late_payments.len(), 1);
+        assert_eq!(late_payments[0].payment_id, Some(order.id()));
+        assert_eq!(late_payments[0].amount, 0.0007);
+    }
+
+    #[tokio::test]
+    async fn records_invoices_settled_after_the_order_expired_once() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.lightning_order(&pool, &user, &["alice.o"]).await;
+        let payment_hash = payment_hash(&pool, &order.id()).await;
+
+        pool.cancel_payment(&order.id()).await.unwrap();
+
+        assert!(services.lightning.settle(&payment_hash));
+        process_lightning_payments(&pool, &services.lightning).await;
+        process_lightning_payments(&pool, &services.lightning).await;
+
+        let late_payments = pool.get_late_payments(None).await.unwrap();
+        assert_eq!(late_payments.len(), 1);
+        assert_eq!(late_payments[0].payment_id, Some(order.id()));
+
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert!(!payment.initiated);
+        assert!(payment.expired);
+    }
+}
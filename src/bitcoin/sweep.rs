@@ -0,0 +1,368 @@
+use std::{env, str::FromStr, sync::Arc};
+
+use bitcoincore_rpc::bitcoin::{address::NetworkChecked, Address, Amount};
+use bitcoincore_rpc::{Client, RpcApi};
+use tracing::{debug, error, info};
+
+use crate::bitcoin::address_provider::canonical_descriptor;
+use crate::bitcoin::transactions::sweep_with_fee_rate;
+use crate::bitcoin::wallet::WalletBackend;
+use crate::db::PaymentRepository;
+use crate::CHAIN;
+
+const DEFAULT_SWEEP_INTERVAL_SECS: u64 = 60 * 60;
+/// Used when `SWEEP_FEE_RATE` is not set and the wallet has no fee estimate yet.
+const DEFAULT_SWEEP_FEE_RATE: f64 = 5.0;
+/// Sweeps are not urgent, aim to confirm within a day.
+const SWEEP_FEE_CONF_TARGET: u16 = 144;
+const DEFAULT_SWEEP_MIN_HOT_BALANCE_BTC: f64 = 0.01;
+const DEFAULT_SWEEP_MIN_AMOUNT_BTC: f64 = 0.001;
+const SWEEP_MIN_CONFIRMATIONS: u32 = 6;
+
+pub enum SweepDestination {
+    Address(Address<NetworkChecked>),
+    /// A fresh address is derived from the descriptor for every sweep, through the RPC client it
+    /// was validated with.
+    Descriptor(Arc<Client>, String),
+}
+
+pub struct SweepConfig {
+    pub destination: SweepDestination,
+    pub interval_secs: u64,
+    /// Fee rate in sat/vB, estimated by the wallet when `None`.
+    pub fee_rate: Option<f64>,
+    /// Balance that has to stay in the hot wallet to pay for inscriptions.
+    pub min_hot_balance: Amount,
+    /// Sweeps below this amount are postponed, they would mostly pay fees.
+    pub min_amount: Amount,
+}
+
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+impl SweepConfig {
+    /// Returns `None` when neither `SWEEP_ADDRESS` nor `SWEEP_DESCRIPTOR` is set.
+    ///
+    /// Sweeping is not supported together with `RECEIVE_DESCRIPTOR`: order payments then land in
+    /// the watch-only wallet, which cannot sign, while the sweep spends from the hot wallet. A
+    /// sweep configured in that mode panics instead of silently never moving any funds.
+    pub fn from_env(rpc: &Arc<Client>) -> Option<Self> {
+        let destination = match (env::var("SWEEP_ADDRESS"), env::var("SWEEP_DESCRIPTOR")) {
+            (Ok(address), _) => SweepDestination::Address(
+                Address::from_str(&address)
+                    .expect("SWEEP_ADDRESS is not a valid address")
+                    .require_network(CHAIN.network())
+                    .expect("SWEEP_ADDRESS is not on the configured network"),
+            ),
+            (_, Ok(descriptor)) => SweepDestination::Descriptor(
+                rpc.clone(),
+                canonical_descriptor(rpc, &descriptor).unwrap(),
+            ),
+            _ => return None,
+        };
+
+        if env::var("RECEIVE_DESCRIPTOR").is_ok() {
+            panic!(
+                "SWEEP_ADDRESS and SWEEP_DESCRIPTOR cannot be used with RECEIVE_DESCRIPTOR, \
+                 payments to descriptor addresses are held by the watch-only wallet and have to \
+                 be swept by the holder of its keys"
+            );
+        }
+
+        Some(Self {
+            destination,
+            interval_secs: env_or("SWEEP_INTERVAL_SECS", DEFAULT_SWEEP_INTERVAL_SECS),
+            fee_rate: env::var("SWEEP_FEE_RATE")
+                .ok()
+                .and_then(|fee_rate| fee_rate.parse().ok()),
+            min_hot_balance: Amount::from_btc(env_or(
+                "SWEEP_MIN_HOT_BALANCE_BTC",
+                DEFAULT_SWEEP_MIN_HOT_BALANCE_BTC,
+            ))
+            .unwrap(),
+            min_amount: Amount::from_btc(env_or(
+                "SWEEP_MIN_AMOUNT_BTC",
+                DEFAULT_SWEEP_MIN_AMOUNT_BTC,
+            ))
+            .unwrap(),
+        })
+    }
+
+    async fn destination_address<R: PaymentRepository>(
+        &self,
+        pool: &R,
+    ) -> Result<Address<NetworkChecked>, String> {
+        match &self.destination {
+            SweepDestination::Address(address) => Ok(address.clone()),
+            SweepDestination::Descriptor(rpc, descriptor) => {
+                let index = pool
+                    .next_derivation_index(descriptor)
+                    .await
+                    .map_err(|e| format!("Failed to reserve derivation index: {}", e))?;
+
+                rpc.derive_addresses(descriptor, Some([index, index]))
+                    .map_err(|e| format!("Failed to derive address {}: {}", index, e))?
+                    .pop()
+                    .ok_or_else(|| format!("No address derived at index {}", index))?
+                    .require_network(CHAIN.network())
+                    .map_err(|e| format!("Address is on the wrong network: {}", e))
+            }
+        }
+    }
+
+    fn fee_rate<W: WalletBackend>(&self, wallet: &W) -> Result<f64, String> {
+        if let Some(fee_rate) = self.fee_rate {
+            return Ok(fee_rate);
+        }
+
+        wallet
+            .estimate_fee(SWEEP_FEE_CONF_TARGET)
+            .map(|fee_rate| fee_rate.unwrap_or(DEFAULT_SWEEP_FEE_RATE))
+            .map_err(|e| format!("Failed to estimate sweep fee rate: {}", e))
+    }
+}
+
+/// Moves confirmed order UTXOs of completed payments out of the hot wallet, keeping at least
+/// `min_hot_balance` behind for inscription fees.
+pub async fn sweep<R: PaymentRepository, W: WalletBackend>(
+    wallet: &W,
+    pool: &R,
+    config: &SweepConfig,
+) -> Result<(), String> {
+    let payments = pool
+        .get_completed_payment_addresses()
+        .await
+        .map_err(|e| format!("Failed to get completed payment addresses: {}", e))?;
+
+    let addresses = payments
+        .iter()
+        .filter_map(|(address, _)| Address::from_str(address).ok())
+        .filter_map(|address| address.require_network(CHAIN.network()).ok())
+        .collect::<Vec<_>>();
+
+    if addresses.is_empty() {
+        return Ok(());
+    }
+
+    let mut utxos = wallet
+        .list_unspent(&addresses, SWEEP_MIN_CONFIRMATIONS, None)
+        .map_err(|e| format!("Failed to list unspent order outputs: {}", e))?;
+
+    let balance = wallet
+        .balance()
+        .map_err(|e| format!("Failed to get hot wallet balance: {}", e))
+        .map(to_amount)?;
+
+    let sweepable = match balance.checked_sub(config.min_hot_balance) {
+        Some(sweepable) => sweepable,
+        None => {
+            debug!(
+                "Hot wallet balance {} is below the reserve, skipping sweep",
+                balance
+            );
+            return Ok(());
+        }
+    };
+
+    // Largest first so the reserve is kept with as few inputs as possible.
+    utxos.sort_by(|a, b| b.amount.total_cmp(&a.amount));
+
+    let mut total = Amount::ZERO;
+    let mut selected = Vec::new();
+
+    for utxo in utxos {
+        let amount = to_amount(utxo.amount);
+
+        if total + amount > sweepable {
+            continue;
+        }
+
+        let payment_id = match payments.iter().find(|(a, _)| *a == utxo.address) {
+            Some((_, payment_id)) => *payment_id,
+            None => continue,
+        };
+
+        total += amount;
+        selected.push((utxo, payment_id));
+    }
+
+    if total < config.min_amount {
+        debug!("Only {} sweepable, skipping sweep", total);
+        return Ok(());
+    }
+
+    let fee_rate = config.fee_rate(wallet)?;
+    let destination = config.destination_address(pool).await?;
+    let inputs = selected
+        .iter()
+        .map(|(utxo, _)| utxo.clone())
+        .collect::<Vec<_>>();
+
+    let (txid, fee) = sweep_with_fee_rate(wallet, &inputs, &destination, total.to_btc(), fee_rate)
+        .map_err(|e| format!("Failed to broadcast sweep: {}", e))?;
+
+    info!(
+        "Swept {} from {} outputs to {} in {} (fee {} BTC)",
+        total,
+        selected.len(),
+        destination,
+        txid,
+        fee
+    );
+
+    let inputs = selected
+        .iter()
+        .map(|(utxo, payment_id)| (utxo.txid.clone(), utxo.vout as i32, *payment_id))
+        .collect::<Vec<_>>();
+
+    if let Err(e) = pool
+        .add_sweep(
+            &txid,
+            &destination.to_string(),
+            total.to_btc(),
+            fee,
+            fee_rate,
+            &inputs,
+        )
+        .await
+    {
+        error!(
+            "Sweep {} was broadcast but could not be recorded: {}",
+            txid, e
+        );
+    }
+
+    Ok(())
+}
+
+fn to_amount(btc: f64) -> Amount {
+    Amount::from_sat((btc * 100000000f64).round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::test_utils::TestServices;
+
+    fn config(destination: &str, min_hot_balance: f64, fee_rate: Option<f64>) -> SweepConfig {
+        SweepConfig {
+            destination: SweepDestination::Address(
+                Address::from_str(destination)
+                    .unwrap()
+                    .require_network(CHAIN.network())
+                    .unwrap(),
+            ),
+            interval_secs: DEFAULT_SWEEP_INTERVAL_SECS,
+            fee_rate,
+            min_hot_balance: Amount::from_btc(min_hot_balance).unwrap(),
+            min_amount: Amount::from_btc(0.0001).unwrap(),
+        }
+    }
+
+    /// Places and pays an order for `domain`, completed when `complete` is set.
+    async fn paid_order(
+        pool: &InMemoryRepository,
+        services: &TestServices,
+        domain: &str,
+        complete: bool,
+    ) -> Uuid {
+        let order = services.order(pool, &Uuid::new_v4(), &[domain]).await;
+        let address = Address::from_str(order.address())
+            .unwrap()
+            .require_network(CHAIN.network())
+            .unwrap();
+
+        services.wallet.receive(&address, 0.0007);
+        if complete {
+            pool.complete_payment(&order.id()).await.unwrap();
+        }
+
+        order.id()
+    }
+
+    #[tokio::test]
+    async fn sweeps_confirmed_outputs_of_completed_payments() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let completed = paid_order(&pool, &services, "alice.o", true).await;
+        paid_order(&pool, &services, "bob.o", false).await;
+        let destination = services.target();
+
+        services.wallet.mine(SWEEP_MIN_CONFIRMATIONS - 1);
+        sweep(
+            &services.wallet,
+            &pool,
+            &config(&destination, 0.0, Some(2.0)),
+        )
+        .await
+        .unwrap();
+        assert!(pool.sweeps().is_empty());
+
+        services.wallet.mine(1);
+        sweep(
+            &services.wallet,
+            &pool,
+            &config(&destination, 0.0, Some(2.0)),
+        )
+        .await
+        .unwrap();
+
+        let sweeps = pool.sweeps();
+        assert_eq!(sweeps.len(), 1);
+        assert_eq!(sweeps[0].address, destination);
+        assert_eq!(sweeps[0].amount, 0.0007);
+        assert_eq!(sweeps[0].fee_rate, 2.0);
+        assert!(sweeps[0].fee > 0f64);
+        assert_eq!(sweeps[0].inputs.len(), 1);
+        assert_eq!(sweeps[0].inputs[0].2, completed);
+        assert_eq!(services.wallet.broadcasts().len(), 1);
+
+        // The swept output is spent, there is nothing left to sweep.
+        sweep(
+            &services.wallet,
+            &pool,
+            &config(&destination, 0.0, Some(2.0)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(pool.sweeps().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_hot_wallet_reserve() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        paid_order(&pool, &services, "alice.o", true).await;
+        let destination = services.target();
+
+        services.wallet.mine(SWEEP_MIN_CONFIRMATIONS);
+        sweep(&services.wallet, &pool, &config(&destination, 0.0005, None))
+            .await
+            .unwrap();
+
+        assert!(pool.sweeps().is_empty());
+    }
+
+    #[tokio::test]
+    async fn uses_the_wallet_fee_estimate() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        paid_order(&pool, &services, "alice.o", true).await;
+        let destination = services.target();
+
+        services.wallet.mine(SWEEP_MIN_CONFIRMATIONS);
+        services.wallet.set_fee_rate(Some(3.0));
+        sweep(&services.wallet, &pool, &config(&destination, 0.0, None))
+            .await
+            .unwrap();
+
+        assert_eq!(pool.sweeps()[0].fee_rate, 3.0);
+    }
+}
@@ -0,0 +1,41 @@
+use bitcoincore_rpc::bitcoin::{address::NetworkChecked, Address};
+
+use crate::bitcoin::wallet::{Unspent, WalletBackend, WalletError};
+
+/// Builds, signs and broadcasts a transaction paying `amount` BTC to `address` from the wallet.
+///
+/// The network fee is subtracted from the output, so the recipient receives `amount` minus the
+/// fee at `fee_rate` (sat/vB).
+pub fn send_with_fee_rate<W: WalletBackend>(
+    wallet: &W,
+    address: &Address<NetworkChecked>,
+    amount: f64,
+    fee_rate: f64,
+) -> Result<String, WalletError> {
+    sign_and_send(wallet, &[], address, amount, fee_rate).map(|(txid, _)| txid)
+}
+
+/// Spends exactly `inputs` (worth `amount` BTC in total) to `address`, returning the txid and
+/// the fee in BTC.
+pub fn sweep_with_fee_rate<W: WalletBackend>(
+    wallet: &W,
+    inputs: &[Unspent],
+    address: &Address<NetworkChecked>,
+    amount: f64,
+    fee_rate: f64,
+) -> Result<(String, f64), WalletError> {
+    sign_and_send(wallet, inputs, address, amount, fee_rate)
+}
+
+fn sign_and_send<W: WalletBackend>(
+    wallet: &W,
+    inputs: &[Unspent],
+    address: &Address<NetworkChecked>,
+    amount: f64,
+    fee_rate: f64,
+) -> Result<(String, f64), WalletError> {
+    let signed = wallet.sign_transaction(inputs, address, amount, fee_rate)?;
+    let txid = wallet.send_raw_transaction(&signed.hex)?;
+
+    Ok((txid, signed.fee))
+}
@@ -0,0 +1,279 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use bitcoincore_rpc::bitcoin::{address::NetworkChecked, Address, ScriptBuf};
+
+use super::{SignedTransaction, Unspent, WalletBackend, WalletError, WalletTransaction};
+use crate::CHAIN;
+
+#[derive(Debug, Clone)]
+struct FakeOutput {
+    txid: String,
+    vout: u32,
+    address: String,
+    amount: f64,
+    /// Height of the block that mined the output, `None` while it is in the mempool.
+    height: Option<u32>,
+}
+
+#[derive(Debug, Default)]
+struct FakeChain {
+    height: u32,
+    transactions: u64,
+    addresses: u64,
+    outputs: Vec<FakeOutput>,
+    /// Raw hex of signed transactions to the (txid, vout) outputs they spend.
+    signed: HashMap<String, Vec<(String, u32)>>,
+    /// txid to the raw hex of broadcast transactions.
+    broadcasts: HashMap<String, String>,
+    fee_rate: Option<f64>,
+}
+
+impl FakeChain {
+    fn next_txid(&mut self) -> String {
+        self.transactions += 1;
+        format!("{:064x}", self.transactions)
+    }
+
+    fn confirmations(&self, height: Option<u32>) -> u32 {
+        height.map_or(0, |height| self.height - height + 1)
+    }
+
+    fn confirmed(&self) -> impl Iterator<Item = &FakeOutput> {
+        self.outputs.iter().filter(|output| output.height.is_some())
+    }
+}
+
+/// Rough size of a transaction with `inputs` P2TR inputs and two outputs.
+fn fake_vbytes(inputs: usize) -> u64 {
+    11 + 58 * inputs as u64 + 2 * 43
+}
+
+/// In-memory wallet for tests, the chain only moves through [`receive`] and [`mine`].
+/// Broadcasting a transaction removes the outputs it spends, its own outputs are not tracked.
+///
+/// [`receive`]: FakeWallet::receive
+/// [`mine`]: FakeWallet::mine
+#[derive(Clone, Default)]
+pub struct FakeWallet {
+    chain: Arc<Mutex<FakeChain>>,
+}
+
+impl FakeWallet {
+    /// Puts a payment of `amount` BTC to `address` in the mempool, returning its txid.
+    pub fn receive(&self, address: &Address<NetworkChecked>, amount: f64) -> String {
+        let mut chain = self.chain.lock().unwrap();
+
+        let txid = chain.next_txid();
+        chain.outputs.push(FakeOutput {
+            txid: txid.clone(),
+            vout: 0,
+            address: address.to_string(),
+            amount,
+            height: None,
+        });
+
+        txid
+    }
+
+    /// Mines `blocks` blocks, the first one confirms everything in the mempool.
+    pub fn mine(&self, blocks: u32) {
+        let mut chain = self.chain.lock().unwrap();
+
+        if blocks == 0 {
+            return;
+        }
+
+        let height = chain.height + 1;
+        for output in chain.outputs.iter_mut() {
+            output.height.get_or_insert(height);
+        }
+
+        chain.height += blocks;
+    }
+
+    pub fn set_fee_rate(&self, fee_rate: Option<f64>) {
+        self.chain.lock().unwrap().fee_rate = fee_rate;
+    }
+
+    /// Raw hex of every transaction broadcast through the wallet.
+    pub fn broadcasts(&self) -> Vec<String> {
+        self.chain
+            .lock()
+            .unwrap()
+            .broadcasts
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+impl WalletBackend for FakeWallet {
+    fn new_address(&self) -> Result<Address<NetworkChecked>, WalletError> {
+        let mut chain = self.chain.lock().unwrap();
+
+        chain.addresses += 1;
+        let script = ScriptBuf::from(chain.addresses.to_le_bytes().to_vec());
+
+        Ok(Address::p2wsh(&script, CHAIN.network()))
+    }
+
+    fn list_unspent(
+        &self,
+        addresses: &[Address<NetworkChecked>],
+        min_confirmations: u32,
+        max_confirmations: Option<u32>,
+    ) -> Result<Vec<Unspent>, WalletError> {
+        let chain = self.chain.lock().unwrap();
+        let addresses = addresses
+            .iter()
+            .map(|address| address.to_string())
+            .collect::<Vec<_>>();
+
+        Ok(chain
+            .outputs
+            .iter()
+            .filter(|output| addresses.contains(&output.address))
+            .map(|output| Unspent {
+                txid: output.txid.clone(),
+                vout: output.vout,
+                address: output.address.clone(),
+                amount: output.amount,
+                confirmations: chain.confirmations(output.height),
+            })
+            .filter(|utxo| utxo.confirmations >= min_confirmations)
+            .filter(|utxo| max_confirmations.map_or(true, |max| utxo.confirmations <= max))
+            .collect())
+    }
+
+    fn get_transaction(&self, txid: &str) -> Result<WalletTransaction, WalletError> {
+        let chain = self.chain.lock().unwrap();
+
+        let outputs = chain
+            .outputs
+            .iter()
+            .filter(|output| output.txid == txid)
+            .collect::<Vec<_>>();
+
+        if outputs.is_empty() && !chain.broadcasts.contains_key(txid) {
+            return Err(WalletError::Backend(format!(
+                "transaction {} not found",
+                txid
+            )));
+        }
+
+        Ok(WalletTransaction {
+            txid: txid.to_string(),
+            amount: outputs.iter().map(|output| output.amount).sum(),
+            confirmations: outputs
+                .first()
+                .map_or(0, |output| chain.confirmations(output.height) as i32),
+            hex: chain.broadcasts.get(txid).cloned().unwrap_or_default(),
+        })
+    }
+
+    fn balance(&self) -> Result<f64, WalletError> {
+        let chain = self.chain.lock().unwrap();
+
+        Ok(chain.confirmed().map(|output| output.amount).sum())
+    }
+
+    fn sign_transaction(
+        &self,
+        inputs: &[Unspent],
+        _address: &Address<NetworkChecked>,
+        amount: f64,
+        fee_rate: f64,
+    ) -> Result<SignedTransaction, WalletError> {
+        let mut chain = self.chain.lock().unwrap();
+
+        let spent = if inputs.is_empty() {
+            let mut confirmed = chain.confirmed().collect::<Vec<_>>();
+            confirmed.sort_by(|a, b| b.amount.total_cmp(&a.amount));
+
+            let mut total = 0f64;
+            let mut spent = Vec::new();
+            for output in confirmed {
+                if total >= amount {
+                    break;
+                }
+
+                total += output.amount;
+                spent.push((output.txid.clone(), output.vout));
+            }
+
+            if total < amount {
+                return Err(WalletError::Backend("Insufficient funds".to_string()));
+            }
+
+            spent
+        } else {
+            for input in inputs {
+                let unspent = chain
+                    .confirmed()
+                    .any(|output| output.txid == input.txid && output.vout == input.vout);
+
+                if !unspent {
+                    return Err(WalletError::Backend(format!(
+                        "Input {}:{} is not spendable",
+                        input.txid, input.vout
+                    )));
+                }
+            }
+
+            inputs
+                .iter()
+                .map(|input| (input.txid.clone(), input.vout))
+                .collect()
+        };
+
+        let fee = (fake_vbytes(spent.len()) as f64 * fee_rate).ceil() / 100000000f64;
+        if fee >= amount {
+            return Err(WalletError::Backend(
+                "Amount does not cover the fee".to_string(),
+            ));
+        }
+
+        let hex = format!("{:016x}", chain.signed.len() + 1);
+        chain.signed.insert(hex.clone(), spent);
+
+        Ok(SignedTransaction { hex, fee })
+    }
+
+    fn send_raw_transaction(&self, hex: &str) -> Result<String, WalletError> {
+        let mut chain = self.chain.lock().unwrap();
+
+        let spent = chain
+            .signed
+            .get(hex)
+            .cloned()
+            .ok_or_else(|| WalletError::Backend("Transaction is not signed".to_string()))?;
+
+        let double_spend = spent.iter().any(|(txid, vout)| {
+            !chain
+                .outputs
+                .iter()
+                .any(|output| output.txid == *txid && output.vout == *vout)
+        });
+        if double_spend {
+            return Err(WalletError::Backend(
+                "Transaction spends missing outputs".to_string(),
+            ));
+        }
+
+        chain
+            .outputs
+            .retain(|output| !spent.contains(&(output.txid.clone(), output.vout)));
+
+        let txid = chain.next_txid();
+        chain.broadcasts.insert(txid.clone(), hex.to_string());
+
+        Ok(txid)
+    }
+
+    fn estimate_fee(&self, _conf_target: u16) -> Result<Option<f64>, WalletError> {
+        Ok(self.chain.lock().unwrap().fee_rate)
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+
+use bitcoincore_rpc::bitcoin::{address::NetworkChecked, Address};
+
+#[cfg(test)]
+pub mod fake;
+pub mod rpc;
+
+#[cfg(test)]
+pub use fake::FakeWallet;
+pub use rpc::RpcWallet;
+
+/// An output paying one of the requested addresses that has not been spent yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unspent {
+    pub txid: String,
+    pub vout: u32,
+    pub address: String,
+    /// Amount in BTC.
+    pub amount: f64,
+    pub confirmations: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalletTransaction {
+    pub txid: String,
+    /// Net amount in BTC the transaction moved in or out of the wallet.
+    pub amount: f64,
+    /// Negative when the transaction conflicts with the best chain.
+    pub confirmations: i32,
+    /// Hex encoded raw transaction.
+    pub hex: String,
+}
+
+/// A signed transaction that has not been broadcast yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignedTransaction {
+    /// Hex encoded raw transaction.
+    pub hex: String,
+    /// Fee in BTC.
+    pub fee: f64,
+}
+
+#[derive(Debug)]
+pub enum WalletError {
+    Rpc(bitcoincore_rpc::Error),
+    Backend(String),
+}
+
+impl fmt::Display for WalletError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalletError::Rpc(e) => write!(f, "wallet rpc failed: {}", e),
+            WalletError::Backend(e) => write!(f, "wallet backend error: {}", e),
+        }
+    }
+}
+
+impl From<bitcoincore_rpc::Error> for WalletError {
+    fn from(e: bitcoincore_rpc::Error) -> Self {
+        WalletError::Rpc(e)
+    }
+}
+
+/// The part of a bitcoin wallet that order creation and payment detection rely on.
+pub trait WalletBackend
+where
+    Self: Clone,
+{
+    fn new_address(&self) -> Result<Address<NetworkChecked>, WalletError>;
+
+    fn list_unspent(
+        &self,
+        addresses: &[Address<NetworkChecked>],
+        min_confirmations: u32,
+        max_confirmations: Option<u32>,
+    ) -> Result<Vec<Unspent>, WalletError>;
+
+    fn get_transaction(&self, txid: &str) -> Result<WalletTransaction, WalletError>;
+
+    /// Spendable balance in BTC.
+    fn balance(&self) -> Result<f64, WalletError>;
+
+    /// Builds and signs a transaction paying `amount` BTC to `address` at `fee_rate` (sat/vB), the
+    /// fee is subtracted from the output. Spends exactly `inputs`, or coins picked by the wallet
+    /// when there are none.
+    fn sign_transaction(
+        &self,
+        inputs: &[Unspent],
+        address: &Address<NetworkChecked>,
+        amount: f64,
+        fee_rate: f64,
+    ) -> Result<SignedTransaction, WalletError>;
+
+    /// Broadcasts a signed transaction, returning its txid.
+    fn send_raw_transaction(&self, hex: &str) -> Result<String, WalletError>;
+
+    /// Fee rate in sat/vB to confirm within `conf_target` blocks, `None` when the backend has no
+    /// estimate yet.
+    fn estimate_fee(&self, conf_target: u16) -> Result<Option<f64>, WalletError>;
+}
+
+/// The wallet backing order addresses and payment detection.
+#[derive(Clone)]
+pub enum Wallet {
+    Rpc(RpcWallet),
+    #[cfg(test)]
+    Fake(FakeWallet),
+}
+
+impl WalletBackend for Wallet {
+    fn new_address(&self) -> Result<Address<NetworkChecked>, WalletError> {
+        match self {
+            Self::Rpc(backend) => backend.new_address(),
+            #[cfg(test)]
+            Self::Fake(backend) => backend.new_address(),
+        }
+    }
+
+    fn list_unspent(
+        &self,
+        addresses: &[Address<NetworkChecked>],
+        min_confirmations: u32,
+        max_confirmations: Option<u32>,
+    ) -> Result<Vec<Unspent>, WalletError> {
+        match self {
+            Self::Rpc(backend) => {
+                backend.list_unspent(addresses, min_confirmations, max_confirmations)
+            }
+            #[cfg(test)]
+            Self::Fake(backend) => {
+                backend.list_unspent(addresses, min_confirmations, max_confirmations)
+            }
+        }
+    }
+
+    fn get_transaction(&self, txid: &str) -> Result<WalletTransaction, WalletError> {
+        match self {
+            Self::Rpc(backend) => backend.get_transaction(txid),
+            #[cfg(test)]
+            Self::Fake(backend) => backend.get_transaction(txid),
+        }
+    }
+
+    fn balance(&self) -> Result<f64, WalletError> {
+        match self {
+            Self::Rpc(backend) => backend.balance(),
+            #[cfg(test)]
+            Self::Fake(backend) => backend.balance(),
+        }
+    }
+
+    fn sign_transaction(
+        &self,
+        inputs: &[Unspent],
+        address: &Address<NetworkChecked>,
+        amount: f64,
+        fee_rate: f64,
+    ) -> Result<SignedTransaction, WalletError> {
+        match self {
+            Self::Rpc(backend) => backend.sign_transaction(inputs, address, amount, fee_rate),
+            #[cfg(test)]
+            Self::Fake(backend) => backend.sign_transaction(inputs, address, amount, fee_rate),
+        }
+    }
+
+    fn send_raw_transaction(&self, hex: &str) -> Result<String, WalletError> {
+        match self {
+            Self::Rpc(backend) => backend.send_raw_transaction(hex),
+            #[cfg(test)]
+            Self::Fake(backend) => backend.send_raw_transaction(hex),
+        }
+    }
+
+    fn estimate_fee(&self, conf_target: u16) -> Result<Option<f64>, WalletError> {
+        match self {
+            Self::Rpc(backend) => backend.estimate_fee(conf_target),
+            #[cfg(test)]
+            Self::Fake(backend) => backend.estimate_fee(conf_target),
+        }
+    }
+}
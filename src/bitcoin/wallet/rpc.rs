@@ -0,0 +1,154 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use bitcoincore_rpc::bitcoin::{address::NetworkChecked, Address, Amount, Txid};
+use bitcoincore_rpc::json::{
+    AddressType, CreateRawTransactionInput, EstimateMode, FundRawTransactionOptions,
+};
+use bitcoincore_rpc::{Client, RpcApi};
+use tracing::warn;
+
+use super::{SignedTransaction, Unspent, WalletBackend, WalletError, WalletTransaction};
+use crate::CHAIN;
+
+/// A bitcoind wallet reached over RPC.
+#[derive(Clone)]
+pub struct RpcWallet {
+    rpc: Arc<Client>,
+}
+
+impl RpcWallet {
+    pub fn new(rpc: Arc<Client>) -> Self {
+        Self { rpc }
+    }
+}
+
+impl WalletBackend for RpcWallet {
+    fn new_address(&self) -> Result<Address<NetworkChecked>, WalletError> {
+        self.rpc
+            .get_new_address(None, Some(AddressType::Bech32m))?
+            .require_network(CHAIN.network())
+            .map_err(|e| WalletError::Backend(format!("Address is on the wrong network: {}", e)))
+    }
+
+    fn list_unspent(
+        &self,
+        addresses: &[Address<NetworkChecked>],
+        min_confirmations: u32,
+        max_confirmations: Option<u32>,
+    ) -> Result<Vec<Unspent>, WalletError> {
+        let utxos = self.rpc.list_unspent(
+            Some(min_confirmations as usize),
+            max_confirmations.map(|confirmations| confirmations as usize),
+            Some(addresses.iter().collect::<Vec<_>>().as_slice()),
+            Some(true),
+            None,
+        )?;
+
+        Ok(utxos
+            .into_iter()
+            .filter_map(|utxo| {
+                let address = utxo.address?.require_network(CHAIN.network()).ok()?;
+
+                Some(Unspent {
+                    txid: utxo.txid.to_string(),
+                    vout: utxo.vout,
+                    address: address.to_string(),
+                    amount: utxo.amount.to_btc(),
+                    confirmations: utxo.confirmations,
+                })
+            })
+            .collect())
+    }
+
+    fn get_transaction(&self, txid: &str) -> Result<WalletTransaction, WalletError> {
+        let txid = Txid::from_str(txid)
+            .map_err(|e| WalletError::Backend(format!("Invalid txid {}: {}", txid, e)))?;
+        let transaction = self.rpc.get_transaction(&txid, Some(true))?;
+
+        Ok(WalletTransaction {
+            txid: transaction.info.txid.to_string(),
+            amount: transaction.amount.to_btc(),
+            confirmations: transaction.info.confirmations,
+            hex: hex::encode(transaction.hex),
+        })
+    }
+
+    fn balance(&self) -> Result<f64, WalletError> {
+        Ok(self.rpc.get_balance(None, None)?.to_btc())
+    }
+
+    fn sign_transaction(
+        &self,
+        inputs: &[Unspent],
+        address: &Address<NetworkChecked>,
+        amount: f64,
+        fee_rate: f64,
+    ) -> Result<SignedTransaction, WalletError> {
+        let inputs = inputs
+            .iter()
+            .map(|input| {
+                Ok(CreateRawTransactionInput {
+                    txid: Txid::from_str(&input.txid).map_err(|e| {
+                        WalletError::Backend(format!("Invalid txid {}: {}", input.txid, e))
+                    })?,
+                    vout: input.vout,
+                    sequence: None,
+                })
+            })
+            .collect::<Result<Vec<_>, WalletError>>()?;
+
+        let mut outputs = HashMap::new();
+        outputs.insert(
+            address.to_string(),
+            Amount::from_sat((amount * 100000000f64).round() as u64),
+        );
+
+        let raw = self
+            .rpc
+            .create_raw_transaction_hex(&inputs, &outputs, None, Some(true))?;
+
+        // bitcoind expects the fee rate in BTC/kvB
+        let options = FundRawTransactionOptions {
+            add_inputs: if inputs.is_empty() { None } else { Some(false) },
+            fee_rate: Some(Amount::from_sat((fee_rate * 1000f64).ceil() as u64)),
+            subtract_fee_from_outputs: Some(vec![0]),
+            replaceable: Some(true),
+            ..Default::default()
+        };
+        let funded = self.rpc.fund_raw_transaction(raw, Some(&options), None)?;
+
+        let signed = self
+            .rpc
+            .sign_raw_transaction_with_wallet(&funded.hex, None, None)?;
+        if !signed.complete {
+            return Err(WalletError::Backend(format!(
+                "Failed to sign transaction: {:?}",
+                signed.errors
+            )));
+        }
+
+        Ok(SignedTransaction {
+            hex: hex::encode(signed.hex),
+            fee: funded.fee.to_btc(),
+        })
+    }
+
+    fn send_raw_transaction(&self, hex: &str) -> Result<String, WalletError> {
+        Ok(self.rpc.send_raw_transaction(hex)?.to_string())
+    }
+
+    fn estimate_fee(&self, conf_target: u16) -> Result<Option<f64>, WalletError> {
+        let estimate = self
+            .rpc
+            .estimate_smart_fee(conf_target, Some(EstimateMode::Conservative))?;
+
+        if let Some(errors) = &estimate.errors {
+            warn!("Fee estimation reported {:?}", errors);
+        }
+
+        // estimatesmartfee answers in BTC/kvB
+        Ok(estimate
+            .fee_rate
+            .map(|fee_rate| fee_rate.to_sat() as f64 / 1000f64))
+    }
+}
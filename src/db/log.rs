@@ -1,8 +1,34 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+use poem_openapi::Enum;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Version of the JSON layout of `LogEvent`, bump it when a payload changes incompatibly.
+pub const LOG_EVENT_VERSION: u16 = 1;
+
+#[derive(Debug, Enum, Clone, Copy, PartialEq, Eq, Hash)]
+#[oai(rename_all = "snake_case")]
 pub enum LogTypes {
     PaymentRequested,
     PaymentReceivedUnconfirmed,
     PaymentReceivedConfirmed,
+    PaymentExpired,
+    LatePaymentReceived,
+    LatePaymentReactivated,
+    LatePaymentRefundApproved,
+    RefundRequested,
+    RefundBroadcast,
+    RefundRejected,
+    RecordUpdateRequested,
+    CustodialSigningChanged,
+    DomainTransferInitiated,
+    DomainTransferAccepted,
+    DomainTransferRejected,
+    DomainTransferCancelled,
+    PaymentManuallyInitiated,
+    PaymentManuallyCompleted,
+    PaymentCancelled,
+    InscriptionRequeued,
+    AddressRescanned,
 }
 
 impl From<&str> for LogTypes {
@@ -11,24 +37,268 @@ impl From<&str> for LogTypes {
             "payment_requested" => LogTypes::PaymentRequested,
             "payment_received_unconfirmed" => LogTypes::PaymentReceivedUnconfirmed,
             "payment_received_confirmed" => LogTypes::PaymentReceivedConfirmed,
+            "payment_expired" => LogTypes::PaymentExpired,
+            "late_payment_received" => LogTypes::LatePaymentReceived,
+            "late_payment_reactivated" => LogTypes::LatePaymentReactivated,
+            "late_payment_refund_approved" => LogTypes::LatePaymentRefundApproved,
+            "refund_requested" => LogTypes::RefundRequested,
+            "refund_broadcast" => LogTypes::RefundBroadcast,
+            "refund_rejected" => LogTypes::RefundRejected,
+            "record_update_requested" => LogTypes::RecordUpdateRequested,
+            "custodial_signing_changed" => LogTypes::CustodialSigningChanged,
+            "domain_transfer_initiated" => LogTypes::DomainTransferInitiated,
+            "domain_transfer_accepted" => LogTypes::DomainTransferAccepted,
+            "domain_transfer_rejected" => LogTypes::DomainTransferRejected,
+            "domain_transfer_cancelled" => LogTypes::DomainTransferCancelled,
+            "payment_manually_initiated" => LogTypes::PaymentManuallyInitiated,
+            "payment_manually_completed" => LogTypes::PaymentManuallyCompleted,
+            "payment_cancelled" => LogTypes::PaymentCancelled,
+            "inscription_requeued" => LogTypes::InscriptionRequeued,
+            "address_rescanned" => LogTypes::AddressRescanned,
             _ => panic!("Invalid log type"),
         }
     }
 }
 
-impl Into<&str> for LogTypes {
-    fn into(self) -> &'static str {
-        match self {
+impl From<LogTypes> for &'static str {
+    fn from(value: LogTypes) -> Self {
+        match value {
             LogTypes::PaymentRequested => "payment_requested",
             LogTypes::PaymentReceivedUnconfirmed => "payment_received_unconfirmed",
             LogTypes::PaymentReceivedConfirmed => "payment_received_confirmed",
+            LogTypes::PaymentExpired => "payment_expired",
+            LogTypes::LatePaymentReceived => "late_payment_received",
+            LogTypes::LatePaymentReactivated => "late_payment_reactivated",
+            LogTypes::LatePaymentRefundApproved => "late_payment_refund_approved",
+            LogTypes::RefundRequested => "refund_requested",
+            LogTypes::RefundBroadcast => "refund_broadcast",
+            LogTypes::RefundRejected => "refund_rejected",
+            LogTypes::RecordUpdateRequested => "record_update_requested",
+            LogTypes::CustodialSigningChanged => "custodial_signing_changed",
+            LogTypes::DomainTransferInitiated => "domain_transfer_initiated",
+            LogTypes::DomainTransferAccepted => "domain_transfer_accepted",
+            LogTypes::DomainTransferRejected => "domain_transfer_rejected",
+            LogTypes::DomainTransferCancelled => "domain_transfer_cancelled",
+            LogTypes::PaymentManuallyInitiated => "payment_manually_initiated",
+            LogTypes::PaymentManuallyCompleted => "payment_manually_completed",
+            LogTypes::PaymentCancelled => "payment_cancelled",
+            LogTypes::InscriptionRequeued => "inscription_requeued",
+            LogTypes::AddressRescanned => "address_rescanned",
         }
     }
 }
 
-impl Into<String> for LogTypes {
-    fn into(self) -> String {
-        let s: &'static str = self.into();
+impl From<LogTypes> for String {
+    fn from(value: LogTypes) -> Self {
+        let s: &'static str = value.into();
         s.to_string()
     }
 }
+
+/// Fields of an event that only staff may read, the admin who acted and their internal reason.
+const STAFF_FIELDS: [&str; 2] = ["admin_id", "reason"];
+
+/// A manual change an admin made to an order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaymentOverride {
+    pub payment_id: Uuid,
+    pub admin_id: Uuid,
+    pub reason: String,
+}
+
+/// What a log entry records, one variant per `LogTypes`. Serialized as versioned JSON before it
+/// gets encrypted into the `data` of the entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LogEvent {
+    PaymentRequested {
+        payment_id: Uuid,
+        amount: f64,
+        domains: Vec<String>,
+    },
+    PaymentReceivedUnconfirmed {
+        payment_id: Uuid,
+        transaction_id: String,
+        amount: f64,
+    },
+    PaymentReceivedConfirmed {
+        payment_id: Uuid,
+        transaction_id: String,
+        amount: f64,
+    },
+    PaymentExpired {
+        payment_id: Uuid,
+    },
+    LatePaymentReceived {
+        late_payment_id: Uuid,
+        payment_id: Option<Uuid>,
+        address: String,
+        transaction_id: String,
+        amount: f64,
+    },
+    LatePaymentReactivated {
+        late_payment_id: Uuid,
+        payment_id: Uuid,
+        amount: f64,
+        admin_id: Uuid,
+    },
+    LatePaymentRefundApproved {
+        late_payment_id: Uuid,
+        payment_id: Option<Uuid>,
+        amount: f64,
+        admin_id: Uuid,
+    },
+    RefundRequested {
+        refund_id: Uuid,
+        payment_id: Option<Uuid>,
+        late_payment_id: Option<Uuid>,
+        amount: f64,
+    },
+    RefundBroadcast {
+        refund_id: Uuid,
+        payment_id: Option<Uuid>,
+        transaction_id: String,
+        amount: f64,
+        fee_rate: f64,
+        admin_id: Uuid,
+    },
+    RefundRejected {
+        refund_id: Uuid,
+        payment_id: Option<Uuid>,
+        admin_id: Uuid,
+        reason: String,
+    },
+    RecordUpdateRequested {
+        payment_id: Uuid,
+        domain: String,
+    },
+    CustodialSigningChanged {
+        domain: String,
+        enabled: bool,
+    },
+    DomainTransferInitiated {
+        transfer_id: Uuid,
+        domain: String,
+        from_account_id: Uuid,
+        to_account_id: Option<Uuid>,
+        to_address: Option<String>,
+    },
+    DomainTransferAccepted {
+        transfer_id: Uuid,
+        domain: String,
+        from_account_id: Uuid,
+        accepted_by: Uuid,
+    },
+    DomainTransferRejected {
+        transfer_id: Uuid,
+        domain: String,
+        closed_by: Uuid,
+    },
+    DomainTransferCancelled {
+        transfer_id: Uuid,
+        domain: String,
+        closed_by: Uuid,
+    },
+    PaymentManuallyInitiated(PaymentOverride),
+    PaymentManuallyCompleted(PaymentOverride),
+    PaymentCancelled(PaymentOverride),
+    InscriptionRequeued(PaymentOverride),
+    AddressRescanned {
+        address: String,
+        admin_id: Uuid,
+        reason: String,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct VersionedLogEvent {
+    version: u16,
+    #[serde(flatten)]
+    event: LogEvent,
+}
+
+impl LogEvent {
+    pub fn log_type(&self) -> LogTypes {
+        match self {
+            LogEvent::PaymentRequested { .. } => LogTypes::PaymentRequested,
+            LogEvent::PaymentReceivedUnconfirmed { .. } => LogTypes::PaymentReceivedUnconfirmed,
+            LogEvent::PaymentReceivedConfirmed { .. } => LogTypes::PaymentReceivedConfirmed,
+            LogEvent::PaymentExpired { .. } => LogTypes::PaymentExpired,
+            LogEvent::LatePaymentReceived { .. } => LogTypes::LatePaymentReceived,
+            LogEvent::LatePaymentReactivated { .. } => LogTypes::LatePaymentReactivated,
+            LogEvent::LatePaymentRefundApproved { .. } => LogTypes::LatePaymentRefundApproved,
+            LogEvent::RefundRequested { .. } => LogTypes::RefundRequested,
+            LogEvent::RefundBroadcast { .. } => LogTypes::RefundBroadcast,
+            LogEvent::RefundRejected { .. } => LogTypes::RefundRejected,
+            LogEvent::RecordUpdateRequested { .. } => LogTypes::RecordUpdateRequested,
+            LogEvent::CustodialSigningChanged { .. } => LogTypes::CustodialSigningChanged,
+            LogEvent::DomainTransferInitiated { .. } => LogTypes::DomainTransferInitiated,
+            LogEvent::DomainTransferAccepted { .. } => LogTypes::DomainTransferAccepted,
+            LogEvent::DomainTransferRejected { .. } => LogTypes::DomainTransferRejected,
+            LogEvent::DomainTransferCancelled { .. } => LogTypes::DomainTransferCancelled,
+            LogEvent::PaymentManuallyInitiated(_) => LogTypes::PaymentManuallyInitiated,
+            LogEvent::PaymentManuallyCompleted(_) => LogTypes::PaymentManuallyCompleted,
+            LogEvent::PaymentCancelled(_) => LogTypes::PaymentCancelled,
+            LogEvent::InscriptionRequeued(_) => LogTypes::InscriptionRequeued,
+            LogEvent::AddressRescanned { .. } => LogTypes::AddressRescanned,
+        }
+    }
+
+    /// The payment the event is about, stored in plaintext so a payment's entries can be looked
+    /// up without decrypting the log of the whole account.
+    pub fn payment_id(&self) -> Option<Uuid> {
+        match self {
+            LogEvent::PaymentRequested { payment_id, .. }
+            | LogEvent::PaymentReceivedUnconfirmed { payment_id, .. }
+            | LogEvent::PaymentReceivedConfirmed { payment_id, .. }
+            | LogEvent::PaymentExpired { payment_id }
+            | LogEvent::LatePaymentReactivated { payment_id, .. }
+            | LogEvent::RecordUpdateRequested { payment_id, .. } => Some(*payment_id),
+            LogEvent::LatePaymentReceived { payment_id, .. }
+            | LogEvent::LatePaymentRefundApproved { payment_id, .. }
+            | LogEvent::RefundRequested { payment_id, .. }
+            | LogEvent::RefundBroadcast { payment_id, .. }
+            | LogEvent::RefundRejected { payment_id, .. } => *payment_id,
+            LogEvent::PaymentManuallyInitiated(payment_override)
+            | LogEvent::PaymentManuallyCompleted(payment_override)
+            | LogEvent::PaymentCancelled(payment_override)
+            | LogEvent::InscriptionRequeued(payment_override) => Some(payment_override.payment_id),
+            LogEvent::CustodialSigningChanged { .. }
+            | LogEvent::DomainTransferInitiated { .. }
+            | LogEvent::DomainTransferAccepted { .. }
+            | LogEvent::DomainTransferRejected { .. }
+            | LogEvent::DomainTransferCancelled { .. }
+            | LogEvent::AddressRescanned { .. } => None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&VersionedLogEvent {
+            version: LOG_EVENT_VERSION,
+            event: self.clone(),
+        })
+        .unwrap()
+    }
+
+    /// The JSON of an event without its `STAFF_FIELDS`, for the account the entry belongs to.
+    /// Entries written before events were typed are returned as they are.
+    pub fn redact_json(data: &str) -> String {
+        match serde_json::from_str::<serde_json::Value>(data) {
+            Ok(serde_json::Value::Object(mut fields)) => {
+                for field in STAFF_FIELDS {
+                    fields.remove(field);
+                }
+                serde_json::Value::Object(fields).to_string()
+            }
+            _ => data.to_string(),
+        }
+    }
+
+    /// Entries written before events were typed hold a plain sentence, those don't parse.
+    pub fn from_json(data: &str) -> Option<Self> {
+        serde_json::from_str::<VersionedLogEvent>(data)
+            .ok()
+            .filter(|versioned| versioned.version == LOG_EVENT_VERSION)
+            .map(|versioned| versioned.event)
+    }
+}
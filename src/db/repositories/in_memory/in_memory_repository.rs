@@ -0,0 +1,1472 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{Duration, NaiveDateTime};
+use uuid::Uuid;
+
+use super::{
+    state::{now, InscriptionContent, Log, PrivateKey, State, StoredLoyaltyDiscount, Sweep},
+    InMemoryUnitOfWork,
+};
+use crate::db::{
+    log::LogEvent,
+    repositories::models::{
+        domain_rule::{DomainList, DomainRule},
+        late_payment::{LatePayment, LatePaymentStatus},
+        payment::Payment,
+        refund::{Refund, RefundStatus},
+        transfer::{DomainTransfer, TransferStatus},
+    },
+    traits::{
+        repository::{
+            DomainRegistration, IdempotentResponse, InscriptionRequeue, LatePaymentReactivation,
+            LightningPayment, LogEntry, LogsFilter, LoyaltyDiscount, OwnedDomain,
+            PaymentInscription, PaymentSearch, PaymentsFilter,
+        },
+        SessionRepository,
+    },
+    PaymentRepository,
+};
+use crate::inscription::record::DomainUpdateInscription;
+use crate::LIGHTNING_EXPIRY_GRACE_HOURS;
+
+/// Keeps every table in memory with the same semantics as the Postgres repository, so endpoints
+/// can be exercised without a database. Rows that other services write (sessions, admins, linked
+/// addresses, loyalty discounts and reveal transactions) are seeded through the `add_*` and
+/// `set_reveal_tx` methods.
+#[derive(Clone, Default)]
+pub struct InMemoryRepository {
+    state: Arc<Mutex<State>>,
+}
+
+impl InMemoryRepository {
+    pub fn add_session(&self, token: &str, account_id: &Uuid) {
+        let mut state = self.state.lock().unwrap();
+        state.sessions.insert(token.to_string(), *account_id);
+    }
+
+    pub fn add_admin(&self, account_id: &Uuid) {
+        let mut state = self.state.lock().unwrap();
+        state.admins.insert(*account_id);
+    }
+
+    /// Links a wallet address to an account, used to look up its collections for discounts.
+    pub fn add_address(&self, account_id: &Uuid, address: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.addresses.push((*account_id, address.to_string()));
+    }
+
+    pub fn add_loyalty_discount(
+        &self,
+        collection_type: i16,
+        collection_minimum_owned: Option<f64>,
+        discount: LoyaltyDiscount,
+    ) {
+        let LoyaltyDiscount(collection_id, amount, currency, message, stackable) = discount;
+
+        let mut state = self.state.lock().unwrap();
+        state.loyalty_discounts.push(StoredLoyaltyDiscount {
+            id: Uuid::new_v4(),
+            collection_id,
+            collection_type,
+            collection_minimum_owned,
+            amount,
+            currency,
+            message,
+            stackable,
+        });
+    }
+
+    /// Records the reveal transaction of every inscription of a payment.
+    pub fn set_reveal_tx(&self, payment_id: &Uuid, reveal_tx: &str) {
+        let mut state = self.state.lock().unwrap();
+
+        for content in state.inscription_contents.iter_mut() {
+            if content.payment_id == *payment_id {
+                content.reveal_tx = Some(reveal_tx.to_string());
+            }
+        }
+    }
+
+    /// The sweeps recorded so far.
+    pub fn sweeps(&self) -> Vec<Sweep> {
+        self.state.lock().unwrap().sweeps.clone()
+    }
+
+    /// The events logged for an account in the order they were written.
+    pub fn logs(&self, account_id: &Uuid) -> Vec<LogEvent> {
+        let state = self.state.lock().unwrap();
+
+        state
+            .logs
+            .iter()
+            .filter(|log| log.account_id == *account_id)
+            .filter_map(|log| log.data.as_deref().and_then(LogEvent::from_json))
+            .collect()
+    }
+}
+
+impl PaymentRepository for InMemoryRepository {
+    type UnitOfWork = InMemoryUnitOfWork;
+
+    async fn new() -> Self {
+        Self::default()
+    }
+
+    async fn begin(&self) -> Result<InMemoryUnitOfWork, sqlx::Error> {
+        Ok(InMemoryUnitOfWork::new(self.state.clone()))
+    }
+
+    async fn add_log(&self, account_id: &Uuid, event: &LogEvent) -> Result<(), sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        state.logs.push(Log {
+            id: Uuid::new_v4(),
+            account_id: *account_id,
+            log_type: event.log_type(),
+            payment_id: event.payment_id(),
+            data: Some(event.to_json()),
+            created_at: now(),
+        });
+
+        Ok(())
+    }
+
+    async fn add_payment_received(
+        &self,
+        payment_id: &Uuid,
+        received: f64,
+        transaction_id: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        state
+            .payment_transactions
+            .push((*payment_id, transaction_id.to_string()));
+
+        if let Some(payment) = state.payment_mut(payment_id) {
+            payment.received += received;
+        }
+
+        Ok(())
+    }
+
+    async fn complete_payment(&self, payment_id: &Uuid) -> Result<(), sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(payment) = state.payment_mut(payment_id) {
+            payment.completed = true;
+        }
+
+        Ok(())
+    }
+
+    async fn create_payment_inscription(
+        &self,
+        payment_id: &Uuid,
+        target: &str,
+        contents: &str,
+    ) -> Result<Uuid, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let id = Uuid::new_v4();
+        state.inscription_contents.push(InscriptionContent {
+            id,
+            payment_id: *payment_id,
+            target: target.to_string(),
+            content: contents.to_string(),
+            reveal_tx: None,
+        });
+
+        Ok(id)
+    }
+
+    async fn initiate_payment(&self, payment_id: &Uuid) -> Result<(), sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(payment) = state.payment_mut(payment_id) {
+            payment.initiated = true;
+        }
+
+        Ok(())
+    }
+
+    async fn get_to_be_initiated_addresses(&self) -> Result<Vec<String>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .payments
+            .iter()
+            .filter(|stored| !stored.payment.initiated)
+            .map(|stored| stored.payment.address.clone())
+            .collect())
+    }
+
+    async fn get_watched_addresses(&self) -> Result<Vec<String>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state.issued_addresses.keys().cloned().collect())
+    }
+
+    async fn get_issued_address_account(&self, address: &str) -> Result<Option<Uuid>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .issued_addresses
+            .get(address)
+            .map(|issued| issued.account_id))
+    }
+
+    async fn get_pending_lightning_payments(&self) -> Result<Vec<LightningPayment>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .payments
+            .iter()
+            .filter(|stored| {
+                (!stored.payment.completed && !stored.payment.expired)
+                    || stored.lightning_expires_at.is_some_and(|expires_at| {
+                        expires_at > now() - Duration::hours(LIGHTNING_EXPIRY_GRACE_HOURS as i64)
+                    })
+            })
+            .filter_map(|stored| {
+                Some(LightningPayment {
+                    payment_id: stored.payment.id,
+                    account_id: stored.payment.account_id,
+                    address: stored.payment.address.clone(),
+                    payment_hash: stored.lightning_payment_hash.clone()?,
+                    initiated: stored.payment.initiated,
+                })
+            })
+            .collect())
+    }
+
+    async fn credit_lightning_payment(
+        &self,
+        payment_id: &Uuid,
+        received: f64,
+        transaction_id: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        match state.payment_mut(payment_id) {
+            Some(payment) if !payment.initiated && !payment.expired => {
+                payment.initiated = true;
+                payment.received += received;
+            }
+            _ => return Ok(false),
+        }
+
+        state
+            .payment_transactions
+            .push((*payment_id, transaction_id.to_string()));
+
+        Ok(true)
+    }
+
+    async fn next_derivation_index(&self, descriptor: &str) -> Result<u32, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let next_index = state
+            .derivation_indexes
+            .entry(descriptor.to_string())
+            .or_insert(0);
+        let index = *next_index;
+        *next_index += 1;
+
+        Ok(index)
+    }
+
+    async fn get_to_be_completed_payments(&self) -> Result<Vec<Uuid>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .payments
+            .iter()
+            .map(|stored| &stored.payment)
+            .filter(|payment| {
+                payment.initiated && !payment.completed && payment.received >= payment.amount
+            })
+            .map(|payment| payment.id)
+            .collect())
+    }
+
+    async fn get_payment(&self, payment_id: &Uuid) -> Result<Option<Payment>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state.payment(payment_id).cloned())
+    }
+
+    async fn is_already_processed(
+        &self,
+        transaction_id: &str,
+        address: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .payment_transactions
+            .iter()
+            .any(|(payment_id, processed_transaction_id)| {
+                processed_transaction_id == transaction_id
+                    && state
+                        .payment(payment_id)
+                        .is_some_and(|payment| payment.address == address)
+            }))
+    }
+
+    async fn get_payments(
+        &self,
+        account_id: &Uuid,
+        filter: &PaymentsFilter,
+    ) -> Result<Option<Vec<(Payment, Vec<String>)>>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        let cursor = filter
+            .cursor
+            .and_then(|cursor| state.payment(&cursor))
+            .filter(|payment| payment.account_id == *account_id)
+            .map(|payment| (payment.created_at, payment.id));
+
+        if filter.cursor.is_some() && cursor.is_none() {
+            return Ok(None);
+        }
+
+        let mut payments = state
+            .payments
+            .iter()
+            .map(|stored| &stored.payment)
+            .filter(|payment| payment.account_id == *account_id)
+            .filter(|payment| filter.initiated.map_or(true, |i| payment.initiated == i))
+            .filter(|payment| filter.completed.map_or(true, |c| payment.completed == c))
+            .filter(|payment| filter.expired.map_or(true, |e| payment.expired == e))
+            .filter(|payment| filter.from.map_or(true, |from| payment.created_at >= from))
+            .filter(|payment| filter.to.map_or(true, |to| payment.created_at < to))
+            .filter(|payment| match cursor {
+                Some(cursor) if filter.ascending => (payment.created_at, payment.id) > cursor,
+                Some(cursor) => (payment.created_at, payment.id) < cursor,
+                None => true,
+            })
+            .collect::<Vec<_>>();
+
+        payments.sort_by_key(|payment| (payment.created_at, payment.id));
+        if !filter.ascending {
+            payments.reverse();
+        }
+
+        Ok(Some(
+            payments
+                .into_iter()
+                .take(filter.limit.max(0) as usize)
+                .map(|payment| (payment.clone(), state.inscribed_domains(&payment.id)))
+                .collect(),
+        ))
+    }
+
+    async fn get_payment_by_address(&self, address: &str) -> Result<Option<Payment>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .payments
+            .iter()
+            .map(|stored| &stored.payment)
+            .find(|payment| payment.address == address)
+            .cloned())
+    }
+
+    async fn add_payment_inscription_contents(
+        &self,
+        payment_id: &Uuid,
+        target: &str,
+        contents: &str,
+    ) -> Result<(), sqlx::Error> {
+        self.create_payment_inscription(payment_id, target, contents)
+            .await
+            .map(|_| ())
+    }
+
+    async fn get_payment_inscriptions_content(
+        &self,
+        payment_id: &Uuid,
+    ) -> Result<Option<Vec<(String, String)>>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(Some(
+            state
+                .inscription_contents
+                .iter()
+                .filter(|content| content.payment_id == *payment_id)
+                .map(|content| (content.target.clone(), content.content.clone()))
+                .collect(),
+        ))
+    }
+
+    async fn add_private_key(
+        &self,
+        account_id: &Uuid,
+        payment_inscription_content_id: &Uuid,
+        domain: &str,
+        private_key: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        state.private_keys.push(PrivateKey {
+            account_id: *account_id,
+            payment_inscription_content_id: *payment_inscription_content_id,
+            domain: domain.to_string(),
+            private_key: private_key.to_string(),
+            custodial_signing: false,
+            retired: false,
+        });
+
+        Ok(())
+    }
+
+    async fn get_private_key(
+        &self,
+        account_id: &Uuid,
+        domain: &str,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .private_keys
+            .iter()
+            .find(|key| !key.retired && key.account_id == *account_id && key.domain == domain)
+            .map(|key| key.private_key.clone()))
+    }
+
+    async fn get_owned_domains(
+        &self,
+        account_id: &Uuid,
+    ) -> Result<Vec<(String, bool, Option<String>)>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .domains()
+            .filter(|(key, _, payment)| {
+                payment.initiated && !key.retired && key.account_id == *account_id
+            })
+            .map(|(key, content, payment)| {
+                (
+                    key.domain.clone(),
+                    payment.completed,
+                    content.reveal_tx.clone(),
+                )
+            })
+            .collect())
+    }
+
+    async fn get_owned_domain(
+        &self,
+        account_id: &Uuid,
+        domain: &str,
+    ) -> Result<Option<OwnedDomain>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        let owned = state
+            .domains()
+            .find(|(key, _, payment)| {
+                payment.initiated
+                    && !key.retired
+                    && key.account_id == *account_id
+                    && key.domain == domain
+            })
+            .map(|(key, content, payment)| OwnedDomain {
+                domain: key.domain.clone(),
+                target: content.target.clone(),
+                inscription: content.content.clone(),
+                private_key: key.private_key.clone(),
+                custodial_signing: key.custodial_signing,
+                completed: payment.completed,
+            });
+
+        Ok(owned)
+    }
+
+    async fn set_custodial_signing(
+        &self,
+        account_id: &Uuid,
+        domain: &str,
+        enabled: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let mut updated = false;
+        for key in state.private_keys.iter_mut() {
+            if !key.retired && key.account_id == *account_id && key.domain == domain {
+                key.custodial_signing = enabled;
+                updated = true;
+            }
+        }
+
+        Ok(updated)
+    }
+
+    async fn get_domain_registration(
+        &self,
+        domain: &str,
+    ) -> Result<Option<DomainRegistration>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        let mut registrations = state
+            .domains()
+            .filter(|(key, _, payment)| key.domain == domain && !payment.expired)
+            .collect::<Vec<_>>();
+
+        registrations.sort_by_key(|(_, _, payment)| (!payment.initiated, payment.created_at));
+
+        let mut registration = match registrations.first() {
+            Some((key, content, payment)) => DomainRegistration {
+                domain: key.domain.clone(),
+                target: content.target.clone(),
+                inscription: content.content.clone(),
+                initiated: payment.initiated,
+                completed: payment.completed,
+                reveal_tx: content.reveal_tx.clone(),
+            },
+            None => return Ok(None),
+        };
+
+        let target = state
+            .domain_updates
+            .iter()
+            .filter(|(_, updated)| updated == domain)
+            .filter_map(|(content_id, _)| {
+                state
+                    .inscription_contents
+                    .iter()
+                    .find(|content| content.id == *content_id)
+            })
+            .filter(|content| content.reveal_tx.is_some())
+            .filter(|content| {
+                state
+                    .payment(&content.payment_id)
+                    .map_or(false, |payment| payment.completed)
+            })
+            .rev()
+            .find_map(|content| {
+                content
+                    .content
+                    .parse::<DomainUpdateInscription>()
+                    .ok()
+                    .and_then(|update| update.target().map(str::to_string))
+            });
+
+        if let Some(target) = target {
+            registration.target = target;
+        }
+
+        Ok(Some(registration))
+    }
+
+    async fn get_already_owned_domains(
+        &self,
+        user: &Uuid,
+        domains: &[String],
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .domains()
+            .filter(|(key, _, payment)| {
+                domains.contains(&key.domain)
+                    && (key.account_id != *user || key.retired)
+                    && !payment.expired
+            })
+            .map(|(key, _, _)| key.domain.clone())
+            .collect())
+    }
+
+    async fn expire_old_orders(&self) -> Result<Vec<(Uuid, Uuid)>, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let now = now();
+        let mut expired = Vec::new();
+
+        for stored in state.payments.iter_mut() {
+            let payment = &mut stored.payment;
+
+            if !payment.initiated && !payment.expired && payment.expires_at < now {
+                payment.expired = true;
+                expired.push((payment.id, payment.account_id));
+            }
+        }
+
+        state
+            .domain_reservations
+            .retain(|_, payment_id| !expired.iter().any(|(id, _)| id == payment_id));
+
+        Ok(expired)
+    }
+
+    async fn flag_payment_for_refund(&self, payment_id: &Uuid) -> Result<(), sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(payment) = state.payment_mut(payment_id) {
+            payment.refund_required = true;
+        }
+
+        Ok(())
+    }
+
+    async fn get_loyalty_discounts_for_collections(
+        &self,
+        collections: &[(String, i16, f64)],
+    ) -> Result<Vec<LoyaltyDiscount>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        let mut discounts = HashMap::new();
+
+        for (collection, collection_type, amount_owned) in collections {
+            for discount in state.loyalty_discounts.iter().filter(|discount| {
+                discount.collection_id == *collection
+                    && discount.collection_type == *collection_type
+                    && discount
+                        .collection_minimum_owned
+                        .map_or(true, |minimum| minimum <= *amount_owned)
+            }) {
+                discounts.insert(
+                    discount.id,
+                    LoyaltyDiscount(
+                        discount.collection_id.clone(),
+                        discount.amount,
+                        discount.currency.clone(),
+                        discount.message.clone(),
+                        discount.stackable,
+                    ),
+                );
+            }
+        }
+
+        Ok(discounts.into_values().collect())
+    }
+
+    async fn delete_payment(
+        &self,
+        user: &Uuid,
+        payment_id: &Uuid,
+    ) -> Result<Result<(), ()>, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let deletable = state
+            .payment(payment_id)
+            .is_some_and(|payment| payment.account_id == *user && !payment.initiated);
+
+        if !deletable {
+            return Ok(Err(()));
+        }
+
+        state.delete_payment(payment_id);
+
+        Ok(Ok(()))
+    }
+
+    async fn get_addresses(&self, account_id: &Uuid) -> Result<Vec<String>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .addresses
+            .iter()
+            .filter(|(id, _)| id == account_id)
+            .map(|(_, address)| address.clone())
+            .collect())
+    }
+
+    async fn get_payment_domains(&self, payment_id: &Uuid) -> Result<Vec<String>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state.payment_domains(payment_id))
+    }
+
+    async fn reactivate_payment(&self, payment_id: &Uuid) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        Ok(state.restore_payment(payment_id))
+    }
+
+    async fn is_late_payment_recorded(
+        &self,
+        transaction_id: &str,
+        address: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state.late_payments.iter().any(|late_payment| {
+            late_payment.transaction_id == transaction_id && late_payment.address == address
+        }))
+    }
+
+    async fn add_late_payment(
+        &self,
+        account_id: &Uuid,
+        payment_id: Option<&Uuid>,
+        address: &str,
+        transaction_id: &str,
+        amount: f64,
+    ) -> Result<Uuid, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let id = Uuid::new_v4();
+        let created_at = now();
+        state.late_payments.push(LatePayment {
+            id,
+            account_id: *account_id,
+            payment_id: payment_id.copied(),
+            address: address.to_string(),
+            transaction_id: transaction_id.to_string(),
+            amount,
+            status: LatePaymentStatus::Pending,
+            created_at,
+            updated_at: created_at,
+        });
+
+        Ok(id)
+    }
+
+    async fn get_late_payment(&self, id: &Uuid) -> Result<Option<LatePayment>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .late_payments
+            .iter()
+            .find(|late_payment| late_payment.id == *id)
+            .cloned())
+    }
+
+    async fn get_late_payments(
+        &self,
+        status: Option<LatePaymentStatus>,
+    ) -> Result<Vec<LatePayment>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .late_payments
+            .iter()
+            .rev()
+            .filter(|late_payment| status.map_or(true, |status| late_payment.status == status))
+            .cloned()
+            .collect())
+    }
+
+    async fn set_late_payment_status(
+        &self,
+        id: &Uuid,
+        status: LatePaymentStatus,
+    ) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        match state
+            .late_payments
+            .iter_mut()
+            .find(|late_payment| late_payment.id == *id)
+        {
+            Some(late_payment) if late_payment.status == LatePaymentStatus::Pending => {
+                late_payment.status = status;
+                late_payment.updated_at = now();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn reactivate_late_payment(
+        &self,
+        id: &Uuid,
+    ) -> Result<LatePaymentReactivation, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let late_payment = match state
+            .late_payments
+            .iter()
+            .find(|late_payment| late_payment.id == *id)
+        {
+            Some(late_payment) if late_payment.status == LatePaymentStatus::Pending => {
+                late_payment.clone()
+            }
+            _ => return Ok(LatePaymentReactivation::AlreadyHandled),
+        };
+
+        let payment = match late_payment
+            .payment_id
+            .and_then(|payment_id| state.payment(&payment_id).cloned())
+        {
+            Some(payment) => payment,
+            None => return Ok(LatePaymentReactivation::OrderDeleted),
+        };
+
+        if payment.expired && !state.restore_payment(&payment.id) {
+            return Ok(LatePaymentReactivation::DomainsReserved);
+        }
+
+        state
+            .payment_transactions
+            .push((payment.id, late_payment.transaction_id.clone()));
+
+        if let Some(payment) = state.payment_mut(&payment.id) {
+            payment.received += late_payment.amount;
+        }
+
+        if let Some(late_payment) = state
+            .late_payments
+            .iter_mut()
+            .find(|late_payment| late_payment.id == *id)
+        {
+            late_payment.status = LatePaymentStatus::Reactivated;
+            late_payment.updated_at = now();
+        }
+
+        Ok(LatePaymentReactivation::Reactivated)
+    }
+
+    async fn create_refund(
+        &self,
+        account_id: &Uuid,
+        payment_id: Option<&Uuid>,
+        late_payment_id: Option<&Uuid>,
+        amount: f64,
+        refunded: f64,
+        address: &str,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(payment_id) = payment_id {
+            if (state.refunded_amount(payment_id) - refunded).abs() >= 0.000000005 {
+                return Ok(None);
+            }
+        }
+
+        let open = state.refunds.iter().any(|refund| {
+            refund.status != RefundStatus::Rejected
+                && late_payment_id.is_some()
+                && refund.late_payment_id.as_ref() == late_payment_id
+        });
+
+        if open {
+            return Ok(None);
+        }
+
+        if let Some(late_payment_id) = late_payment_id {
+            match state
+                .late_payments
+                .iter_mut()
+                .find(|late_payment| late_payment.id == *late_payment_id)
+            {
+                Some(late_payment) if late_payment.status != LatePaymentStatus::Reactivated => {
+                    late_payment.status = LatePaymentStatus::Refund;
+                    late_payment.updated_at = now();
+                }
+                _ => return Ok(None),
+            }
+        }
+
+        let id = Uuid::new_v4();
+        let created_at = now();
+        state.refunds.push(Refund {
+            id,
+            account_id: *account_id,
+            payment_id: payment_id.copied(),
+            late_payment_id: late_payment_id.copied(),
+            amount,
+            address: address.to_string(),
+            status: RefundStatus::Requested,
+            reason: None,
+            fee_rate: None,
+            transaction_id: None,
+            created_at,
+            updated_at: created_at,
+        });
+
+        Ok(Some(id))
+    }
+
+    async fn get_refund(&self, id: &Uuid) -> Result<Option<Refund>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .refunds
+            .iter()
+            .find(|refund| refund.id == *id)
+            .cloned())
+    }
+
+    async fn get_refunds(
+        &self,
+        account_id: Option<&Uuid>,
+        status: Option<RefundStatus>,
+    ) -> Result<Vec<Refund>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .refunds
+            .iter()
+            .rev()
+            .filter(|refund| account_id.map_or(true, |id| refund.account_id == *id))
+            .filter(|refund| status.map_or(true, |status| refund.status == status))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_refunded_amount(&self, payment_id: &Uuid) -> Result<f64, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state.refunded_amount(payment_id))
+    }
+
+    async fn claim_refund(&self, id: &Uuid) -> Result<bool, sqlx::Error> {
+        Ok(self.move_refund(id, RefundStatus::Requested, RefundStatus::Processing))
+    }
+
+    async fn release_refund(&self, id: &Uuid) -> Result<(), sqlx::Error> {
+        self.move_refund(id, RefundStatus::Processing, RefundStatus::Requested);
+
+        Ok(())
+    }
+
+    async fn complete_refund(
+        &self,
+        id: &Uuid,
+        transaction_id: &str,
+        fee_rate: f64,
+    ) -> Result<(), sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(refund) = state.refunds.iter_mut().find(|refund| refund.id == *id) {
+            refund.status = RefundStatus::Broadcast;
+            refund.transaction_id = Some(transaction_id.to_string());
+            refund.fee_rate = Some(fee_rate);
+            refund.updated_at = now();
+        }
+
+        Ok(())
+    }
+
+    async fn reject_refund(&self, id: &Uuid, reason: &str) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        match state
+            .refunds
+            .iter_mut()
+            .find(|refund| refund.id == *id && refund.status == RefundStatus::Requested)
+        {
+            Some(refund) => {
+                refund.status = RefundStatus::Rejected;
+                refund.reason = Some(reason.to_string());
+                refund.updated_at = now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn get_completed_payment_addresses(&self) -> Result<Vec<(String, Uuid)>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .payments
+            .iter()
+            .map(|stored| &stored.payment)
+            .filter(|payment| payment.completed)
+            .map(|payment| (payment.address.clone(), payment.id))
+            .collect())
+    }
+
+    async fn add_sweep(
+        &self,
+        transaction_id: &str,
+        address: &str,
+        amount: f64,
+        fee: f64,
+        fee_rate: f64,
+        inputs: &[(String, i32, Uuid)],
+    ) -> Result<Uuid, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        state.sweeps.push(Sweep {
+            transaction_id: transaction_id.to_string(),
+            address: address.to_string(),
+            amount,
+            fee,
+            fee_rate,
+            inputs: inputs.to_vec(),
+        });
+
+        Ok(Uuid::new_v4())
+    }
+
+    async fn create_transfer(
+        &self,
+        domain: &str,
+        from_account_id: &Uuid,
+        to_account_id: Option<&Uuid>,
+        to_address: Option<&str>,
+        inscribe: bool,
+    ) -> Result<Option<DomainTransfer>, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        if state
+            .transfers
+            .iter()
+            .any(|transfer| transfer.domain == domain && transfer.status == TransferStatus::Pending)
+        {
+            return Ok(None);
+        }
+
+        let created_at = now();
+        let transfer = DomainTransfer {
+            id: Uuid::new_v4(),
+            domain: domain.to_string(),
+            from_account_id: *from_account_id,
+            to_account_id: to_account_id.copied(),
+            to_address: to_address.map(str::to_string),
+            status: TransferStatus::Pending,
+            inscribe,
+            payment_id: None,
+            created_at,
+            updated_at: created_at,
+        };
+
+        state.transfers.push(transfer.clone());
+
+        Ok(Some(transfer))
+    }
+
+    async fn get_transfer(&self, id: &Uuid) -> Result<Option<DomainTransfer>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .transfers
+            .iter()
+            .find(|transfer| transfer.id == *id)
+            .cloned())
+    }
+
+    async fn get_transfers(&self, account_id: &Uuid) -> Result<Vec<DomainTransfer>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .transfers
+            .iter()
+            .rev()
+            .filter(|transfer| {
+                transfer.from_account_id == *account_id
+                    || transfer.to_account_id.as_ref() == Some(account_id)
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn close_transfer(&self, id: &Uuid, status: TransferStatus) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        match state
+            .transfers
+            .iter_mut()
+            .find(|transfer| transfer.id == *id && transfer.status == TransferStatus::Pending)
+        {
+            Some(transfer) => {
+                transfer.status = status;
+                transfer.updated_at = now();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn cancel_unpaid_transfers(&self) -> Result<Vec<DomainTransfer>, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let unpaid = state
+            .payments
+            .iter()
+            .filter(|stored| !stored.payment.initiated && stored.payment.expired)
+            .map(|stored| stored.payment.id)
+            .collect::<Vec<_>>();
+
+        let mut cancelled = Vec::new();
+        for transfer in state.transfers.iter_mut() {
+            if transfer.to_address.is_some()
+                && transfer.status == TransferStatus::Accepted
+                && transfer
+                    .payment_id
+                    .is_some_and(|payment_id| unpaid.contains(&payment_id))
+            {
+                transfer.status = TransferStatus::Cancelled;
+                transfer.updated_at = now();
+                cancelled.push(transfer.clone());
+            }
+        }
+
+        Ok(cancelled)
+    }
+
+    async fn retire_transferred_keys(&self) -> Result<Vec<Uuid>, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let completed = state
+            .transfers
+            .iter()
+            .filter(|transfer| {
+                transfer.to_address.is_some() && transfer.status == TransferStatus::Accepted
+            })
+            .filter(|transfer| {
+                transfer.payment_id.is_some_and(|payment_id| {
+                    state
+                        .payment(&payment_id)
+                        .is_some_and(|payment| payment.completed)
+                        && state.inscription_contents.iter().any(|content| {
+                            content.payment_id == payment_id && content.reveal_tx.is_some()
+                        })
+                })
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut retired = Vec::new();
+        for transfer in completed {
+            let mut keys = state
+                .private_keys
+                .iter_mut()
+                .filter(|key| {
+                    !key.retired
+                        && key.account_id == transfer.from_account_id
+                        && key.domain == transfer.domain
+                })
+                .peekable();
+
+            if keys.peek().is_none() {
+                continue;
+            }
+
+            for key in keys {
+                key.retired = true;
+                key.custodial_signing = false;
+            }
+            retired.push(transfer.id);
+        }
+
+        Ok(retired)
+    }
+
+    async fn get_active_domain_rules(
+        &self,
+        domains: &[String],
+    ) -> Result<Vec<DomainRule>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        let now = now();
+
+        Ok(domains
+            .iter()
+            .filter_map(|domain| state.domain_rules.get(domain))
+            .filter(|rule| rule.release_at.map_or(true, |release_at| release_at > now))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_domain_rules(
+        &self,
+        list: Option<DomainList>,
+    ) -> Result<Vec<DomainRule>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .domain_rules
+            .values()
+            .filter(|rule| list.map_or(true, |list| rule.list == list))
+            .cloned()
+            .collect())
+    }
+
+    async fn set_domain_rule(
+        &self,
+        domain: &str,
+        list: DomainList,
+        reason: &str,
+        allocated_account_id: Option<&Uuid>,
+        release_at: Option<NaiveDateTime>,
+    ) -> Result<DomainRule, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let updated_at = now();
+        let created_at = state
+            .domain_rules
+            .get(domain)
+            .map_or(updated_at, |rule| rule.created_at);
+
+        let rule = DomainRule {
+            domain: domain.to_string(),
+            list,
+            reason: reason.to_string(),
+            allocated_account_id: allocated_account_id.copied(),
+            release_at,
+            created_at,
+            updated_at,
+        };
+
+        state.domain_rules.insert(domain.to_string(), rule.clone());
+
+        Ok(rule)
+    }
+
+    async fn delete_domain_rule(&self, domain: &str) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        Ok(state.domain_rules.remove(domain).is_some())
+    }
+
+    async fn get_idempotent_response(
+        &self,
+        account_id: &Uuid,
+        key: &str,
+        retention_hours: i32,
+    ) -> Result<Option<IdempotentResponse>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        let retained_since = now() - Duration::hours(retention_hours as i64);
+
+        Ok(state
+            .idempotency_keys
+            .get(&(*account_id, key.to_string()))
+            .filter(|stored| stored.created_at > retained_since)
+            .and_then(|stored| {
+                Some(IdempotentResponse {
+                    request_hash: stored.request_hash.clone(),
+                    response: stored.response.clone()?,
+                })
+            }))
+    }
+
+    async fn delete_expired_idempotency_keys(
+        &self,
+        retention_hours: i32,
+    ) -> Result<(), sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let retained_since = now() - Duration::hours(retention_hours as i64);
+        state
+            .idempotency_keys
+            .retain(|_, stored| stored.created_at > retained_since);
+
+        Ok(())
+    }
+
+    async fn search_payments(
+        &self,
+        search: &PaymentSearch,
+    ) -> Result<Vec<(Payment, Vec<String>)>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        let mut payments = state
+            .payments
+            .iter()
+            .map(|stored| &stored.payment)
+            .filter(|payment| {
+                search
+                    .account_id
+                    .map_or(true, |account_id| payment.account_id == account_id)
+            })
+            .filter(|payment| {
+                search
+                    .address
+                    .as_ref()
+                    .map_or(true, |address| payment.address == *address)
+            })
+            .filter(|payment| {
+                search.transaction_id.as_ref().map_or(true, |txid| {
+                    state
+                        .payment_transactions
+                        .iter()
+                        .any(|(payment_id, transaction_id)| {
+                            *payment_id == payment.id && transaction_id == txid
+                        })
+                })
+            })
+            .filter(|payment| {
+                search.domain.as_ref().map_or(true, |domain| {
+                    state.payment_domains(&payment.id).contains(domain)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        payments.sort_by_key(|payment| (payment.created_at, payment.id));
+        payments.reverse();
+
+        Ok(payments
+            .into_iter()
+            .take(search.limit.max(0) as usize)
+            .map(|payment| (payment.clone(), state.payment_domains(&payment.id)))
+            .collect())
+    }
+
+    async fn get_payment_transactions(
+        &self,
+        payment_id: &Uuid,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .payment_transactions
+            .iter()
+            .filter(|(id, _)| id == payment_id)
+            .map(|(_, transaction_id)| transaction_id.clone())
+            .collect())
+    }
+
+    async fn get_payment_inscriptions(
+        &self,
+        payment_id: &Uuid,
+    ) -> Result<Vec<PaymentInscription>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state
+            .inscription_contents
+            .iter()
+            .filter(|content| content.payment_id == *payment_id)
+            .map(|content| PaymentInscription {
+                id: content.id,
+                domain: state
+                    .private_keys
+                    .iter()
+                    .find(|key| key.payment_inscription_content_id == content.id)
+                    .map(|key| key.domain.clone()),
+                target: content.target.clone(),
+                content: content.content.clone(),
+                reveal_tx: content.reveal_tx.clone(),
+            })
+            .collect())
+    }
+
+    async fn get_logs(
+        &self,
+        account_id: &Uuid,
+        filter: &LogsFilter,
+    ) -> Result<Option<Vec<LogEntry>>, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        let cursor = filter
+            .cursor
+            .and_then(|cursor| state.logs.iter().find(|log| log.id == cursor))
+            .filter(|log| log.account_id == *account_id)
+            .map(|log| (log.created_at, log.id));
+
+        if filter.cursor.is_some() && cursor.is_none() {
+            return Ok(None);
+        }
+
+        let mut logs = state
+            .logs
+            .iter()
+            .filter(|log| log.account_id == *account_id)
+            .filter(|log| filter.log_types.is_empty() || filter.log_types.contains(&log.log_type))
+            .filter(|log| filter.payment_id.is_none() || log.payment_id == filter.payment_id)
+            .filter(|log| match cursor {
+                Some(cursor) if filter.ascending => (log.created_at, log.id) > cursor,
+                Some(cursor) => (log.created_at, log.id) < cursor,
+                None => true,
+            })
+            .collect::<Vec<_>>();
+
+        logs.sort_by_key(|log| (log.created_at, log.id));
+        if !filter.ascending {
+            logs.reverse();
+        }
+
+        Ok(Some(
+            logs.into_iter()
+                .take(
+                    filter
+                        .limit
+                        .map_or(usize::MAX, |limit| limit.max(0) as usize),
+                )
+                .map(|log| LogEntry {
+                    id: log.id,
+                    log_type: log.log_type,
+                    payment_id: log.payment_id,
+                    data: log.data.clone(),
+                    created_at: log.created_at,
+                })
+                .collect(),
+        ))
+    }
+
+    async fn cancel_payment(&self, payment_id: &Uuid) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        match state.payment_mut(payment_id) {
+            Some(payment) if !payment.completed && !payment.expired => {
+                payment.expired = true;
+                payment.refund_required = payment.received > 0f64;
+            }
+            _ => return Ok(false),
+        }
+
+        state
+            .domain_reservations
+            .retain(|_, reserved_by| reserved_by != payment_id);
+
+        Ok(true)
+    }
+
+    async fn requeue_inscription(
+        &self,
+        payment_id: &Uuid,
+    ) -> Result<InscriptionRequeue, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        if state
+            .inscription_contents
+            .iter()
+            .any(|content| content.payment_id == *payment_id && content.reveal_tx.is_some())
+        {
+            return Ok(InscriptionRequeue::AlreadyRevealed);
+        }
+
+        match state.payment_mut(payment_id) {
+            Some(payment) if payment.initiated && !payment.expired => payment.completed = false,
+            _ => return Ok(InscriptionRequeue::NotInitiated),
+        }
+
+        Ok(InscriptionRequeue::Requeued)
+    }
+}
+
+impl InMemoryRepository {
+    /// Moves a refund from `from` into `to`, returns false when it was not in `from`.
+    fn move_refund(&self, id: &Uuid, from: RefundStatus, to: RefundStatus) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        match state
+            .refunds
+            .iter_mut()
+            .find(|refund| refund.id == *id && refund.status == from)
+        {
+            Some(refund) => {
+                refund.status = to;
+                refund.updated_at = now();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl SessionRepository for InMemoryRepository {
+    async fn get_session(&self, token: &str) -> Result<Uuid, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        state
+            .sessions
+            .get(token)
+            .copied()
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    async fn is_admin(&self, account_id: &Uuid) -> Result<bool, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        Ok(state.admins.contains(account_id))
+    }
+}
@@ -0,0 +1,371 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::{Duration, NaiveDateTime};
+use uuid::Uuid;
+
+use super::state::{
+    now, IdempotencyKey, InscriptionContent, IssuedAddress, Log, PrivateKey, State, StoredPayment,
+};
+use crate::db::{
+    log::LogEvent,
+    repositories::models::{payment::Payment, transfer::TransferStatus},
+    traits::UnitOfWork,
+};
+
+/// Buffers its rows and accepted transfers until `commit`. Domain reservations and idempotency
+/// keys are claimed right away, the way Postgres holds their rows locked until the transaction
+/// ends, and are released again when the unit of work is rolled back or dropped.
+pub struct InMemoryUnitOfWork {
+    state: Arc<Mutex<State>>,
+    payments: Vec<StoredPayment>,
+    issued_addresses: Vec<(String, IssuedAddress)>,
+    inscription_contents: Vec<InscriptionContent>,
+    private_keys: Vec<PrivateKey>,
+    domain_updates: Vec<(Uuid, String)>,
+    logs: Vec<Log>,
+    reserved_domains: Vec<String>,
+    idempotency_keys: Vec<(Uuid, String)>,
+    accepted_transfers: Vec<Uuid>,
+    /// (transfer id, payment id)
+    transfer_payments: Vec<(Uuid, Uuid)>,
+    finished: bool,
+}
+
+impl InMemoryUnitOfWork {
+    pub(super) fn new(state: Arc<Mutex<State>>) -> Self {
+        Self {
+            state,
+            payments: Vec::new(),
+            issued_addresses: Vec::new(),
+            inscription_contents: Vec::new(),
+            private_keys: Vec::new(),
+            domain_updates: Vec::new(),
+            logs: Vec::new(),
+            reserved_domains: Vec::new(),
+            idempotency_keys: Vec::new(),
+            accepted_transfers: Vec::new(),
+            transfer_payments: Vec::new(),
+            finished: false,
+        }
+    }
+
+    fn release(&mut self) {
+        let mut state = self.state.lock().unwrap();
+
+        for domain in self.reserved_domains.drain(..) {
+            state.domain_reservations.remove(&domain);
+        }
+        for key in self.idempotency_keys.drain(..) {
+            state.idempotency_keys.remove(&key);
+        }
+
+        self.finished = true;
+    }
+}
+
+impl UnitOfWork for InMemoryUnitOfWork {
+    async fn create_payment(
+        &mut self,
+        account_id: &Uuid,
+        address: &str,
+        amount: f64,
+        expiration_minutes: i32,
+    ) -> Result<Payment, sqlx::Error> {
+        let created_at = now();
+        let payment = Payment {
+            id: Uuid::new_v4(),
+            account_id: *account_id,
+            address: address.to_string(),
+            amount,
+            received: 0.0,
+            lightning_invoice: None,
+            initiated: false,
+            completed: false,
+            expired: false,
+            refund_required: false,
+            created_at,
+            updated_at: created_at,
+            expires_at: created_at + Duration::minutes(expiration_minutes as i64),
+        };
+
+        self.payments.push(StoredPayment {
+            payment: payment.clone(),
+            lightning_payment_hash: None,
+            lightning_expires_at: None,
+        });
+        self.issued_addresses.push((
+            address.to_string(),
+            IssuedAddress {
+                account_id: *account_id,
+                payment_id: Some(payment.id),
+            },
+        ));
+
+        Ok(payment)
+    }
+
+    async fn set_lightning_invoice(
+        &mut self,
+        payment_id: &Uuid,
+        invoice: &str,
+        payment_hash: &str,
+        expires_at: &NaiveDateTime,
+    ) -> Result<(), sqlx::Error> {
+        if let Some(stored) = self
+            .payments
+            .iter_mut()
+            .find(|stored| stored.payment.id == *payment_id)
+        {
+            stored.payment.lightning_invoice = Some(invoice.to_string());
+            stored.lightning_payment_hash = Some(payment_hash.to_string());
+            stored.lightning_expires_at = Some(*expires_at);
+        }
+
+        Ok(())
+    }
+
+    async fn reserve_domains(
+        &mut self,
+        payment_id: &Uuid,
+        domains: &[String],
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        let conflicts = domains
+            .iter()
+            .filter(|domain| state.domain_reservations.contains_key(*domain))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if conflicts.is_empty() {
+            for domain in domains {
+                state
+                    .domain_reservations
+                    .insert(domain.clone(), *payment_id);
+                self.reserved_domains.push(domain.clone());
+            }
+        }
+
+        Ok(conflicts)
+    }
+
+    async fn create_payment_inscription(
+        &mut self,
+        payment_id: &Uuid,
+        target: &str,
+        contents: &str,
+    ) -> Result<Uuid, sqlx::Error> {
+        let id = Uuid::new_v4();
+
+        self.inscription_contents.push(InscriptionContent {
+            id,
+            payment_id: *payment_id,
+            target: target.to_string(),
+            content: contents.to_string(),
+            reveal_tx: None,
+        });
+
+        Ok(id)
+    }
+
+    async fn add_private_key(
+        &mut self,
+        account_id: &Uuid,
+        payment_inscription_content_id: &Uuid,
+        domain: &str,
+        private_key: &str,
+    ) -> Result<(), sqlx::Error> {
+        self.private_keys.push(PrivateKey {
+            account_id: *account_id,
+            payment_inscription_content_id: *payment_inscription_content_id,
+            domain: domain.to_string(),
+            private_key: private_key.to_string(),
+            custodial_signing: false,
+            retired: false,
+        });
+
+        Ok(())
+    }
+
+    async fn add_domain_update(
+        &mut self,
+        payment_inscription_content_id: &Uuid,
+        domain: &str,
+    ) -> Result<(), sqlx::Error> {
+        self.domain_updates
+            .push((*payment_inscription_content_id, domain.to_string()));
+
+        Ok(())
+    }
+
+    async fn add_log(&mut self, account_id: &Uuid, event: &LogEvent) -> Result<(), sqlx::Error> {
+        self.logs.push(Log {
+            id: Uuid::new_v4(),
+            account_id: *account_id,
+            log_type: event.log_type(),
+            payment_id: event.payment_id(),
+            data: Some(event.to_json()),
+            created_at: now(),
+        });
+
+        Ok(())
+    }
+
+    async fn accept_transfer(&mut self, id: &Uuid) -> Result<bool, sqlx::Error> {
+        let state = self.state.lock().unwrap();
+
+        let transfer = match state
+            .transfers
+            .iter()
+            .find(|transfer| transfer.id == *id && transfer.status == TransferStatus::Pending)
+        {
+            Some(transfer) => transfer,
+            None => return Ok(false),
+        };
+
+        if transfer.to_account_id.is_some()
+            && !state.private_keys.iter().any(|key| {
+                !key.retired
+                    && key.account_id == transfer.from_account_id
+                    && key.domain == transfer.domain
+            })
+        {
+            return Ok(false);
+        }
+
+        drop(state);
+        self.accepted_transfers.push(*id);
+
+        Ok(true)
+    }
+
+    async fn set_transfer_payment(
+        &mut self,
+        id: &Uuid,
+        payment_id: &Uuid,
+    ) -> Result<(), sqlx::Error> {
+        self.transfer_payments.push((*id, *payment_id));
+
+        Ok(())
+    }
+
+    async fn claim_idempotency_key(
+        &mut self,
+        account_id: &Uuid,
+        key: &str,
+        request_hash: &str,
+        retention_hours: i32,
+    ) -> Result<bool, sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+        let id = (*account_id, key.to_string());
+
+        let retained = state.idempotency_keys.get(&id).is_some_and(|stored| {
+            stored.created_at > now() - Duration::hours(retention_hours as i64)
+        });
+
+        if retained {
+            return Ok(false);
+        }
+
+        state.idempotency_keys.insert(
+            id.clone(),
+            IdempotencyKey {
+                request_hash: request_hash.to_string(),
+                response: None,
+                created_at: now(),
+            },
+        );
+        self.idempotency_keys.push(id);
+
+        Ok(true)
+    }
+
+    async fn store_idempotent_response(
+        &mut self,
+        account_id: &Uuid,
+        key: &str,
+        response: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(stored) = state
+            .idempotency_keys
+            .get_mut(&(*account_id, key.to_string()))
+        {
+            stored.response = Some(response.to_string());
+        }
+
+        Ok(())
+    }
+
+    async fn commit(mut self) -> Result<(), sqlx::Error> {
+        let mut state = self.state.lock().unwrap();
+
+        state.payments.append(&mut self.payments);
+        state
+            .inscription_contents
+            .append(&mut self.inscription_contents);
+        state.private_keys.append(&mut self.private_keys);
+        state.domain_updates.append(&mut self.domain_updates);
+        state.logs.append(&mut self.logs);
+        state
+            .issued_addresses
+            .extend(self.issued_addresses.drain(..));
+
+        for id in self.accepted_transfers.drain(..) {
+            let transfer = match state
+                .transfers
+                .iter_mut()
+                .find(|transfer| transfer.id == id)
+            {
+                Some(transfer) => transfer,
+                None => continue,
+            };
+
+            transfer.status = TransferStatus::Accepted;
+            transfer.updated_at = now();
+            let transfer = transfer.clone();
+
+            if let Some(to_account_id) = transfer.to_account_id {
+                for key in state.private_keys.iter_mut().filter(|key| {
+                    !key.retired
+                        && key.account_id == transfer.from_account_id
+                        && key.domain == transfer.domain
+                }) {
+                    key.account_id = to_account_id;
+                    key.custodial_signing = false;
+                }
+            }
+        }
+
+        for (id, payment_id) in self.transfer_payments.drain(..) {
+            if let Some(transfer) = state
+                .transfers
+                .iter_mut()
+                .find(|transfer| transfer.id == id)
+            {
+                transfer.payment_id = Some(payment_id);
+                transfer.updated_at = now();
+            }
+        }
+
+        drop(state);
+        self.finished = true;
+
+        Ok(())
+    }
+
+    async fn rollback(mut self) -> Result<(), sqlx::Error> {
+        self.release();
+
+        Ok(())
+    }
+}
+
+impl Drop for InMemoryUnitOfWork {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.release();
+        }
+    }
+}
@@ -0,0 +1,6 @@
+mod in_memory_repository;
+mod in_memory_unit_of_work;
+mod state;
+
+pub use in_memory_repository::InMemoryRepository;
+pub use in_memory_unit_of_work::InMemoryUnitOfWork;
@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::db::{
+    log::LogTypes,
+    repositories::models::{
+        domain_rule::DomainRule,
+        late_payment::LatePayment,
+        payment::Payment,
+        refund::{Refund, RefundStatus},
+        transfer::DomainTransfer,
+    },
+};
+
+pub(super) fn now() -> NaiveDateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct StoredPayment {
+    pub payment: Payment,
+    pub lightning_payment_hash: Option<String>,
+    pub lightning_expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct InscriptionContent {
+    pub id: Uuid,
+    pub payment_id: Uuid,
+    pub target: String,
+    pub content: String,
+    pub reveal_tx: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct PrivateKey {
+    pub account_id: Uuid,
+    pub payment_inscription_content_id: Uuid,
+    pub domain: String,
+    pub private_key: String,
+    pub custodial_signing: bool,
+    pub retired: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Log {
+    pub id: Uuid,
+    pub account_id: Uuid,
+    pub log_type: LogTypes,
+    pub payment_id: Option<Uuid>,
+    pub data: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct StoredLoyaltyDiscount {
+    pub id: Uuid,
+    pub collection_id: String,
+    pub collection_type: i16,
+    pub collection_minimum_owned: Option<f64>,
+    pub amount: f64,
+    pub currency: String,
+    pub message: String,
+    pub stackable: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct IssuedAddress {
+    pub account_id: Uuid,
+    pub payment_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sweep {
+    pub transaction_id: String,
+    pub address: String,
+    pub amount: f64,
+    pub fee: f64,
+    pub fee_rate: f64,
+    pub inputs: Vec<(String, i32, Uuid)>,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct IdempotencyKey {
+    pub request_hash: String,
+    /// Unset while the request that claimed the key is still running.
+    pub response: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// The tables of the Postgres schema, rows are kept in insertion order.
+#[derive(Debug, Default)]
+pub(super) struct State {
+    pub payments: Vec<StoredPayment>,
+    /// (payment id, transaction id)
+    pub payment_transactions: Vec<(Uuid, String)>,
+    pub inscription_contents: Vec<InscriptionContent>,
+    pub private_keys: Vec<PrivateKey>,
+    /// (payment inscription content id, domain)
+    pub domain_updates: Vec<(Uuid, String)>,
+    pub logs: Vec<Log>,
+    pub loyalty_discounts: Vec<StoredLoyaltyDiscount>,
+    pub addresses: Vec<(Uuid, String)>,
+    pub sessions: HashMap<String, Uuid>,
+    pub admins: HashSet<Uuid>,
+    pub issued_addresses: HashMap<String, IssuedAddress>,
+    pub derivation_indexes: HashMap<String, u32>,
+    /// Domain to the payment reserving it.
+    pub domain_reservations: HashMap<String, Uuid>,
+    pub late_payments: Vec<LatePayment>,
+    pub refunds: Vec<Refund>,
+    pub sweeps: Vec<Sweep>,
+    pub transfers: Vec<DomainTransfer>,
+    pub domain_rules: BTreeMap<String, DomainRule>,
+    pub idempotency_keys: HashMap<(Uuid, String), IdempotencyKey>,
+}
+
+impl State {
+    pub fn payment(&self, id: &Uuid) -> Option<&Payment> {
+        self.payments
+            .iter()
+            .map(|stored| &stored.payment)
+            .find(|payment| payment.id == *id)
+    }
+
+    pub fn payment_mut(&mut self, id: &Uuid) -> Option<&mut Payment> {
+        self.payments
+            .iter_mut()
+            .map(|stored| &mut stored.payment)
+            .find(|payment| payment.id == *id)
+    }
+
+    /// Private keys joined with the inscription contents and payments they belong to.
+    pub fn domains(&self) -> impl Iterator<Item = (&PrivateKey, &InscriptionContent, &Payment)> {
+        self.private_keys.iter().filter_map(|key| {
+            let content = self
+                .inscription_contents
+                .iter()
+                .find(|content| content.id == key.payment_inscription_content_id)?;
+            let payment = self.payment(&content.payment_id)?;
+
+            Some((key, content, payment))
+        })
+    }
+
+    pub fn payment_domains(&self, payment_id: &Uuid) -> Vec<String> {
+        self.domains()
+            .filter(|(_, content, _)| content.payment_id == *payment_id)
+            .map(|(key, _, _)| key.domain.clone())
+            .collect()
+    }
+
+    /// The domain named by the first record of every inscription of the payment, for
+    /// registrations and record updates alike.
+    pub fn inscribed_domains(&self, payment_id: &Uuid) -> Vec<String> {
+        self.inscription_contents
+            .iter()
+            .filter(|content| content.payment_id == *payment_id)
+            .filter_map(|content| content.content.lines().next()?.split(' ').nth(1))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Sum of the refunds of a payment that have not been rejected.
+    pub fn refunded_amount(&self, payment_id: &Uuid) -> f64 {
+        self.refunds
+            .iter()
+            .filter(|refund| refund.payment_id.as_ref() == Some(payment_id))
+            .filter(|refund| refund.status != RefundStatus::Rejected)
+            .map(|refund| refund.amount)
+            .sum()
+    }
+
+    /// Brings an expired payment back and reserves its domains again, returns false without
+    /// changing anything when one of them is reserved by another order.
+    pub fn restore_payment(&mut self, payment_id: &Uuid) -> bool {
+        let domains = self.payment_domains(payment_id);
+
+        if domains
+            .iter()
+            .any(|domain| self.domain_reservations.contains_key(domain))
+        {
+            return false;
+        }
+
+        for domain in domains {
+            self.domain_reservations.insert(domain, *payment_id);
+        }
+
+        if let Some(payment) = self.payment_mut(payment_id) {
+            payment.expired = false;
+            payment.refund_required = false;
+            payment.initiated = true;
+        }
+
+        true
+    }
+
+    /// Removes a payment together with the rows that reference it, like the foreign keys do.
+    pub fn delete_payment(&mut self, id: &Uuid) {
+        let contents = self
+            .inscription_contents
+            .iter()
+            .filter(|content| content.payment_id == *id)
+            .map(|content| content.id)
+            .collect::<Vec<_>>();
+
+        self.payments.retain(|stored| stored.payment.id != *id);
+        self.payment_transactions
+            .retain(|(payment_id, _)| payment_id != id);
+        self.inscription_contents
+            .retain(|content| content.payment_id != *id);
+        self.private_keys
+            .retain(|key| !contents.contains(&key.payment_inscription_content_id));
+        self.domain_reservations
+            .retain(|_, payment_id| payment_id != id);
+
+        for issued in self.issued_addresses.values_mut() {
+            if issued.payment_id.as_ref() == Some(id) {
+                issued.payment_id = None;
+            }
+        }
+        for late_payment in self.late_payments.iter_mut() {
+            if late_payment.payment_id.as_ref() == Some(id) {
+                late_payment.payment_id = None;
+            }
+        }
+        for refund in self.refunds.iter_mut() {
+            if refund.payment_id.as_ref() == Some(id) {
+                refund.payment_id = None;
+            }
+        }
+        for transfer in self.transfers.iter_mut() {
+            if transfer.payment_id.as_ref() == Some(id) {
+                transfer.payment_id = None;
+            }
+        }
+    }
+}
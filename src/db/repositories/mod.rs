@@ -1,4 +1,8 @@
+#[cfg(test)]
+mod in_memory;
 pub mod models;
 mod sqlx_postgresql;
 
+#[cfg(test)]
+pub use in_memory::InMemoryRepository;
 pub use sqlx_postgresql::SqlxPostgresqlRepository as Repository;
@@ -0,0 +1,62 @@
+use chrono::NaiveDateTime;
+use poem_openapi::{Enum, Object};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Enum, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[oai(rename_all = "snake_case")]
+pub enum DomainList {
+    /// Kept for ourselves or for a specific account.
+    Reserved,
+    Blocked,
+    /// Held for a trademark owner until the hold is released.
+    TrademarkHold,
+}
+
+impl From<&str> for DomainList {
+    fn from(value: &str) -> Self {
+        match value {
+            "reserved" => DomainList::Reserved,
+            "blocked" => DomainList::Blocked,
+            "trademark_hold" => DomainList::TrademarkHold,
+            _ => panic!("Invalid domain list"),
+        }
+    }
+}
+
+impl From<DomainList> for &'static str {
+    fn from(value: DomainList) -> Self {
+        match value {
+            DomainList::Reserved => "reserved",
+            DomainList::Blocked => "blocked",
+            DomainList::TrademarkHold => "trademark_hold",
+        }
+    }
+}
+
+#[derive(Debug, Object, Serialize, Clone, PartialEq)]
+pub struct DomainRule {
+    pub domain: String,
+    pub list: DomainList,
+    pub reason: String,
+    /// Account allowed to order a reserved domain.
+    pub allocated_account_id: Option<Uuid>,
+    /// The rule stops applying from this moment on.
+    pub release_at: Option<NaiveDateTime>,
+
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl DomainRule {
+    /// Whether the rule keeps `account_id` from ordering the domain.
+    pub fn blocks(&self, account_id: Option<&Uuid>) -> bool {
+        match self.list {
+            DomainList::Reserved => {
+                self.allocated_account_id.is_none()
+                    || self.allocated_account_id.as_ref() != account_id
+            }
+            DomainList::Blocked | DomainList::TrademarkHold => true,
+        }
+    }
+}
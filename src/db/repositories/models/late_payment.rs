@@ -0,0 +1,51 @@
+use chrono::NaiveDateTime;
+use poem_openapi::{Enum, Object};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Enum, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[oai(rename_all = "snake_case")]
+pub enum LatePaymentStatus {
+    Pending,
+    Reactivated,
+    Refund,
+}
+
+impl From<&str> for LatePaymentStatus {
+    fn from(value: &str) -> Self {
+        match value {
+            "pending" => LatePaymentStatus::Pending,
+            "reactivated" => LatePaymentStatus::Reactivated,
+            "refund" => LatePaymentStatus::Refund,
+            _ => panic!("Invalid late payment status"),
+        }
+    }
+}
+
+impl From<LatePaymentStatus> for &'static str {
+    fn from(value: LatePaymentStatus) -> Self {
+        match value {
+            LatePaymentStatus::Pending => "pending",
+            LatePaymentStatus::Reactivated => "reactivated",
+            LatePaymentStatus::Refund => "refund",
+        }
+    }
+}
+
+/// Funds received on an issued address after its order expired or was deleted.
+#[derive(Debug, Object, Serialize, Clone, PartialEq)]
+pub struct LatePayment {
+    pub id: Uuid,
+    pub account_id: Uuid,
+    /// `None` when the order was deleted before the funds arrived.
+    pub payment_id: Option<Uuid>,
+
+    pub address: String,
+    pub transaction_id: String,
+    pub amount: f64,
+
+    pub status: LatePaymentStatus,
+
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
@@ -1 +1,5 @@
+pub mod domain_rule;
+pub mod late_payment;
 pub mod payment;
+pub mod refund;
+pub mod transfer;
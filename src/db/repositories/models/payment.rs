@@ -11,10 +11,15 @@ pub struct Payment {
     pub address: String,
     pub amount: f64,
     pub received: f64,
+    /// BOLT11 invoice when the order can also be paid over lightning.
+    pub lightning_invoice: Option<String>,
 
     pub initiated: bool,
     pub completed: bool,
+    pub expired: bool,
+    pub refund_required: bool,
 
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
 }
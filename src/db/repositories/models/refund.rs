@@ -0,0 +1,57 @@
+use chrono::NaiveDateTime;
+use poem_openapi::{Enum, Object};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Enum, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[oai(rename_all = "snake_case")]
+pub enum RefundStatus {
+    Requested,
+    /// Claimed by an admin approval, the transaction is being built and broadcast.
+    Processing,
+    Broadcast,
+    Rejected,
+}
+
+impl From<&str> for RefundStatus {
+    fn from(value: &str) -> Self {
+        match value {
+            "requested" => RefundStatus::Requested,
+            "processing" => RefundStatus::Processing,
+            "broadcast" => RefundStatus::Broadcast,
+            "rejected" => RefundStatus::Rejected,
+            _ => panic!("Invalid refund status"),
+        }
+    }
+}
+
+impl From<RefundStatus> for &'static str {
+    fn from(value: RefundStatus) -> Self {
+        match value {
+            RefundStatus::Requested => "requested",
+            RefundStatus::Processing => "processing",
+            RefundStatus::Broadcast => "broadcast",
+            RefundStatus::Rejected => "rejected",
+        }
+    }
+}
+
+#[derive(Debug, Object, Serialize, Clone, PartialEq)]
+pub struct Refund {
+    pub id: Uuid,
+    pub account_id: Uuid,
+    pub payment_id: Option<Uuid>,
+    pub late_payment_id: Option<Uuid>,
+
+    pub amount: f64,
+    pub address: String,
+
+    pub status: RefundStatus,
+    pub reason: Option<String>,
+    /// Fee rate in sat/vB the refund transaction was built with.
+    pub fee_rate: Option<f64>,
+    pub transaction_id: Option<String>,
+
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
@@ -0,0 +1,55 @@
+use chrono::NaiveDateTime;
+use poem_openapi::{Enum, Object};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Enum, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[oai(rename_all = "snake_case")]
+pub enum TransferStatus {
+    Pending,
+    Accepted,
+    Rejected,
+    Cancelled,
+}
+
+impl From<&str> for TransferStatus {
+    fn from(value: &str) -> Self {
+        match value {
+            "pending" => TransferStatus::Pending,
+            "accepted" => TransferStatus::Accepted,
+            "rejected" => TransferStatus::Rejected,
+            "cancelled" => TransferStatus::Cancelled,
+            _ => panic!("Invalid transfer status"),
+        }
+    }
+}
+
+impl From<TransferStatus> for &'static str {
+    fn from(value: TransferStatus) -> Self {
+        match value {
+            TransferStatus::Pending => "pending",
+            TransferStatus::Accepted => "accepted",
+            TransferStatus::Rejected => "rejected",
+            TransferStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+#[derive(Debug, Object, Serialize, Clone, PartialEq)]
+pub struct DomainTransfer {
+    pub id: Uuid,
+    pub domain: String,
+    pub from_account_id: Uuid,
+    /// Set for transfers to another Xiler account.
+    pub to_account_id: Option<Uuid>,
+    /// Set for transfers to an external address.
+    pub to_address: Option<String>,
+
+    pub status: TransferStatus,
+    /// Whether a `TARGET` update inscription is created once the transfer is accepted.
+    pub inscribe: bool,
+    pub payment_id: Option<Uuid>,
+
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
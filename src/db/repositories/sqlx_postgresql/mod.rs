@@ -1,3 +1,5 @@
 mod sqlx_postgresql_repository;
+mod sqlx_postgresql_unit_of_work;
 
 pub use sqlx_postgresql_repository::SqlxPostgresqlRepository;
+pub use sqlx_postgresql_unit_of_work::SqlxPostgresqlUnitOfWork;
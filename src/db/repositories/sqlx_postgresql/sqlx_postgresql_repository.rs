@@ -1,17 +1,34 @@
 use std::collections::HashMap;
 
+use chrono::NaiveDateTime;
 use sqlx::PgPool;
 use tracing::{debug, error};
 use uuid::Uuid;
 
+use super::SqlxPostgresqlUnitOfWork;
 use crate::{
     db::{
-        log::LogTypes,
-        repositories::models::payment::Payment,
-        traits::{repository::LoyaltyDiscount, SessionRepository},
+        log::LogEvent,
+        repositories::models::{
+            domain_rule::{DomainList, DomainRule},
+            late_payment::{LatePayment, LatePaymentStatus},
+            payment::Payment,
+            refund::{Refund, RefundStatus},
+            transfer::{DomainTransfer, TransferStatus},
+        },
+        traits::{
+            repository::{
+                DomainRegistration, IdempotentResponse, InscriptionRequeue,
+                LatePaymentReactivation, LightningPayment, LogEntry, LogsFilter, LoyaltyDiscount,
+                OwnedDomain, PaymentInscription, PaymentSearch, PaymentsFilter,
+            },
+            SessionRepository,
+        },
         PaymentRepository,
     },
+    inscription::record::DomainUpdateInscription,
     utils::encryption::{decrypt_string, encrypt_string},
+    LIGHTNING_EXPIRY_GRACE_HOURS,
 };
 
 #[derive(Clone)]
@@ -19,7 +36,92 @@ pub struct SqlxPostgresqlRepository {
     pool: PgPool,
 }
 
+impl SqlxPostgresqlRepository {
+    /// Applies the migrations in `migrations/` that have not run against the database yet.
+    ///
+    /// The `query!` macros check against a database with these migrations applied, regenerate the
+    /// offline query data in `.sqlx/` with `cargo sqlx prepare` after changing a query or migration.
+    pub async fn migrate(&self) -> Result<(), sqlx::migrate::MigrateError> {
+        debug!("[DB] Running migrations");
+        let res = sqlx::migrate!().run(&self.pool).await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to run migrations: {}", e);
+            return Err(e);
+        }
+
+        debug!("[DB] Migrations are up to date");
+
+        Ok(())
+    }
+
+    /// Brings an expired payment back within `tx` and reserves its domains again. Returns false
+    /// when one of them has been reserved by another order in the meantime, the caller then has
+    /// to roll `tx` back.
+    async fn restore_payment(
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        payment_id: &Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"SELECT private_keys.domain FROM private_keys
+            INNER JOIN payment_inscription_contents ON payment_inscription_contents.id = private_keys.payment_inscription_content_id
+                WHERE payment_inscription_contents.payment_id = $1;"#,
+            payment_id
+        )
+        .fetch_all(&mut **tx)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get domains of payment {}", payment_id);
+            return Err(e);
+        }
+
+        let domains = res
+            .unwrap()
+            .into_iter()
+            .map(|row| row.domain)
+            .collect::<Vec<_>>();
+
+        let res = sqlx::query!(
+            r#"INSERT INTO domain_reservations (domain, payment_id) SELECT UNNEST($1::TEXT[]), $2 ON CONFLICT (domain) DO NOTHING RETURNING domain;"#,
+            &domains,
+            payment_id
+        )
+        .fetch_all(&mut **tx)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to reserve domains of payment {}", payment_id);
+            return Err(e);
+        }
+
+        if res.unwrap().len() != domains.len() {
+            debug!(
+                "[DB] Domains of payment {} have been reserved by another order",
+                payment_id
+            );
+            return Ok(false);
+        }
+
+        let res = sqlx::query!(
+            r#"UPDATE payments SET expired = FALSE, refund_required = FALSE, initiated = TRUE WHERE id = $1;"#,
+            payment_id
+        )
+        .execute(&mut **tx)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to reactivate payment {}", payment_id);
+            return Err(e);
+        }
+
+        Ok(true)
+    }
+}
+
 impl PaymentRepository for SqlxPostgresqlRepository {
+    type UnitOfWork = SqlxPostgresqlUnitOfWork;
+
     async fn new() -> Self {
         let url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
 
@@ -30,30 +132,31 @@ impl PaymentRepository for SqlxPostgresqlRepository {
         Self { pool }
     }
 
-    async fn add_log(
-        &self,
-        account_id: &Uuid,
-        log_type: LogTypes,
-        log_data: Option<&str>,
-    ) -> Result<(), sqlx::Error> {
-        debug!(
-            "[DB] Adding log {} {:?} {:?}",
-            account_id, log_type, log_data
-        );
-        let (log_data, encryption_method) = match log_data {
-            Some(log_data) => {
-                let (log_data, encryption_method) = encrypt_string(log_data);
-                (Some(log_data), Some(encryption_method as i16))
-            }
-            None => (None, None),
-        };
-        let log_type: &str = log_type.into();
+    async fn begin(&self) -> Result<SqlxPostgresqlUnitOfWork, sqlx::Error> {
+        debug!("[DB] Beginning unit of work");
+        let res = self.pool.begin().await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to begin unit of work: {}", e);
+            return Err(e);
+        }
+
+        Ok(SqlxPostgresqlUnitOfWork::new(res.unwrap()))
+    }
+
+    async fn add_log(&self, account_id: &Uuid, event: &LogEvent) -> Result<(), sqlx::Error> {
+        debug!("[DB] Adding log {} {:?}", account_id, event);
+
+        let log_type: &str = event.log_type().into();
+        let payment_id = event.payment_id();
+        let (log_data, encryption_method) = encrypt_string(&event.to_json());
         let res = sqlx::query!(
-            r#"INSERT INTO logs (account_id, action, data, encryption_method) VALUES ($1, $2, $3, $4);"#,
+            r#"INSERT INTO logs (account_id, action, payment_id, data, encryption_method) VALUES ($1, $2, $3, $4, $5);"#,
             account_id,
             log_type,
+            payment_id,
             log_data,
-            encryption_method
+            encryption_method as i16
         )
         .execute(&self.pool)
         .await;
@@ -141,36 +244,6 @@ impl PaymentRepository for SqlxPostgresqlRepository {
         Ok(())
     }
 
-    async fn create_payment(
-        &self,
-        account_id: &Uuid,
-        address: &str,
-        amount: f64,
-    ) -> Result<Uuid, sqlx::Error> {
-        debug!("[DB] Creating payment for account {}", account_id);
-
-        let res = sqlx::query!(
-            r#"INSERT INTO payments (account_id, address, amount) VALUES ($1, $2, $3) RETURNING id;"#,
-            account_id,
-            address,
-            amount
-        );
-        let res = res.fetch_one(&self.pool).await;
-
-        if let Err(e) = res {
-            error!("[DB] Failed to create payment for account {}", account_id);
-            return Err(e);
-        }
-
-        let payment_id = res.unwrap().id;
-
-        debug!(
-            "[DB] Created payment {} for account {}",
-            payment_id, account_id
-        );
-        Ok(payment_id)
-    }
-
     async fn create_payment_inscription(
         &self,
         payment_id: &Uuid,
@@ -203,7 +276,7 @@ impl PaymentRepository for SqlxPostgresqlRepository {
                     "[DB] Failed to create payment inscription {} {} {}",
                     payment_id, target, contents
                 );
-                return Err(e);
+                Err(e)
             }
         }
     }
@@ -252,6 +325,167 @@ impl PaymentRepository for SqlxPostgresqlRepository {
         Ok(payments)
     }
 
+    async fn get_watched_addresses(&self) -> Result<Vec<String>, sqlx::Error> {
+        debug!("[DB] Getting watched addresses");
+
+        let res = sqlx::query!(r#"SELECT address FROM issued_addresses;"#)
+            .fetch_all(&self.pool)
+            .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get watched addresses");
+            return Err(e);
+        }
+
+        let addresses = res
+            .unwrap()
+            .into_iter()
+            .map(|row| row.address)
+            .collect::<Vec<_>>();
+
+        debug!("[DB] Got {} watched addresses", addresses.len());
+
+        Ok(addresses)
+    }
+
+    async fn get_issued_address_account(&self, address: &str) -> Result<Option<Uuid>, sqlx::Error> {
+        debug!("[DB] Getting account of issued address {}", address);
+
+        let res = sqlx::query!(
+            r#"SELECT account_id FROM issued_addresses WHERE address = $1;"#,
+            address
+        )
+        .fetch_optional(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get account of issued address {}", address);
+            return Err(e);
+        }
+
+        Ok(res.unwrap().map(|row| row.account_id))
+    }
+
+    async fn get_pending_lightning_payments(&self) -> Result<Vec<LightningPayment>, sqlx::Error> {
+        debug!("[DB] Getting pending lightning payments");
+
+        let res = sqlx::query!(
+            r#"SELECT id, account_id, address, lightning_payment_hash as "lightning_payment_hash!", initiated FROM payments
+            WHERE lightning_payment_hash IS NOT NULL
+            AND ((completed = FALSE AND expired = FALSE) OR lightning_expires_at > NOW() - make_interval(hours => $1));"#,
+            LIGHTNING_EXPIRY_GRACE_HOURS as i32
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get pending lightning payments");
+            return Err(e);
+        }
+
+        let payments = res
+            .unwrap()
+            .into_iter()
+            .map(|row| LightningPayment {
+                payment_id: row.id,
+                account_id: row.account_id,
+                address: row.address,
+                payment_hash: row.lightning_payment_hash,
+                initiated: row.initiated,
+            })
+            .collect::<Vec<_>>();
+
+        debug!("[DB] Got pending lightning payments {:?}", payments);
+
+        Ok(payments)
+    }
+
+    async fn credit_lightning_payment(
+        &self,
+        payment_id: &Uuid,
+        received: f64,
+        transaction_id: &str,
+    ) -> Result<bool, sqlx::Error> {
+        debug!(
+            "[DB] Crediting lightning payment {} {} {}",
+            payment_id, received, transaction_id
+        );
+
+        let mut tx = self.pool.begin().await?;
+
+        let res = sqlx::query!(
+            r#"UPDATE payments SET initiated = TRUE, received = received + $2 WHERE id = $1 AND initiated = FALSE AND expired = FALSE RETURNING id;"#,
+            payment_id,
+            received
+        )
+        .fetch_optional(&mut *tx)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to credit lightning payment {}", payment_id);
+            return Err(e);
+        }
+
+        if res.unwrap().is_none() {
+            debug!(
+                "[DB] Payment {} was already initiated or expired",
+                payment_id
+            );
+            tx.rollback().await?;
+
+            return Ok(false);
+        }
+
+        let res = sqlx::query!(
+            r#"INSERT INTO payment_transactions (payment_id, transaction_id) VALUES ($1, $2);"#,
+            payment_id,
+            transaction_id
+        )
+        .execute(&mut *tx)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to add payment received {} {}",
+                payment_id, transaction_id
+            );
+            return Err(e);
+        }
+
+        tx.commit().await?;
+
+        debug!("[DB] Credited lightning payment {}", payment_id);
+
+        Ok(true)
+    }
+
+    async fn next_derivation_index(&self, descriptor: &str) -> Result<u32, sqlx::Error> {
+        debug!("[DB] Reserving derivation index for {}", descriptor);
+
+        let res = sqlx::query!(
+            r#"INSERT INTO derivation_indexes (descriptor, next_index) VALUES ($1, 1)
+            ON CONFLICT (descriptor) DO UPDATE SET next_index = derivation_indexes.next_index + 1
+            RETURNING next_index - 1 as "index!";"#,
+            descriptor
+        )
+        .fetch_one(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to reserve derivation index for {}", descriptor);
+            return Err(e);
+        }
+
+        let index = res.unwrap().index as u32;
+
+        debug!(
+            "[DB] Reserved derivation index {} for {}",
+            index, descriptor
+        );
+
+        Ok(index)
+    }
+
     async fn get_to_be_completed_payments(&self) -> Result<Vec<Uuid>, sqlx::Error> {
         debug!("[DB] Getting to be completed payments");
 
@@ -299,10 +533,14 @@ impl PaymentRepository for SqlxPostgresqlRepository {
                 address: row.address,
                 amount: row.amount,
                 received: row.received,
+                lightning_invoice: row.lightning_invoice,
                 initiated: row.initiated,
                 completed: row.completed,
+                expired: row.expired,
+                refund_required: row.refund_required,
                 created_at: row.created_at,
                 updated_at: row.updated_at,
+                expires_at: row.expires_at,
             }));
         }
 
@@ -338,7 +576,7 @@ impl PaymentRepository for SqlxPostgresqlRepository {
         }
 
         let res = res.unwrap();
-        if let Some(_) = res {
+        if res.is_some() {
             debug!(
                 "[DB] Transaction {} for address {} is already processed",
                 transaction_id, address
@@ -350,7 +588,168 @@ impl PaymentRepository for SqlxPostgresqlRepository {
             "[DB] Transaction {} for address {} is not processed yet",
             transaction_id, address
         );
-        return Ok(false);
+        Ok(false)
+    }
+
+    async fn get_payments(
+        &self,
+        account_id: &Uuid,
+        filter: &PaymentsFilter,
+    ) -> Result<Option<Vec<(Payment, Vec<String>)>>, sqlx::Error> {
+        debug!(
+            "[DB] Getting payments for account {} {:?}",
+            account_id, filter
+        );
+
+        if let Some(cursor) = filter.cursor {
+            let res = sqlx::query!(
+                r#"SELECT EXISTS(SELECT 1 FROM payments WHERE id = $1 AND account_id = $2) as "exists!";"#,
+                cursor,
+                account_id
+            )
+            .fetch_one(&self.pool)
+            .await;
+
+            if let Err(e) = res {
+                error!("[DB] Failed to check payments cursor {}", cursor);
+                return Err(e);
+            }
+
+            if !res.unwrap().exists {
+                debug!(
+                    "[DB] Payments cursor {} is not a payment of account {}",
+                    cursor, account_id
+                );
+                return Ok(None);
+            }
+        }
+
+        // Both directions walk the (account_id, created_at, id) index, the cursor is resolved
+        // to its (created_at, id) pair so pages stay stable while new payments come in.
+        // Domains are read from the first record of each inscription (`DOMAIN <name> <epoch>` or
+        // `DOMAIN-UPDATE <name> <epoch>`), private keys move with transfers and miss updates.
+        let res = if filter.ascending {
+            sqlx::query!(
+                r#"SELECT payments.id, payments.account_id, payments.address, payments.amount, payments.received, payments.lightning_invoice,
+                    payments.initiated, payments.completed, payments.expired, payments.refund_required,
+                    payments.created_at, payments.updated_at, payments.expires_at,
+                    ARRAY_REMOVE(ARRAY_AGG(split_part(split_part(payment_inscription_contents.content, E'\n', 1), ' ', 2)), NULL) as "domains!"
+                FROM payments
+                LEFT JOIN payment_inscription_contents ON payment_inscription_contents.payment_id = payments.id
+                    WHERE payments.account_id = $1
+                    AND ($2::BOOLEAN IS NULL OR payments.initiated = $2)
+                    AND ($3::BOOLEAN IS NULL OR payments.completed = $3)
+                    AND ($4::BOOLEAN IS NULL OR payments.expired = $4)
+                    AND ($5::TIMESTAMP IS NULL OR payments.created_at >= $5)
+                    AND ($6::TIMESTAMP IS NULL OR payments.created_at < $6)
+                    AND ($7::UUID IS NULL OR (payments.created_at, payments.id) > (SELECT created_at, id FROM payments WHERE id = $7 AND account_id = $1))
+                GROUP BY payments.id
+                ORDER BY payments.created_at ASC, payments.id ASC
+                LIMIT $8;"#,
+                account_id,
+                filter.initiated,
+                filter.completed,
+                filter.expired,
+                filter.from,
+                filter.to,
+                filter.cursor,
+                filter.limit
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|row| {
+                        (
+                            Payment {
+                                id: row.id,
+                                account_id: row.account_id,
+                                address: row.address,
+                                amount: row.amount,
+                                received: row.received,
+                                lightning_invoice: row.lightning_invoice,
+                                initiated: row.initiated,
+                                completed: row.completed,
+                                expired: row.expired,
+                                refund_required: row.refund_required,
+                                created_at: row.created_at,
+                                updated_at: row.updated_at,
+                                expires_at: row.expires_at,
+                            },
+                            row.domains,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+        } else {
+            sqlx::query!(
+                r#"SELECT payments.id, payments.account_id, payments.address, payments.amount, payments.received, payments.lightning_invoice,
+                    payments.initiated, payments.completed, payments.expired, payments.refund_required,
+                    payments.created_at, payments.updated_at, payments.expires_at,
+                    ARRAY_REMOVE(ARRAY_AGG(split_part(split_part(payment_inscription_contents.content, E'\n', 1), ' ', 2)), NULL) as "domains!"
+                FROM payments
+                LEFT JOIN payment_inscription_contents ON payment_inscription_contents.payment_id = payments.id
+                    WHERE payments.account_id = $1
+                    AND ($2::BOOLEAN IS NULL OR payments.initiated = $2)
+                    AND ($3::BOOLEAN IS NULL OR payments.completed = $3)
+                    AND ($4::BOOLEAN IS NULL OR payments.expired = $4)
+                    AND ($5::TIMESTAMP IS NULL OR payments.created_at >= $5)
+                    AND ($6::TIMESTAMP IS NULL OR payments.created_at < $6)
+                    AND ($7::UUID IS NULL OR (payments.created_at, payments.id) < (SELECT created_at, id FROM payments WHERE id = $7 AND account_id = $1))
+                GROUP BY payments.id
+                ORDER BY payments.created_at DESC, payments.id DESC
+                LIMIT $8;"#,
+                account_id,
+                filter.initiated,
+                filter.completed,
+                filter.expired,
+                filter.from,
+                filter.to,
+                filter.cursor,
+                filter.limit
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|row| {
+                        (
+                            Payment {
+                                id: row.id,
+                                account_id: row.account_id,
+                                address: row.address,
+                                amount: row.amount,
+                                received: row.received,
+                                lightning_invoice: row.lightning_invoice,
+                                initiated: row.initiated,
+                                completed: row.completed,
+                                expired: row.expired,
+                                refund_required: row.refund_required,
+                                created_at: row.created_at,
+                                updated_at: row.updated_at,
+                                expires_at: row.expires_at,
+                            },
+                            row.domains,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get payments for account {}", account_id);
+            return Err(e);
+        }
+
+        let payments = res.unwrap();
+
+        debug!(
+            "[DB] Got {} payments for account {}",
+            payments.len(),
+            account_id
+        );
+
+        Ok(Some(payments))
     }
 
     async fn get_payment_by_address(&self, address: &str) -> Result<Option<Payment>, sqlx::Error> {
@@ -375,10 +774,14 @@ impl PaymentRepository for SqlxPostgresqlRepository {
                 address: row.address,
                 amount: row.amount,
                 received: row.received,
+                lightning_invoice: row.lightning_invoice,
                 initiated: row.initiated,
                 completed: row.completed,
+                expired: row.expired,
+                refund_required: row.refund_required,
                 created_at: row.created_at,
                 updated_at: row.updated_at,
+                expires_at: row.expires_at,
             }));
         }
 
@@ -516,7 +919,7 @@ impl PaymentRepository for SqlxPostgresqlRepository {
         );
 
         let res = sqlx::query!(
-            r#"SELECT encryption_method, private_key FROM private_keys WHERE account_id = $1 AND domain = $2;"#,
+            r#"SELECT encryption_method, private_key FROM private_keys WHERE account_id = $1 AND domain = $2 AND retired_at IS NULL;"#,
             account_id,
             domain
         )
@@ -553,7 +956,8 @@ impl PaymentRepository for SqlxPostgresqlRepository {
             INNER JOIN payments ON payments.id = payment_inscription_contents.payment_id 
             LEFT JOIN payment_inscriptions ON payment_inscriptions.content = payment_inscription_contents.id 
                 WHERE payments.initiated = TRUE 
-                AND private_keys.account_id = $1;"#,
+                AND private_keys.account_id = $1
+                AND private_keys.retired_at IS NULL;"#,
             account_id
         )
         .fetch_all(&self.pool)
@@ -580,54 +984,251 @@ impl PaymentRepository for SqlxPostgresqlRepository {
         Ok(domains)
     }
 
-    async fn get_already_owned_domains(
+    async fn get_owned_domain(
         &self,
-        user: &Uuid,
-        domains: &[String],
-    ) -> Result<Vec<String>, sqlx::Error> {
-        debug!("[DB] Getting already owned domains {:?}", domains);
+        account_id: &Uuid,
+        domain: &str,
+    ) -> Result<Option<OwnedDomain>, sqlx::Error> {
+        debug!(
+            "[DB] Getting owned domain {} for account {}",
+            domain, account_id
+        );
 
         let res = sqlx::query!(
-            r#"SELECT private_keys.domain FROM private_keys WHERE private_keys.domain = ANY($1) AND private_keys.account_id <> $2;"#,
-            domains,
-            user
+            r#"SELECT private_keys.domain, private_keys.encryption_method, private_keys.private_key, private_keys.custodial_signing,
+                payment_inscription_contents.target, payment_inscription_contents.content, payments.completed
+            FROM private_keys
+            INNER JOIN payment_inscription_contents ON payment_inscription_contents.id = private_keys.payment_inscription_content_id
+            INNER JOIN payments ON payments.id = payment_inscription_contents.payment_id
+                WHERE payments.initiated = TRUE
+                AND private_keys.account_id = $1
+                AND private_keys.domain = $2
+                AND private_keys.retired_at IS NULL;"#,
+            account_id,
+            domain
         )
-        .fetch_all(&self.pool)
+        .fetch_optional(&self.pool)
         .await;
 
         if let Err(e) = res {
-            error!("[DB] Failed to get already owned domains {:?}", domains);
+            error!(
+                "[DB] Failed to get owned domain {} for account {}",
+                domain, account_id
+            );
             return Err(e);
         }
 
-        let res = res.unwrap();
-
-        let mut domains = Vec::new();
-
-        for row in res {
-            domains.push(row.domain);
-        }
-
-        debug!("[DB] Got already owned domains {:?}", domains);
+        debug!(
+            "[DB] Got owned domain {} for account {}",
+            domain, account_id
+        );
 
-        Ok(domains)
+        Ok(res.unwrap().map(|row| OwnedDomain {
+            domain: row.domain,
+            target: row.target,
+            inscription: row.content,
+            private_key: decrypt_string(&row.private_key, row.encryption_method.into()),
+            custodial_signing: row.custodial_signing,
+            completed: row.completed,
+        }))
     }
 
-    async fn cleanup_old_orders(&self) -> Result<(), sqlx::Error> {
-        debug!("[DB] Cleaning up old orders");
-
-        let res = sqlx::query!(
-            r#"DELETE FROM payments WHERE initiated = False AND created_at < NOW() - INTERVAL '35 minutes';"#
-        )
+    async fn set_custodial_signing(
+        &self,
+        account_id: &Uuid,
+        domain: &str,
+        enabled: bool,
+    ) -> Result<bool, sqlx::Error> {
+        debug!(
+            "[DB] Setting custodial signing for domain {} of account {} to {}",
+            domain, account_id, enabled
+        );
+
+        let res = sqlx::query!(
+            r#"UPDATE private_keys SET custodial_signing = $3 WHERE account_id = $1 AND domain = $2 AND retired_at IS NULL;"#,
+            account_id,
+            domain,
+            enabled
+        )
+        .execute(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to set custodial signing for domain {} of account {}",
+                domain, account_id
+            );
+            return Err(e);
+        }
+
+        debug!(
+            "[DB] Set custodial signing for domain {} of account {}",
+            domain, account_id
+        );
+
+        Ok(res.unwrap().rows_affected() > 0)
+    }
+
+    async fn get_domain_registration(
+        &self,
+        domain: &str,
+    ) -> Result<Option<DomainRegistration>, sqlx::Error> {
+        debug!("[DB] Getting registration of {}", domain);
+
+        let res = sqlx::query!(
+            r#"SELECT private_keys.domain, payment_inscription_contents.target, payment_inscription_contents.content,
+                payments.initiated, payments.completed, payment_inscriptions.reveal_tx as "reveal_tx?"
+            FROM private_keys
+            INNER JOIN payment_inscription_contents ON payment_inscription_contents.id = private_keys.payment_inscription_content_id
+            INNER JOIN payments ON payments.id = payment_inscription_contents.payment_id
+            LEFT JOIN payment_inscriptions ON payment_inscriptions.content = payment_inscription_contents.id
+                WHERE private_keys.domain = $1
+                AND payments.expired = FALSE
+            ORDER BY payments.initiated DESC, payments.created_at ASC
+            LIMIT 1;"#,
+            domain
+        )
+        .fetch_optional(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get registration of {}", domain);
+            return Err(e);
+        }
+
+        let mut registration = match res.unwrap() {
+            Some(row) => DomainRegistration {
+                domain: row.domain,
+                target: row.target,
+                inscription: row.content,
+                initiated: row.initiated,
+                completed: row.completed,
+                reveal_tx: row.reveal_tx,
+            },
+            None => {
+                debug!("[DB] No registration of {}", domain);
+                return Ok(None);
+            }
+        };
+
+        // Record updates and external transfers move the domain after it was inscribed.
+        let res = sqlx::query!(
+            r#"SELECT payment_inscription_contents.content
+            FROM domain_updates
+            INNER JOIN payment_inscription_contents ON payment_inscription_contents.id = domain_updates.payment_inscription_content_id
+            INNER JOIN payments ON payments.id = payment_inscription_contents.payment_id
+            INNER JOIN payment_inscriptions ON payment_inscriptions.content = payment_inscription_contents.id
+                WHERE domain_updates.domain = $1
+                AND payments.completed = TRUE
+            ORDER BY payment_inscriptions.created_at DESC;"#,
+            domain
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get updates of {}", domain);
+            return Err(e);
+        }
+
+        if let Some(target) = res.unwrap().iter().find_map(|row| {
+            row.content
+                .parse::<DomainUpdateInscription>()
+                .ok()
+                .and_then(|update| update.target().map(str::to_string))
+        }) {
+            registration.target = target;
+        }
+
+        debug!("[DB] Got registration of {}", domain);
+
+        Ok(Some(registration))
+    }
+
+    async fn get_already_owned_domains(
+        &self,
+        user: &Uuid,
+        domains: &[String],
+    ) -> Result<Vec<String>, sqlx::Error> {
+        debug!("[DB] Getting already owned domains {:?}", domains);
+
+        let res = sqlx::query!(
+            r#"SELECT private_keys.domain FROM private_keys
+            INNER JOIN payment_inscription_contents ON payment_inscription_contents.id = private_keys.payment_inscription_content_id
+            INNER JOIN payments ON payments.id = payment_inscription_contents.payment_id
+                WHERE private_keys.domain = ANY($1)
+                AND (private_keys.account_id <> $2 OR private_keys.retired_at IS NOT NULL)
+                AND payments.expired = FALSE;"#,
+            domains,
+            user
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get already owned domains {:?}", domains);
+            return Err(e);
+        }
+
+        let res = res.unwrap();
+
+        let mut domains = Vec::new();
+
+        for row in res {
+            domains.push(row.domain);
+        }
+
+        debug!("[DB] Got already owned domains {:?}", domains);
+
+        Ok(domains)
+    }
+
+    async fn expire_old_orders(&self) -> Result<Vec<(Uuid, Uuid)>, sqlx::Error> {
+        debug!("[DB] Expiring old orders");
+
+        let res = sqlx::query!(
+            r#"WITH expired AS (
+                UPDATE payments SET expired = TRUE WHERE initiated = FALSE AND expired = FALSE AND expires_at < NOW() RETURNING id, account_id
+            ), released AS (
+                DELETE FROM domain_reservations WHERE payment_id IN (SELECT id FROM expired)
+            )
+            SELECT id as "id!", account_id as "account_id!" FROM expired;"#
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to expire old orders");
+            return Err(e);
+        }
+
+        let expired = res
+            .unwrap()
+            .into_iter()
+            .map(|row| (row.id, row.account_id))
+            .collect::<Vec<_>>();
+
+        debug!("[DB] Expired old orders {:?}", expired);
+
+        Ok(expired)
+    }
+
+    async fn flag_payment_for_refund(&self, payment_id: &Uuid) -> Result<(), sqlx::Error> {
+        debug!("[DB] Flagging payment {} for refund", payment_id);
+
+        let res = sqlx::query!(
+            r#"UPDATE payments SET refund_required = TRUE WHERE id = $1;"#,
+            payment_id
+        )
         .execute(&self.pool)
         .await;
 
         if let Err(e) = res {
-            error!("[DB] Failed to clean up old orders");
+            error!("[DB] Failed to flag payment {} for refund", payment_id);
             return Err(e);
         }
 
-        debug!("[DB] Cleaned up old orders");
+        debug!("[DB] Flagged payment {} for refund", payment_id);
 
         Ok(())
     }
@@ -736,17 +1337,1566 @@ impl PaymentRepository for SqlxPostgresqlRepository {
 
         Ok(addresses)
     }
-}
 
-impl SessionRepository for SqlxPostgresqlRepository {
-    async fn get_session(&self, token: &str) -> Result<Uuid, sqlx::Error> {
-        debug!("[DB] Getting session {}", token);
-        let id = sqlx::query!(r#"SELECT account_id FROM sessions WHERE id = $1;"#, token)
-            .fetch_one(&self.pool)
-            .await?;
+    async fn get_payment_domains(&self, payment_id: &Uuid) -> Result<Vec<String>, sqlx::Error> {
+        debug!("[DB] Getting domains of payment {}", payment_id);
 
-        debug!("[DB] Got session {} from token {}", id.account_id, token);
+        let res = sqlx::query!(
+            r#"SELECT private_keys.domain FROM private_keys
+            INNER JOIN payment_inscription_contents ON payment_inscription_contents.id = private_keys.payment_inscription_content_id
+                WHERE payment_inscription_contents.payment_id = $1;"#,
+            payment_id
+        )
+        .fetch_all(&self.pool)
+        .await;
 
-        Ok(id.account_id)
+        if let Err(e) = res {
+            error!("[DB] Failed to get domains of payment {}", payment_id);
+            return Err(e);
+        }
+
+        let domains = res
+            .unwrap()
+            .into_iter()
+            .map(|row| row.domain)
+            .collect::<Vec<_>>();
+
+        debug!("[DB] Got domains {:?} of payment {}", domains, payment_id);
+
+        Ok(domains)
+    }
+
+    async fn reactivate_payment(&self, payment_id: &Uuid) -> Result<bool, sqlx::Error> {
+        debug!("[DB] Reactivating payment {}", payment_id);
+
+        let mut tx = self.pool.begin().await?;
+
+        if !Self::restore_payment(&mut tx, payment_id).await? {
+            tx.rollback().await?;
+
+            return Ok(false);
+        }
+
+        tx.commit().await?;
+
+        debug!("[DB] Reactivated payment {}", payment_id);
+
+        Ok(true)
+    }
+
+    async fn reactivate_late_payment(
+        &self,
+        id: &Uuid,
+    ) -> Result<LatePaymentReactivation, sqlx::Error> {
+        debug!("[DB] Reactivating late payment {}", id);
+
+        let mut tx = self.pool.begin().await?;
+
+        let pending: &str = LatePaymentStatus::Pending.into();
+        let reactivated: &str = LatePaymentStatus::Reactivated.into();
+        let res = sqlx::query!(
+            r#"UPDATE late_payments SET status = $1, updated_at = NOW() WHERE id = $2 AND status = $3 RETURNING payment_id, amount, transaction_id;"#,
+            reactivated,
+            id,
+            pending
+        )
+        .fetch_optional(&mut *tx)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to claim late payment {}", id);
+            return Err(e);
+        }
+
+        let late_payment = match res.unwrap() {
+            Some(late_payment) => late_payment,
+            None => {
+                tx.rollback().await?;
+                return Ok(LatePaymentReactivation::AlreadyHandled);
+            }
+        };
+
+        let payment_id = match late_payment.payment_id {
+            Some(payment_id) => payment_id,
+            None => {
+                tx.rollback().await?;
+                return Ok(LatePaymentReactivation::OrderDeleted);
+            }
+        };
+
+        let res = sqlx::query!(
+            r#"SELECT expired FROM payments WHERE id = $1 FOR UPDATE;"#,
+            payment_id
+        )
+        .fetch_optional(&mut *tx)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get payment {}", payment_id);
+            return Err(e);
+        }
+
+        let expired = match res.unwrap() {
+            Some(row) => row.expired,
+            None => {
+                tx.rollback().await?;
+                return Ok(LatePaymentReactivation::OrderDeleted);
+            }
+        };
+
+        if expired && !Self::restore_payment(&mut tx, &payment_id).await? {
+            tx.rollback().await?;
+            return Ok(LatePaymentReactivation::DomainsReserved);
+        }
+
+        let res = sqlx::query!(
+            r#"INSERT INTO payment_transactions (payment_id, transaction_id) VALUES ($1, $2);"#,
+            payment_id,
+            late_payment.transaction_id
+        )
+        .execute(&mut *tx)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to add late payment {} to payment {}",
+                id, payment_id
+            );
+            return Err(e);
+        }
+
+        let res = sqlx::query!(
+            r#"UPDATE payments SET received = received + $1 WHERE id = $2;"#,
+            late_payment.amount,
+            payment_id
+        )
+        .execute(&mut *tx)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to credit late payment {} to payment {}",
+                id, payment_id
+            );
+            return Err(e);
+        }
+
+        tx.commit().await?;
+
+        debug!(
+            "[DB] Credited late payment {} to payment {}",
+            id, payment_id
+        );
+
+        Ok(LatePaymentReactivation::Reactivated)
+    }
+
+    async fn is_late_payment_recorded(
+        &self,
+        transaction_id: &str,
+        address: &str,
+    ) -> Result<bool, sqlx::Error> {
+        debug!(
+            "[DB] Checking if late payment {} for address {} is recorded",
+            transaction_id, address
+        );
+
+        let res = sqlx::query!(
+            r#"SELECT id FROM late_payments WHERE transaction_id = $1 AND address = $2;"#,
+            transaction_id,
+            address
+        )
+        .fetch_optional(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to check if late payment {} for address {} is recorded",
+                transaction_id, address
+            );
+            return Err(e);
+        }
+
+        Ok(res.unwrap().is_some())
+    }
+
+    async fn add_late_payment(
+        &self,
+        account_id: &Uuid,
+        payment_id: Option<&Uuid>,
+        address: &str,
+        transaction_id: &str,
+        amount: f64,
+    ) -> Result<Uuid, sqlx::Error> {
+        debug!(
+            "[DB] Adding late payment {} {} {}BTC for account {}",
+            address, transaction_id, amount, account_id
+        );
+
+        let status: &str = LatePaymentStatus::Pending.into();
+        let res = sqlx::query!(
+            r#"INSERT INTO late_payments (account_id, payment_id, address, transaction_id, amount, status) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id;"#,
+            account_id,
+            payment_id.copied(),
+            address,
+            transaction_id,
+            amount,
+            status
+        )
+        .fetch_one(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to add late payment {} {} for account {}",
+                address, transaction_id, account_id
+            );
+            return Err(e);
+        }
+
+        let id = res.unwrap().id;
+
+        debug!("[DB] Added late payment {} for account {}", id, account_id);
+
+        Ok(id)
+    }
+
+    async fn get_late_payment(&self, id: &Uuid) -> Result<Option<LatePayment>, sqlx::Error> {
+        debug!("[DB] Getting late payment {}", id);
+
+        let res = sqlx::query!(r#"SELECT * FROM late_payments WHERE id = $1;"#, id)
+            .fetch_optional(&self.pool)
+            .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get late payment {}", id);
+            return Err(e);
+        }
+
+        Ok(res.unwrap().map(|row| LatePayment {
+            id: row.id,
+            account_id: row.account_id,
+            payment_id: row.payment_id,
+            address: row.address,
+            transaction_id: row.transaction_id,
+            amount: row.amount,
+            status: row.status.as_str().into(),
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }))
+    }
+
+    async fn get_late_payments(
+        &self,
+        status: Option<LatePaymentStatus>,
+    ) -> Result<Vec<LatePayment>, sqlx::Error> {
+        debug!("[DB] Getting late payments {:?}", status);
+
+        let status: Option<&str> = status.map(|status| status.into());
+        let res = sqlx::query!(
+            r#"SELECT * FROM late_payments WHERE ($1::TEXT IS NULL OR status = $1) ORDER BY created_at DESC;"#,
+            status
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get late payments {:?}", status);
+            return Err(e);
+        }
+
+        let late_payments = res
+            .unwrap()
+            .into_iter()
+            .map(|row| LatePayment {
+                id: row.id,
+                account_id: row.account_id,
+                payment_id: row.payment_id,
+                address: row.address,
+                transaction_id: row.transaction_id,
+                amount: row.amount,
+                status: row.status.as_str().into(),
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+            .collect::<Vec<_>>();
+
+        debug!("[DB] Got {} late payments", late_payments.len());
+
+        Ok(late_payments)
+    }
+
+    async fn set_late_payment_status(
+        &self,
+        id: &Uuid,
+        status: LatePaymentStatus,
+    ) -> Result<bool, sqlx::Error> {
+        debug!("[DB] Setting late payment {} status to {:?}", id, status);
+
+        let status: &str = status.into();
+        let pending: &str = LatePaymentStatus::Pending.into();
+        let res = sqlx::query!(
+            r#"UPDATE late_payments SET status = $1, updated_at = NOW() WHERE id = $2 AND status = $3 RETURNING id;"#,
+            status,
+            id,
+            pending
+        )
+        .fetch_optional(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to set late payment {} status", id);
+            return Err(e);
+        }
+
+        let updated = res.unwrap().is_some();
+
+        debug!(
+            "[DB] Set late payment {} status to {}: {}",
+            id, status, updated
+        );
+
+        Ok(updated)
+    }
+
+    async fn create_refund(
+        &self,
+        account_id: &Uuid,
+        payment_id: Option<&Uuid>,
+        late_payment_id: Option<&Uuid>,
+        amount: f64,
+        refunded: f64,
+        address: &str,
+    ) -> Result<Option<Uuid>, sqlx::Error> {
+        debug!(
+            "[DB] Creating refund of {}BTC for account {} (payment: {:?}, late payment: {:?})",
+            amount, account_id, payment_id, late_payment_id
+        );
+
+        let mut tx = self.pool.begin().await?;
+
+        if let Some(payment_id) = payment_id {
+            // Refunds of the same payment are serialized on the payment, the amount was worked out
+            // from what had been refunded before and is only valid if nothing was refunded since.
+            let res = sqlx::query!(
+                r#"SELECT id FROM payments WHERE id = $1 FOR UPDATE;"#,
+                payment_id
+            )
+            .fetch_optional(&mut *tx)
+            .await;
+
+            if let Err(e) = res {
+                error!("[DB] Failed to lock payment {}", payment_id);
+                return Err(e);
+            }
+
+            let rejected: &str = RefundStatus::Rejected.into();
+            let res = sqlx::query!(
+                r#"SELECT COALESCE(SUM(amount), 0) as "refunded!" FROM refunds WHERE payment_id = $1 AND status <> $2;"#,
+                payment_id,
+                rejected
+            )
+            .fetch_one(&mut *tx)
+            .await;
+
+            if let Err(e) = res {
+                error!(
+                    "[DB] Failed to get refunded amount of payment {}",
+                    payment_id
+                );
+                return Err(e);
+            }
+
+            if (res.unwrap().refunded - refunded).abs() >= 0.000000005 {
+                debug!(
+                    "[DB] Payment {} has been refunded in the meantime",
+                    payment_id
+                );
+                tx.rollback().await?;
+
+                return Ok(None);
+            }
+        }
+
+        if let Some(late_payment_id) = late_payment_id {
+            let refund: &str = LatePaymentStatus::Refund.into();
+            let reactivated: &str = LatePaymentStatus::Reactivated.into();
+            let res = sqlx::query!(
+                r#"UPDATE late_payments SET status = $1, updated_at = NOW() WHERE id = $2 AND status <> $3 RETURNING id;"#,
+                refund,
+                late_payment_id,
+                reactivated
+            )
+            .fetch_optional(&mut *tx)
+            .await;
+
+            if let Err(e) = res {
+                error!("[DB] Failed to set late payment {} status", late_payment_id);
+                return Err(e);
+            }
+
+            if res.unwrap().is_none() {
+                debug!("[DB] Late payment {} has been reactivated", late_payment_id);
+                tx.rollback().await?;
+
+                return Ok(None);
+            }
+        }
+
+        let (address, encryption_method) = encrypt_string(address);
+        let status: &str = RefundStatus::Requested.into();
+        let res = sqlx::query!(
+            r#"INSERT INTO refunds (account_id, payment_id, late_payment_id, amount, address, encryption_method, status) VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT DO NOTHING RETURNING id;"#,
+            account_id,
+            payment_id.copied(),
+            late_payment_id.copied(),
+            amount,
+            address,
+            encryption_method as i16,
+            status
+        )
+        .fetch_optional(&mut *tx)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to create refund for account {}", account_id);
+            return Err(e);
+        }
+
+        let id = match res.unwrap() {
+            Some(row) => row.id,
+            None => {
+                debug!(
+                    "[DB] A refund is already open (payment: {:?}, late payment: {:?})",
+                    payment_id, late_payment_id
+                );
+                tx.rollback().await?;
+
+                return Ok(None);
+            }
+        };
+
+        tx.commit().await?;
+
+        debug!("[DB] Created refund {} for account {}", id, account_id);
+
+        Ok(Some(id))
+    }
+
+    async fn get_refund(&self, id: &Uuid) -> Result<Option<Refund>, sqlx::Error> {
+        debug!("[DB] Getting refund {}", id);
+
+        let res = sqlx::query!(r#"SELECT * FROM refunds WHERE id = $1;"#, id)
+            .fetch_optional(&self.pool)
+            .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get refund {}", id);
+            return Err(e);
+        }
+
+        Ok(res.unwrap().map(|row| Refund {
+            id: row.id,
+            account_id: row.account_id,
+            payment_id: row.payment_id,
+            late_payment_id: row.late_payment_id,
+            amount: row.amount,
+            address: decrypt_string(&row.address, row.encryption_method.into()),
+            status: row.status.as_str().into(),
+            reason: row.reason,
+            fee_rate: row.fee_rate,
+            transaction_id: row.transaction_id,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }))
+    }
+
+    async fn get_refunds(
+        &self,
+        account_id: Option<&Uuid>,
+        status: Option<RefundStatus>,
+    ) -> Result<Vec<Refund>, sqlx::Error> {
+        debug!("[DB] Getting refunds {:?} {:?}", account_id, status);
+
+        let status: Option<&str> = status.map(|status| status.into());
+        let res = sqlx::query!(
+            r#"SELECT * FROM refunds WHERE ($1::UUID IS NULL OR account_id = $1) AND ($2::TEXT IS NULL OR status = $2) ORDER BY created_at DESC;"#,
+            account_id.copied(),
+            status
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get refunds {:?} {:?}", account_id, status);
+            return Err(e);
+        }
+
+        let refunds = res
+            .unwrap()
+            .into_iter()
+            .map(|row| Refund {
+                id: row.id,
+                account_id: row.account_id,
+                payment_id: row.payment_id,
+                late_payment_id: row.late_payment_id,
+                amount: row.amount,
+                address: decrypt_string(&row.address, row.encryption_method.into()),
+                status: row.status.as_str().into(),
+                reason: row.reason,
+                fee_rate: row.fee_rate,
+                transaction_id: row.transaction_id,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+            .collect::<Vec<_>>();
+
+        debug!("[DB] Got {} refunds", refunds.len());
+
+        Ok(refunds)
+    }
+
+    async fn get_refunded_amount(&self, payment_id: &Uuid) -> Result<f64, sqlx::Error> {
+        debug!("[DB] Getting refunded amount of payment {}", payment_id);
+
+        let rejected: &str = RefundStatus::Rejected.into();
+        let res = sqlx::query!(
+            r#"SELECT COALESCE(SUM(amount), 0) as "refunded!" FROM refunds WHERE payment_id = $1 AND status <> $2;"#,
+            payment_id,
+            rejected
+        )
+        .fetch_one(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to get refunded amount of payment {}",
+                payment_id
+            );
+            return Err(e);
+        }
+
+        Ok(res.unwrap().refunded)
+    }
+
+    async fn claim_refund(&self, id: &Uuid) -> Result<bool, sqlx::Error> {
+        debug!("[DB] Claiming refund {}", id);
+
+        let requested: &str = RefundStatus::Requested.into();
+        let processing: &str = RefundStatus::Processing.into();
+        let res = sqlx::query!(
+            r#"UPDATE refunds SET status = $1, updated_at = NOW() WHERE id = $2 AND status = $3 RETURNING id;"#,
+            processing,
+            id,
+            requested
+        )
+        .fetch_optional(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to claim refund {}", id);
+            return Err(e);
+        }
+
+        Ok(res.unwrap().is_some())
+    }
+
+    async fn release_refund(&self, id: &Uuid) -> Result<(), sqlx::Error> {
+        debug!("[DB] Releasing refund {}", id);
+
+        let requested: &str = RefundStatus::Requested.into();
+        let processing: &str = RefundStatus::Processing.into();
+        let res = sqlx::query!(
+            r#"UPDATE refunds SET status = $1, updated_at = NOW() WHERE id = $2 AND status = $3;"#,
+            requested,
+            id,
+            processing
+        )
+        .execute(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to release refund {}", id);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    async fn complete_refund(
+        &self,
+        id: &Uuid,
+        transaction_id: &str,
+        fee_rate: f64,
+    ) -> Result<(), sqlx::Error> {
+        debug!("[DB] Completing refund {} with {}", id, transaction_id);
+
+        let status: &str = RefundStatus::Broadcast.into();
+        let res = sqlx::query!(
+            r#"UPDATE refunds SET status = $1, transaction_id = $2, fee_rate = $3, updated_at = NOW() WHERE id = $4;"#,
+            status,
+            transaction_id,
+            fee_rate,
+            id
+        )
+        .execute(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to complete refund {} with {}",
+                id, transaction_id
+            );
+            return Err(e);
+        }
+
+        debug!("[DB] Completed refund {} with {}", id, transaction_id);
+
+        Ok(())
+    }
+
+    async fn reject_refund(&self, id: &Uuid, reason: &str) -> Result<bool, sqlx::Error> {
+        debug!("[DB] Rejecting refund {}", id);
+
+        let requested: &str = RefundStatus::Requested.into();
+        let rejected: &str = RefundStatus::Rejected.into();
+        let res = sqlx::query!(
+            r#"UPDATE refunds SET status = $1, reason = $2, updated_at = NOW() WHERE id = $3 AND status = $4 RETURNING id;"#,
+            rejected,
+            reason,
+            id,
+            requested
+        )
+        .fetch_optional(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to reject refund {}", id);
+            return Err(e);
+        }
+
+        Ok(res.unwrap().is_some())
+    }
+
+    async fn get_completed_payment_addresses(&self) -> Result<Vec<(String, Uuid)>, sqlx::Error> {
+        debug!("[DB] Getting completed payment addresses");
+
+        let res = sqlx::query!(r#"SELECT address, id FROM payments WHERE completed = TRUE;"#)
+            .fetch_all(&self.pool)
+            .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get completed payment addresses");
+            return Err(e);
+        }
+
+        let addresses = res
+            .unwrap()
+            .into_iter()
+            .map(|row| (row.address, row.id))
+            .collect::<Vec<_>>();
+
+        debug!("[DB] Got {} completed payment addresses", addresses.len());
+
+        Ok(addresses)
+    }
+
+    async fn add_sweep(
+        &self,
+        transaction_id: &str,
+        address: &str,
+        amount: f64,
+        fee: f64,
+        fee_rate: f64,
+        inputs: &[(String, i32, Uuid)],
+    ) -> Result<Uuid, sqlx::Error> {
+        debug!(
+            "[DB] Adding sweep {} of {}BTC to {}",
+            transaction_id, amount, address
+        );
+
+        // The sweep and its inputs are written together, a sweep recorded without its inputs
+        // would leave them looking unswept while its transaction id is taken.
+        let mut tx = self.pool.begin().await?;
+
+        let res = sqlx::query!(
+            r#"INSERT INTO sweeps (transaction_id, address, amount, fee, fee_rate) VALUES ($1, $2, $3, $4, $5) RETURNING id;"#,
+            transaction_id,
+            address,
+            amount,
+            fee,
+            fee_rate
+        )
+        .fetch_one(&mut *tx)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to add sweep {}", transaction_id);
+            return Err(e);
+        }
+
+        let sweep_id = res.unwrap().id;
+
+        let (input_transaction_ids, (vouts, payment_ids)): (Vec<_>, (Vec<_>, Vec<_>)) = inputs
+            .iter()
+            .cloned()
+            .map(|(transaction_id, vout, payment_id)| (transaction_id, (vout, payment_id)))
+            .unzip();
+
+        let res = sqlx::query!(
+            r#"INSERT INTO sweep_inputs (sweep_id, transaction_id, vout, payment_id)
+            SELECT $1, * FROM UNNEST($2::TEXT[], $3::INT4[], $4::UUID[]);"#,
+            sweep_id,
+            &input_transaction_ids,
+            &vouts,
+            &payment_ids
+        )
+        .execute(&mut *tx)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to add inputs of sweep {}", transaction_id);
+            return Err(e);
+        }
+
+        tx.commit().await?;
+
+        debug!("[DB] Added sweep {} ({})", sweep_id, transaction_id);
+
+        Ok(sweep_id)
+    }
+
+    async fn create_transfer(
+        &self,
+        domain: &str,
+        from_account_id: &Uuid,
+        to_account_id: Option<&Uuid>,
+        to_address: Option<&str>,
+        inscribe: bool,
+    ) -> Result<Option<DomainTransfer>, sqlx::Error> {
+        debug!(
+            "[DB] Creating transfer of {} from {} (account: {:?}, address: {:?})",
+            domain, from_account_id, to_account_id, to_address
+        );
+
+        let status: &str = TransferStatus::Pending.into();
+        let res = sqlx::query!(
+            r#"INSERT INTO domain_transfers (domain, from_account_id, to_account_id, to_address, status, inscribe) VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (domain) WHERE status = 'pending' DO NOTHING
+            RETURNING *;"#,
+            domain,
+            from_account_id,
+            to_account_id.copied(),
+            to_address,
+            status,
+            inscribe
+        )
+        .fetch_optional(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to create transfer of {} from {}",
+                domain, from_account_id
+            );
+            return Err(e);
+        }
+
+        let row = match res.unwrap() {
+            Some(row) => row,
+            None => {
+                debug!("[DB] {} already has a pending transfer", domain);
+                return Ok(None);
+            }
+        };
+
+        debug!("[DB] Created transfer {} of {}", row.id, domain);
+
+        Ok(Some(DomainTransfer {
+            id: row.id,
+            domain: row.domain,
+            from_account_id: row.from_account_id,
+            to_account_id: row.to_account_id,
+            to_address: row.to_address,
+            status: row.status.as_str().into(),
+            inscribe: row.inscribe,
+            payment_id: row.payment_id,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }))
+    }
+
+    async fn get_transfer(&self, id: &Uuid) -> Result<Option<DomainTransfer>, sqlx::Error> {
+        debug!("[DB] Getting transfer {}", id);
+
+        let res = sqlx::query!(r#"SELECT * FROM domain_transfers WHERE id = $1;"#, id)
+            .fetch_optional(&self.pool)
+            .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get transfer {}", id);
+            return Err(e);
+        }
+
+        Ok(res.unwrap().map(|row| DomainTransfer {
+            id: row.id,
+            domain: row.domain,
+            from_account_id: row.from_account_id,
+            to_account_id: row.to_account_id,
+            to_address: row.to_address,
+            status: row.status.as_str().into(),
+            inscribe: row.inscribe,
+            payment_id: row.payment_id,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }))
+    }
+
+    async fn get_transfers(&self, account_id: &Uuid) -> Result<Vec<DomainTransfer>, sqlx::Error> {
+        debug!("[DB] Getting transfers of account {}", account_id);
+
+        let res = sqlx::query!(
+            r#"SELECT * FROM domain_transfers WHERE from_account_id = $1 OR to_account_id = $1 ORDER BY created_at DESC;"#,
+            account_id
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get transfers of account {}", account_id);
+            return Err(e);
+        }
+
+        let transfers = res
+            .unwrap()
+            .into_iter()
+            .map(|row| DomainTransfer {
+                id: row.id,
+                domain: row.domain,
+                from_account_id: row.from_account_id,
+                to_account_id: row.to_account_id,
+                to_address: row.to_address,
+                status: row.status.as_str().into(),
+                inscribe: row.inscribe,
+                payment_id: row.payment_id,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+            .collect::<Vec<_>>();
+
+        debug!(
+            "[DB] Got {} transfers of account {}",
+            transfers.len(),
+            account_id
+        );
+
+        Ok(transfers)
+    }
+
+    async fn close_transfer(&self, id: &Uuid, status: TransferStatus) -> Result<bool, sqlx::Error> {
+        debug!("[DB] Closing transfer {} as {:?}", id, status);
+
+        let pending: &str = TransferStatus::Pending.into();
+        let status: &str = status.into();
+        let res = sqlx::query!(
+            r#"UPDATE domain_transfers SET status = $1, updated_at = NOW() WHERE id = $2 AND status = $3 RETURNING id;"#,
+            status,
+            id,
+            pending
+        )
+        .fetch_optional(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to close transfer {}", id);
+            return Err(e);
+        }
+
+        Ok(res.unwrap().is_some())
+    }
+
+    async fn cancel_unpaid_transfers(&self) -> Result<Vec<DomainTransfer>, sqlx::Error> {
+        debug!("[DB] Cancelling external transfers with expired orders");
+
+        let accepted: &str = TransferStatus::Accepted.into();
+        let cancelled: &str = TransferStatus::Cancelled.into();
+        let res = sqlx::query!(
+            r#"UPDATE domain_transfers SET status = $1, updated_at = NOW()
+            FROM payments
+                WHERE payments.id = domain_transfers.payment_id
+                AND domain_transfers.to_address IS NOT NULL
+                AND domain_transfers.status = $2
+                AND payments.initiated = FALSE
+                AND payments.expired = TRUE
+            RETURNING domain_transfers.*;"#,
+            cancelled,
+            accepted
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to cancel external transfers with expired orders");
+            return Err(e);
+        }
+
+        let transfers = res
+            .unwrap()
+            .into_iter()
+            .map(|row| DomainTransfer {
+                id: row.id,
+                domain: row.domain,
+                from_account_id: row.from_account_id,
+                to_account_id: row.to_account_id,
+                to_address: row.to_address,
+                status: row.status.as_str().into(),
+                inscribe: row.inscribe,
+                payment_id: row.payment_id,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+            .collect::<Vec<_>>();
+
+        debug!(
+            "[DB] Cancelled {} external transfers with expired orders",
+            transfers.len()
+        );
+
+        Ok(transfers)
+    }
+
+    async fn retire_transferred_keys(&self) -> Result<Vec<Uuid>, sqlx::Error> {
+        debug!("[DB] Retiring keys of completed external transfers");
+
+        let accepted: &str = TransferStatus::Accepted.into();
+        let res = sqlx::query!(
+            r#"UPDATE private_keys SET retired_at = NOW(), custodial_signing = FALSE
+            FROM domain_transfers
+            INNER JOIN payments ON payments.id = domain_transfers.payment_id
+                WHERE domain_transfers.to_address IS NOT NULL
+                AND domain_transfers.status = $1
+                AND payments.completed = TRUE
+                AND EXISTS (
+                    SELECT 1 FROM payment_inscription_contents
+                    INNER JOIN payment_inscriptions ON payment_inscriptions.content = payment_inscription_contents.id
+                        WHERE payment_inscription_contents.payment_id = payments.id
+                )
+                AND private_keys.account_id = domain_transfers.from_account_id
+                AND private_keys.domain = domain_transfers.domain
+                AND private_keys.retired_at IS NULL
+            RETURNING domain_transfers.id;"#,
+            accepted
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to retire keys of completed external transfers");
+            return Err(e);
+        }
+
+        let transfers = res
+            .unwrap()
+            .into_iter()
+            .map(|row| row.id)
+            .collect::<Vec<_>>();
+
+        debug!("[DB] Retired keys of transfers {:?}", transfers);
+
+        Ok(transfers)
+    }
+
+    async fn get_active_domain_rules(
+        &self,
+        domains: &[String],
+    ) -> Result<Vec<DomainRule>, sqlx::Error> {
+        debug!("[DB] Getting active domain rules for {:?}", domains);
+
+        let res = sqlx::query!(
+            r#"SELECT * FROM domain_rules WHERE domain = ANY($1) AND (release_at IS NULL OR release_at > NOW());"#,
+            domains
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get active domain rules for {:?}", domains);
+            return Err(e);
+        }
+
+        let rules = res
+            .unwrap()
+            .into_iter()
+            .map(|row| DomainRule {
+                domain: row.domain,
+                list: row.list.as_str().into(),
+                reason: row.reason,
+                allocated_account_id: row.allocated_account_id,
+                release_at: row.release_at,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+            .collect::<Vec<_>>();
+
+        debug!("[DB] Got {} active domain rules", rules.len());
+
+        Ok(rules)
+    }
+
+    async fn get_domain_rules(
+        &self,
+        list: Option<DomainList>,
+    ) -> Result<Vec<DomainRule>, sqlx::Error> {
+        debug!("[DB] Getting domain rules {:?}", list);
+
+        let list: Option<&str> = list.map(|list| list.into());
+        let res = sqlx::query!(
+            r#"SELECT * FROM domain_rules WHERE ($1::TEXT IS NULL OR list = $1) ORDER BY domain;"#,
+            list
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get domain rules {:?}", list);
+            return Err(e);
+        }
+
+        let rules = res
+            .unwrap()
+            .into_iter()
+            .map(|row| DomainRule {
+                domain: row.domain,
+                list: row.list.as_str().into(),
+                reason: row.reason,
+                allocated_account_id: row.allocated_account_id,
+                release_at: row.release_at,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            })
+            .collect::<Vec<_>>();
+
+        debug!("[DB] Got {} domain rules", rules.len());
+
+        Ok(rules)
+    }
+
+    async fn set_domain_rule(
+        &self,
+        domain: &str,
+        list: DomainList,
+        reason: &str,
+        allocated_account_id: Option<&Uuid>,
+        release_at: Option<NaiveDateTime>,
+    ) -> Result<DomainRule, sqlx::Error> {
+        debug!("[DB] Setting domain rule {:?} for {}", list, domain);
+
+        let list: &str = list.into();
+        let res = sqlx::query!(
+            r#"INSERT INTO domain_rules (domain, list, reason, allocated_account_id, release_at) VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (domain) DO UPDATE SET list = $2, reason = $3, allocated_account_id = $4, release_at = $5, updated_at = NOW()
+            RETURNING *;"#,
+            domain,
+            list,
+            reason,
+            allocated_account_id.copied(),
+            release_at
+        )
+        .fetch_one(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to set domain rule {} for {}", list, domain);
+            return Err(e);
+        }
+
+        let row = res.unwrap();
+
+        debug!("[DB] Set domain rule {} for {}", list, domain);
+
+        Ok(DomainRule {
+            domain: row.domain,
+            list: row.list.as_str().into(),
+            reason: row.reason,
+            allocated_account_id: row.allocated_account_id,
+            release_at: row.release_at,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        })
+    }
+
+    async fn delete_domain_rule(&self, domain: &str) -> Result<bool, sqlx::Error> {
+        debug!("[DB] Deleting domain rule for {}", domain);
+
+        let res = sqlx::query!(r#"DELETE FROM domain_rules WHERE domain = $1;"#, domain)
+            .execute(&self.pool)
+            .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to delete domain rule for {}", domain);
+            return Err(e);
+        }
+
+        Ok(res.unwrap().rows_affected() > 0)
+    }
+
+    async fn get_idempotent_response(
+        &self,
+        account_id: &Uuid,
+        key: &str,
+        retention_hours: i32,
+    ) -> Result<Option<IdempotentResponse>, sqlx::Error> {
+        debug!(
+            "[DB] Getting response for idempotency key {} of account {}",
+            key, account_id
+        );
+
+        let res = sqlx::query!(
+            r#"SELECT request_hash, response FROM idempotency_keys WHERE account_id = $1 AND key = $2 AND created_at > NOW() - make_interval(hours => $3);"#,
+            account_id,
+            key,
+            retention_hours
+        )
+        .fetch_optional(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to get response for idempotency key {} of account {}",
+                key, account_id
+            );
+            return Err(e);
+        }
+
+        debug!(
+            "[DB] Got response for idempotency key {} of account {}",
+            key, account_id
+        );
+
+        Ok(res.unwrap().map(|row| IdempotentResponse {
+            request_hash: row.request_hash,
+            response: row.response,
+        }))
+    }
+
+    async fn delete_expired_idempotency_keys(
+        &self,
+        retention_hours: i32,
+    ) -> Result<(), sqlx::Error> {
+        debug!("[DB] Deleting expired idempotency keys");
+
+        let res = sqlx::query!(
+            r#"DELETE FROM idempotency_keys WHERE created_at <= NOW() - make_interval(hours => $1);"#,
+            retention_hours
+        )
+        .execute(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to delete expired idempotency keys");
+            return Err(e);
+        }
+
+        debug!("[DB] Deleted expired idempotency keys");
+
+        Ok(())
+    }
+
+    async fn search_payments(
+        &self,
+        search: &PaymentSearch,
+    ) -> Result<Vec<(Payment, Vec<String>)>, sqlx::Error> {
+        debug!("[DB] Searching payments {:?}", search);
+
+        let res = sqlx::query!(
+            r#"SELECT payments.id, payments.account_id, payments.address, payments.amount, payments.received, payments.lightning_invoice,
+                payments.initiated, payments.completed, payments.expired, payments.refund_required,
+                payments.created_at, payments.updated_at, payments.expires_at,
+                ARRAY_REMOVE(ARRAY_AGG(private_keys.domain), NULL) as "domains!"
+            FROM payments
+            LEFT JOIN payment_inscription_contents ON payment_inscription_contents.payment_id = payments.id
+            LEFT JOIN private_keys ON private_keys.payment_inscription_content_id = payment_inscription_contents.id
+                WHERE ($1::UUID IS NULL OR payments.account_id = $1)
+                AND ($2::TEXT IS NULL OR payments.address = $2)
+                AND ($3::TEXT IS NULL OR payments.id IN (SELECT payment_id FROM payment_transactions WHERE transaction_id = $3))
+                AND ($4::TEXT IS NULL OR payments.id IN (
+                    SELECT payment_inscription_contents.payment_id FROM private_keys
+                    INNER JOIN payment_inscription_contents ON payment_inscription_contents.id = private_keys.payment_inscription_content_id
+                        WHERE private_keys.domain = $4
+                ))
+            GROUP BY payments.id
+            ORDER BY payments.created_at DESC, payments.id DESC
+            LIMIT $5;"#,
+            search.account_id,
+            search.address,
+            search.transaction_id,
+            search.domain,
+            search.limit
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to search payments {:?}", search);
+            return Err(e);
+        }
+
+        let payments = res
+            .unwrap()
+            .into_iter()
+            .map(|row| {
+                (
+                    Payment {
+                        id: row.id,
+                        account_id: row.account_id,
+                        address: row.address,
+                        amount: row.amount,
+                        received: row.received,
+                        lightning_invoice: row.lightning_invoice,
+                        initiated: row.initiated,
+                        completed: row.completed,
+                        expired: row.expired,
+                        refund_required: row.refund_required,
+                        created_at: row.created_at,
+                        updated_at: row.updated_at,
+                        expires_at: row.expires_at,
+                    },
+                    row.domains,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        debug!("[DB] Found {} payments for {:?}", payments.len(), search);
+
+        Ok(payments)
+    }
+
+    async fn get_payment_transactions(
+        &self,
+        payment_id: &Uuid,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        debug!("[DB] Getting transactions of payment {}", payment_id);
+
+        let res = sqlx::query!(
+            r#"SELECT transaction_id FROM payment_transactions WHERE payment_id = $1;"#,
+            payment_id
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get transactions of payment {}", payment_id);
+            return Err(e);
+        }
+
+        let transactions = res
+            .unwrap()
+            .into_iter()
+            .map(|row| row.transaction_id)
+            .collect::<Vec<_>>();
+
+        debug!(
+            "[DB] Got transactions {:?} of payment {}",
+            transactions, payment_id
+        );
+
+        Ok(transactions)
+    }
+
+    async fn get_payment_inscriptions(
+        &self,
+        payment_id: &Uuid,
+    ) -> Result<Vec<PaymentInscription>, sqlx::Error> {
+        debug!("[DB] Getting inscriptions of payment {}", payment_id);
+
+        let res = sqlx::query!(
+            r#"SELECT payment_inscription_contents.id, private_keys.domain as "domain?", payment_inscription_contents.target,
+                payment_inscription_contents.content, payment_inscriptions.reveal_tx as "reveal_tx?"
+            FROM payment_inscription_contents
+            LEFT JOIN private_keys ON private_keys.payment_inscription_content_id = payment_inscription_contents.id
+            LEFT JOIN payment_inscriptions ON payment_inscriptions.content = payment_inscription_contents.id
+                WHERE payment_inscription_contents.payment_id = $1;"#,
+            payment_id
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get inscriptions of payment {}", payment_id);
+            return Err(e);
+        }
+
+        let inscriptions = res
+            .unwrap()
+            .into_iter()
+            .map(|row| PaymentInscription {
+                id: row.id,
+                domain: row.domain,
+                target: row.target,
+                content: row.content,
+                reveal_tx: row.reveal_tx,
+            })
+            .collect::<Vec<_>>();
+
+        debug!(
+            "[DB] Got {} inscriptions of payment {}",
+            inscriptions.len(),
+            payment_id
+        );
+
+        Ok(inscriptions)
+    }
+
+    async fn get_logs(
+        &self,
+        account_id: &Uuid,
+        filter: &LogsFilter,
+    ) -> Result<Option<Vec<LogEntry>>, sqlx::Error> {
+        debug!("[DB] Getting logs of account {} {:?}", account_id, filter);
+
+        if let Some(cursor) = filter.cursor {
+            let res = sqlx::query!(
+                r#"SELECT EXISTS(SELECT 1 FROM logs WHERE id = $1 AND account_id = $2) as "exists!";"#,
+                cursor,
+                account_id
+            )
+            .fetch_one(&self.pool)
+            .await;
+
+            if let Err(e) = res {
+                error!("[DB] Failed to check logs cursor {}", cursor);
+                return Err(e);
+            }
+
+            if !res.unwrap().exists {
+                debug!(
+                    "[DB] Logs cursor {} is not an entry of account {}",
+                    cursor, account_id
+                );
+                return Ok(None);
+            }
+        }
+
+        let log_types = filter
+            .log_types
+            .iter()
+            .map(|log_type| (*log_type).into())
+            .collect::<Vec<String>>();
+
+        // Same keyset pagination as payments, on (created_at, id).
+        let res = if filter.ascending {
+            sqlx::query!(
+                r#"SELECT id, action, payment_id, data, encryption_method, created_at FROM logs
+                    WHERE account_id = $1
+                    AND (CARDINALITY($2::TEXT[]) = 0 OR action = ANY($2))
+                    AND ($3::UUID IS NULL OR (created_at, id) > (SELECT created_at, id FROM logs WHERE id = $3 AND account_id = $1))
+                    AND ($5::UUID IS NULL OR payment_id = $5)
+                ORDER BY created_at ASC, id ASC
+                LIMIT $4;"#,
+                account_id,
+                &log_types,
+                filter.cursor,
+                filter.limit,
+                filter.payment_id
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|row| {
+                        (
+                            row.id,
+                            row.action,
+                            row.payment_id,
+                            row.data,
+                            row.encryption_method,
+                            row.created_at,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+        } else {
+            sqlx::query!(
+                r#"SELECT id, action, payment_id, data, encryption_method, created_at FROM logs
+                    WHERE account_id = $1
+                    AND (CARDINALITY($2::TEXT[]) = 0 OR action = ANY($2))
+                    AND ($3::UUID IS NULL OR (created_at, id) < (SELECT created_at, id FROM logs WHERE id = $3 AND account_id = $1))
+                    AND ($5::UUID IS NULL OR payment_id = $5)
+                ORDER BY created_at DESC, id DESC
+                LIMIT $4;"#,
+                account_id,
+                &log_types,
+                filter.cursor,
+                filter.limit,
+                filter.payment_id
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|row| {
+                        (
+                            row.id,
+                            row.action,
+                            row.payment_id,
+                            row.data,
+                            row.encryption_method,
+                            row.created_at,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get logs of account {}", account_id);
+            return Err(e);
+        }
+
+        let logs = res
+            .unwrap()
+            .into_iter()
+            .map(
+                |(id, action, payment_id, data, encryption_method, created_at)| LogEntry {
+                    id,
+                    log_type: action.as_str().into(),
+                    payment_id,
+                    data: match (data, encryption_method) {
+                        (Some(data), Some(encryption_method)) => {
+                            Some(decrypt_string(&data, encryption_method.into()))
+                        }
+                        (data, None) => data,
+                        (None, Some(_)) => None,
+                    },
+                    created_at,
+                },
+            )
+            .collect::<Vec<_>>();
+
+        debug!("[DB] Got {} logs of account {}", logs.len(), account_id);
+
+        Ok(Some(logs))
+    }
+
+    async fn cancel_payment(&self, payment_id: &Uuid) -> Result<bool, sqlx::Error> {
+        debug!("[DB] Cancelling payment {}", payment_id);
+
+        let res = sqlx::query!(
+            r#"WITH cancelled AS (
+                UPDATE payments SET expired = TRUE, refund_required = received > 0
+                    WHERE id = $1 AND completed = FALSE AND expired = FALSE
+                RETURNING id
+            ), released AS (
+                DELETE FROM domain_reservations WHERE payment_id IN (SELECT id FROM cancelled)
+            )
+            SELECT id as "id!" FROM cancelled;"#,
+            payment_id
+        )
+        .fetch_optional(&self.pool)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to cancel payment {}", payment_id);
+            return Err(e);
+        }
+
+        let cancelled = res.unwrap().is_some();
+
+        debug!("[DB] Cancelled payment {}: {}", payment_id, cancelled);
+
+        Ok(cancelled)
+    }
+
+    async fn requeue_inscription(
+        &self,
+        payment_id: &Uuid,
+    ) -> Result<InscriptionRequeue, sqlx::Error> {
+        debug!("[DB] Requeueing inscription of payment {}", payment_id);
+
+        let mut tx = self.pool.begin().await?;
+
+        let res = sqlx::query!(
+            r#"SELECT id FROM payments WHERE id = $1 AND initiated = TRUE AND expired = FALSE FOR UPDATE;"#,
+            payment_id
+        )
+        .fetch_optional(&mut *tx)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get payment {}", payment_id);
+            return Err(e);
+        }
+
+        if res.unwrap().is_none() {
+            tx.rollback().await?;
+            return Ok(InscriptionRequeue::NotInitiated);
+        }
+
+        let res = sqlx::query!(
+            r#"SELECT EXISTS (
+                SELECT 1 FROM payment_inscriptions
+                INNER JOIN payment_inscription_contents ON payment_inscription_contents.id = payment_inscriptions.content
+                WHERE payment_inscription_contents.payment_id = $1
+            ) as "revealed!";"#,
+            payment_id
+        )
+        .fetch_one(&mut *tx)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to get inscriptions of payment {}", payment_id);
+            return Err(e);
+        }
+
+        if res.unwrap().revealed {
+            tx.rollback().await?;
+            return Ok(InscriptionRequeue::AlreadyRevealed);
+        }
+
+        let res = sqlx::query!(
+            r#"UPDATE payments SET completed = FALSE WHERE id = $1;"#,
+            payment_id
+        )
+        .execute(&mut *tx)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to requeue inscription of payment {}",
+                payment_id
+            );
+            return Err(e);
+        }
+
+        tx.commit().await?;
+
+        debug!("[DB] Requeued inscription of payment {}", payment_id);
+
+        Ok(InscriptionRequeue::Requeued)
+    }
+}
+
+impl SessionRepository for SqlxPostgresqlRepository {
+    async fn get_session(&self, token: &str) -> Result<Uuid, sqlx::Error> {
+        debug!("[DB] Getting session {}", token);
+        let id = sqlx::query!(r#"SELECT account_id FROM sessions WHERE id = $1;"#, token)
+            .fetch_one(&self.pool)
+            .await?;
+
+        debug!("[DB] Got session {} from token {}", id.account_id, token);
+
+        Ok(id.account_id)
+    }
+
+    async fn is_admin(&self, account_id: &Uuid) -> Result<bool, sqlx::Error> {
+        debug!("[DB] Checking if account {} is an admin", account_id);
+        let res = sqlx::query!(
+            r#"SELECT account_id FROM admins WHERE account_id = $1;"#,
+            account_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(res.is_some())
     }
 }
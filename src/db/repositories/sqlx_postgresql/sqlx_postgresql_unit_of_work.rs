@@ -0,0 +1,478 @@
+use chrono::NaiveDateTime;
+use sqlx::{Postgres, Transaction};
+use tracing::{debug, error};
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        log::LogEvent,
+        repositories::models::{payment::Payment, transfer::TransferStatus},
+        traits::UnitOfWork,
+    },
+    utils::encryption::encrypt_string,
+};
+
+pub struct SqlxPostgresqlUnitOfWork {
+    tx: Transaction<'static, Postgres>,
+}
+
+impl SqlxPostgresqlUnitOfWork {
+    pub(super) fn new(tx: Transaction<'static, Postgres>) -> Self {
+        Self { tx }
+    }
+}
+
+impl UnitOfWork for SqlxPostgresqlUnitOfWork {
+    async fn create_payment(
+        &mut self,
+        account_id: &Uuid,
+        address: &str,
+        amount: f64,
+        expiration_minutes: i32,
+    ) -> Result<Payment, sqlx::Error> {
+        debug!("[DB] Creating payment for account {}", account_id);
+
+        let res = sqlx::query!(
+            r#"INSERT INTO payments (account_id, address, amount, expires_at) VALUES ($1, $2, $3, NOW() + make_interval(mins => $4)) RETURNING *;"#,
+            account_id,
+            address,
+            amount,
+            expiration_minutes
+        )
+        .fetch_one(&mut *self.tx)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to create payment for account {}", account_id);
+            return Err(e);
+        }
+
+        let row = res.unwrap();
+
+        let res = sqlx::query!(
+            r#"INSERT INTO issued_addresses (address, account_id, payment_id) VALUES ($1, $2, $3) ON CONFLICT (address) DO UPDATE SET payment_id = $3;"#,
+            address,
+            account_id,
+            row.id
+        )
+        .execute(&mut *self.tx)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to register issued address {} for account {}",
+                address, account_id
+            );
+            return Err(e);
+        }
+
+        debug!("[DB] Created payment {} for account {}", row.id, account_id);
+
+        Ok(Payment {
+            id: row.id,
+            account_id: row.account_id,
+            address: row.address,
+            amount: row.amount,
+            received: row.received,
+            lightning_invoice: row.lightning_invoice,
+            initiated: row.initiated,
+            completed: row.completed,
+            expired: row.expired,
+            refund_required: row.refund_required,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            expires_at: row.expires_at,
+        })
+    }
+
+    async fn set_lightning_invoice(
+        &mut self,
+        payment_id: &Uuid,
+        invoice: &str,
+        payment_hash: &str,
+        expires_at: &NaiveDateTime,
+    ) -> Result<(), sqlx::Error> {
+        debug!("[DB] Setting lightning invoice of payment {}", payment_id);
+
+        let res = sqlx::query!(
+            r#"UPDATE payments SET lightning_invoice = $2, lightning_payment_hash = $3, lightning_expires_at = $4 WHERE id = $1;"#,
+            payment_id,
+            invoice,
+            payment_hash,
+            expires_at
+        )
+        .execute(&mut *self.tx)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to set lightning invoice of payment {}",
+                payment_id
+            );
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    async fn reserve_domains(
+        &mut self,
+        payment_id: &Uuid,
+        domains: &[String],
+    ) -> Result<Vec<String>, sqlx::Error> {
+        debug!(
+            "[DB] Reserving domains {:?} for payment {}",
+            domains, payment_id
+        );
+
+        // Undo the domains that did get reserved when some conflict, without losing the rest of
+        // the unit of work.
+        let mut savepoint = sqlx::Connection::begin(&mut *self.tx).await?;
+
+        let res = sqlx::query!(
+            r#"INSERT INTO domain_reservations (domain, payment_id) SELECT UNNEST($1::TEXT[]), $2 ON CONFLICT (domain) DO NOTHING RETURNING domain;"#,
+            domains,
+            payment_id
+        )
+        .fetch_all(&mut *savepoint)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to reserve domains {:?} for payment {}",
+                domains, payment_id
+            );
+            return Err(e);
+        }
+
+        let reserved = res
+            .unwrap()
+            .into_iter()
+            .map(|row| row.domain)
+            .collect::<Vec<_>>();
+
+        let conflicts = domains
+            .iter()
+            .filter(|domain| !reserved.contains(domain))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if conflicts.is_empty() {
+            savepoint.commit().await?;
+            debug!("[DB] Reserved domains for payment {}", payment_id);
+        } else {
+            savepoint.rollback().await?;
+            debug!("[DB] Domains {:?} are already reserved", conflicts);
+        }
+
+        Ok(conflicts)
+    }
+
+    async fn create_payment_inscription(
+        &mut self,
+        payment_id: &Uuid,
+        target: &str,
+        contents: &str,
+    ) -> Result<Uuid, sqlx::Error> {
+        debug!(
+            "[DB] Creating payment inscription {} {} {}",
+            payment_id, target, contents
+        );
+
+        let res = sqlx::query!(
+            r#"INSERT INTO payment_inscription_contents (payment_id, target, content) VALUES ($1, $2, $3) RETURNING id;"#,
+            payment_id,
+            target,
+            contents
+        )
+        .fetch_one(&mut *self.tx)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to create payment inscription {} {} {}",
+                payment_id, target, contents
+            );
+            return Err(e);
+        }
+
+        debug!(
+            "[DB] Created payment inscription {} {} {}",
+            payment_id, target, contents
+        );
+
+        Ok(res.unwrap().id)
+    }
+
+    async fn add_private_key(
+        &mut self,
+        account_id: &Uuid,
+        payment_inscription_content_id: &Uuid,
+        domain: &str,
+        private_key: &str,
+    ) -> Result<(), sqlx::Error> {
+        debug!(
+            "[DB] Adding private key for account {} and domain {}",
+            account_id, domain
+        );
+
+        let (private_key, encryption_method) = encrypt_string(private_key);
+
+        let res = sqlx::query!(
+            r#"INSERT INTO private_keys (account_id, payment_inscription_content_id, domain, encryption_method, private_key) VALUES ($1, $2, $3, $4, $5);"#,
+            account_id,
+            payment_inscription_content_id,
+            domain,
+            encryption_method as i16,
+            private_key
+        )
+        .execute(&mut *self.tx)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to add private key for account {} and domain {}",
+                account_id, domain
+            );
+            return Err(e);
+        }
+
+        debug!(
+            "[DB] Added private key for account {} and domain {}",
+            account_id, domain
+        );
+
+        Ok(())
+    }
+
+    async fn add_domain_update(
+        &mut self,
+        payment_inscription_content_id: &Uuid,
+        domain: &str,
+    ) -> Result<(), sqlx::Error> {
+        debug!(
+            "[DB] Adding update {} of domain {}",
+            payment_inscription_content_id, domain
+        );
+
+        let res = sqlx::query!(
+            r#"INSERT INTO domain_updates (payment_inscription_content_id, domain) VALUES ($1, $2);"#,
+            payment_inscription_content_id,
+            domain
+        )
+        .execute(&mut *self.tx)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to add update {} of domain {}",
+                payment_inscription_content_id, domain
+            );
+            return Err(e);
+        }
+
+        debug!(
+            "[DB] Added update {} of domain {}",
+            payment_inscription_content_id, domain
+        );
+
+        Ok(())
+    }
+
+    async fn add_log(&mut self, account_id: &Uuid, event: &LogEvent) -> Result<(), sqlx::Error> {
+        debug!("[DB] Adding log {} {:?}", account_id, event);
+
+        let log_type: &str = event.log_type().into();
+        let payment_id = event.payment_id();
+        let (log_data, encryption_method) = encrypt_string(&event.to_json());
+        let res = sqlx::query!(
+            r#"INSERT INTO logs (account_id, action, payment_id, data, encryption_method) VALUES ($1, $2, $3, $4, $5);"#,
+            account_id,
+            log_type,
+            payment_id,
+            log_data,
+            encryption_method as i16
+        )
+        .execute(&mut *self.tx)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to add log {} {:?} {:?}",
+                account_id, log_type, log_data
+            );
+            return Err(e);
+        }
+
+        debug!("[DB] Added log to account {}", account_id);
+
+        Ok(())
+    }
+
+    async fn accept_transfer(&mut self, id: &Uuid) -> Result<bool, sqlx::Error> {
+        debug!("[DB] Accepting transfer {}", id);
+
+        let pending: &str = TransferStatus::Pending.into();
+        let accepted: &str = TransferStatus::Accepted.into();
+        let res = sqlx::query!(
+            r#"UPDATE domain_transfers SET status = $1, updated_at = NOW() WHERE id = $2 AND status = $3 RETURNING domain, from_account_id, to_account_id;"#,
+            accepted,
+            id,
+            pending
+        )
+        .fetch_optional(&mut *self.tx)
+        .await;
+
+        if let Err(e) = res {
+            error!("[DB] Failed to accept transfer {}", id);
+            return Err(e);
+        }
+
+        let transfer = match res.unwrap() {
+            Some(transfer) => transfer,
+            None => return Ok(false),
+        };
+
+        if let Some(to_account_id) = transfer.to_account_id {
+            let res = sqlx::query!(
+                r#"UPDATE private_keys SET account_id = $1, custodial_signing = FALSE WHERE account_id = $2 AND domain = $3 AND retired_at IS NULL;"#,
+                to_account_id,
+                transfer.from_account_id,
+                transfer.domain
+            )
+            .execute(&mut *self.tx)
+            .await;
+
+            if let Err(e) = res {
+                error!(
+                    "[DB] Failed to move private key of {} for transfer {}",
+                    transfer.domain, id
+                );
+                return Err(e);
+            }
+
+            if res.unwrap().rows_affected() == 0 {
+                return Ok(false);
+            }
+        }
+
+        debug!("[DB] Accepted transfer {}", id);
+
+        Ok(true)
+    }
+
+    async fn set_transfer_payment(
+        &mut self,
+        id: &Uuid,
+        payment_id: &Uuid,
+    ) -> Result<(), sqlx::Error> {
+        debug!("[DB] Setting payment {} for transfer {}", payment_id, id);
+
+        let res = sqlx::query!(
+            r#"UPDATE domain_transfers SET payment_id = $1, updated_at = NOW() WHERE id = $2;"#,
+            payment_id,
+            id
+        )
+        .execute(&mut *self.tx)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to set payment {} for transfer {}",
+                payment_id, id
+            );
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    async fn claim_idempotency_key(
+        &mut self,
+        account_id: &Uuid,
+        key: &str,
+        request_hash: &str,
+        retention_hours: i32,
+    ) -> Result<bool, sqlx::Error> {
+        debug!(
+            "[DB] Claiming idempotency key {} of account {}",
+            key, account_id
+        );
+
+        // Keys past their retention window may be reused until they get cleaned up. The response
+        // is filled in before the transaction commits, so other requests never see it empty.
+        let res = sqlx::query!(
+            r#"INSERT INTO idempotency_keys (account_id, key, request_hash, response) VALUES ($1, $2, $3, '')
+            ON CONFLICT (account_id, key) DO UPDATE SET request_hash = $3, response = '', created_at = NOW()
+            WHERE idempotency_keys.created_at <= NOW() - make_interval(hours => $4)
+            RETURNING key;"#,
+            account_id,
+            key,
+            request_hash,
+            retention_hours
+        )
+        .fetch_optional(&mut *self.tx)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to claim idempotency key {} of account {}",
+                key, account_id
+            );
+            return Err(e);
+        }
+
+        let claimed = res.unwrap().is_some();
+
+        debug!(
+            "[DB] Claimed idempotency key {} of account {}: {}",
+            key, account_id, claimed
+        );
+
+        Ok(claimed)
+    }
+
+    async fn store_idempotent_response(
+        &mut self,
+        account_id: &Uuid,
+        key: &str,
+        response: &str,
+    ) -> Result<(), sqlx::Error> {
+        debug!(
+            "[DB] Storing response for idempotency key {} of account {}",
+            key, account_id
+        );
+
+        let res = sqlx::query!(
+            r#"UPDATE idempotency_keys SET response = $3 WHERE account_id = $1 AND key = $2;"#,
+            account_id,
+            key,
+            response
+        )
+        .execute(&mut *self.tx)
+        .await;
+
+        if let Err(e) = res {
+            error!(
+                "[DB] Failed to store response for idempotency key {} of account {}",
+                key, account_id
+            );
+            return Err(e);
+        }
+
+        debug!(
+            "[DB] Stored response for idempotency key {} of account {}",
+            key, account_id
+        );
+
+        Ok(())
+    }
+
+    async fn commit(self) -> Result<(), sqlx::Error> {
+        self.tx.commit().await
+    }
+
+    async fn rollback(self) -> Result<(), sqlx::Error> {
+        self.tx.rollback().await
+    }
+}
@@ -1,5 +1,7 @@
 pub mod repository;
 pub mod session_repository;
+pub mod unit_of_work;
 
 pub(crate) use repository::PaymentRepository;
 pub(crate) use session_repository::SessionRepository;
+pub(crate) use unit_of_work::UnitOfWork;
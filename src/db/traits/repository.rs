@@ -1,21 +1,148 @@
+use chrono::NaiveDateTime;
 use uuid::Uuid;
 
-use crate::db::{log::LogTypes, repositories::models::payment::Payment};
+use crate::db::{
+    log::{LogEvent, LogTypes},
+    repositories::models::{
+        domain_rule::{DomainList, DomainRule},
+        late_payment::{LatePayment, LatePaymentStatus},
+        payment::Payment,
+        refund::{Refund, RefundStatus},
+        transfer::{DomainTransfer, TransferStatus},
+    },
+    traits::UnitOfWork,
+};
 
 pub struct LoyaltyDiscount(pub String, pub f64, pub String, pub String, pub bool);
 
+#[derive(Debug, Clone, Default)]
+pub struct PaymentsFilter {
+    pub initiated: Option<bool>,
+    pub completed: Option<bool>,
+    pub expired: Option<bool>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+    /// Id of the last payment of the previous page.
+    pub cursor: Option<Uuid>,
+    pub ascending: bool,
+    pub limit: i64,
+}
+
+/// Admin search criteria, a payment has to match all of the given ones.
+#[derive(Debug, Clone, Default)]
+pub struct PaymentSearch {
+    pub account_id: Option<Uuid>,
+    pub address: Option<String>,
+    pub transaction_id: Option<String>,
+    pub domain: Option<String>,
+    pub limit: i64,
+}
+
+/// An inscription of a payment, `domain` is set for the registration of a domain.
+#[derive(Debug, Clone)]
+pub struct PaymentInscription {
+    pub id: Uuid,
+    pub domain: Option<String>,
+    pub target: String,
+    pub content: String,
+    pub reveal_tx: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LogsFilter {
+    /// Only entries of these types, all types when empty.
+    pub log_types: Vec<LogTypes>,
+    /// Only entries about this payment.
+    pub payment_id: Option<Uuid>,
+    /// Id of the last entry of the previous page.
+    pub cursor: Option<Uuid>,
+    pub ascending: bool,
+    /// Every entry when not set.
+    pub limit: Option<i64>,
+}
+
+/// A decrypted entry of the `logs` table.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub id: Uuid,
+    pub log_type: LogTypes,
+    pub payment_id: Option<Uuid>,
+    pub data: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// A domain of an account together with the inscription that registered it.
+#[derive(Debug, Clone)]
+pub struct OwnedDomain {
+    pub domain: String,
+    pub target: String,
+    pub inscription: String,
+    pub private_key: String,
+    pub custodial_signing: bool,
+    pub completed: bool,
+}
+
+/// Public facts about a domain held by some account, without the account itself.
+#[derive(Debug, Clone)]
+pub struct DomainRegistration {
+    pub domain: String,
+    pub target: String,
+    pub inscription: String,
+    pub initiated: bool,
+    pub completed: bool,
+    pub reveal_tx: Option<String>,
+}
+
+/// An order with a lightning invoice that still has to be watched.
+#[derive(Debug, Clone)]
+pub struct LightningPayment {
+    pub payment_id: Uuid,
+    pub account_id: Uuid,
+    pub address: String,
+    pub payment_hash: String,
+    pub initiated: bool,
+}
+
+/// Outcome of crediting a late payment to its order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatePaymentReactivation {
+    Reactivated,
+    /// The late payment was no longer pending.
+    AlreadyHandled,
+    /// The order was deleted, the late payment can only be refunded.
+    OrderDeleted,
+    /// Domains of the expired order have been reserved by another order since.
+    DomainsReserved,
+}
+
+/// Outcome of handing a payment back to the inscriber.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InscriptionRequeue {
+    Requeued,
+    /// The payment is not initiated or has expired.
+    NotInitiated,
+    /// Some of its inscriptions are already on chain, requeueing would inscribe them twice.
+    AlreadyRevealed,
+}
+
+/// The response stored for an idempotency key, with the hash of the request that produced it.
+#[derive(Debug, Clone)]
+pub struct IdempotentResponse {
+    pub request_hash: String,
+    pub response: String,
+}
+
 pub trait PaymentRepository
 where
     Self: Clone,
 {
+    type UnitOfWork: UnitOfWork;
+
     async fn new() -> Self;
 
-    async fn add_log(
-        &self,
-        account_id: &Uuid,
-        log_type: LogTypes,
-        log_data: Option<&str>,
-    ) -> Result<(), sqlx::Error>;
+    async fn begin(&self) -> Result<Self::UnitOfWork, sqlx::Error>;
+
+    async fn add_log(&self, account_id: &Uuid, event: &LogEvent) -> Result<(), sqlx::Error>;
 
     async fn add_payment_received(
         &self,
@@ -26,13 +153,6 @@ where
 
     async fn complete_payment(&self, payment_id: &Uuid) -> Result<(), sqlx::Error>;
 
-    async fn create_payment(
-        &self,
-        account_id: &Uuid,
-        address: &str,
-        amount: f64,
-    ) -> Result<Uuid, sqlx::Error>;
-
     async fn create_payment_inscription(
         &self,
         payment_id: &Uuid,
@@ -44,6 +164,28 @@ where
 
     async fn get_to_be_initiated_addresses(&self) -> Result<Vec<String>, sqlx::Error>;
 
+    /// Every address ever handed out for an order, including those of expired and deleted orders.
+    async fn get_watched_addresses(&self) -> Result<Vec<String>, sqlx::Error>;
+
+    async fn get_issued_address_account(&self, address: &str) -> Result<Option<Uuid>, sqlx::Error>;
+
+    /// Orders with a lightning invoice that are neither completed nor expired, and any other order
+    /// whose invoice expired less than `LIGHTNING_EXPIRY_GRACE_HOURS` ago, as it may still have
+    /// been paid after the order was paid on chain, completed or cancelled.
+    async fn get_pending_lightning_payments(&self) -> Result<Vec<LightningPayment>, sqlx::Error>;
+
+    /// Initiates the payment and credits the settled invoice in one transaction. Returns false
+    /// without crediting anything when the payment was already initiated or has expired.
+    async fn credit_lightning_payment(
+        &self,
+        payment_id: &Uuid,
+        received: f64,
+        transaction_id: &str,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Reserves the next unused derivation index of `descriptor`.
+    async fn next_derivation_index(&self, descriptor: &str) -> Result<u32, sqlx::Error>;
+
     async fn get_to_be_completed_payments(&self) -> Result<Vec<Uuid>, sqlx::Error>;
 
     async fn get_payment(&self, payment_id: &Uuid) -> Result<Option<Payment>, sqlx::Error>;
@@ -54,6 +196,14 @@ where
         address: &str,
     ) -> Result<bool, sqlx::Error>;
 
+    /// A page of the payments of an account with their domains. Returns `None` when the cursor is
+    /// not a payment of the account.
+    async fn get_payments(
+        &self,
+        account_id: &Uuid,
+        filter: &PaymentsFilter,
+    ) -> Result<Option<Vec<(Payment, Vec<String>)>>, sqlx::Error>;
+
     async fn get_payment_by_address(&self, address: &str) -> Result<Option<Payment>, sqlx::Error>;
 
     async fn add_payment_inscription_contents(
@@ -87,13 +237,37 @@ where
         account_id: &Uuid,
     ) -> Result<Vec<(String, bool, Option<String>)>, sqlx::Error>;
 
+    async fn get_owned_domain(
+        &self,
+        account_id: &Uuid,
+        domain: &str,
+    ) -> Result<Option<OwnedDomain>, sqlx::Error>;
+
+    /// Returns false when the account does not own the domain.
+    async fn set_custodial_signing(
+        &self,
+        account_id: &Uuid,
+        domain: &str,
+        enabled: bool,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// The registration holding `domain`, including unpaid orders that have not expired yet.
+    async fn get_domain_registration(
+        &self,
+        domain: &str,
+    ) -> Result<Option<DomainRegistration>, sqlx::Error>;
+
     async fn get_already_owned_domains(
         &self,
         user: &Uuid,
         domains: &[String],
     ) -> Result<Vec<String>, sqlx::Error>;
 
-    async fn cleanup_old_orders(&self) -> Result<(), sqlx::Error>;
+    /// Moves uninitiated payments past their `expires_at` into the expired state, returning the
+    /// (payment id, account id) pairs that were expired.
+    async fn expire_old_orders(&self) -> Result<Vec<(Uuid, Uuid)>, sqlx::Error>;
+
+    async fn flag_payment_for_refund(&self, payment_id: &Uuid) -> Result<(), sqlx::Error>;
 
     async fn get_loyalty_discounts_for_collections(
         &self,
@@ -107,4 +281,195 @@ where
     ) -> Result<Result<(), ()>, sqlx::Error>;
 
     async fn get_addresses(&self, account_id: &Uuid) -> Result<Vec<String>, sqlx::Error>;
+
+    async fn get_payment_domains(&self, payment_id: &Uuid) -> Result<Vec<String>, sqlx::Error>;
+
+    /// Brings an expired payment back and reserves its domains again, returns false without
+    /// changing anything when one of them has been reserved by another order in the meantime.
+    async fn reactivate_payment(&self, payment_id: &Uuid) -> Result<bool, sqlx::Error>;
+
+    async fn is_late_payment_recorded(
+        &self,
+        transaction_id: &str,
+        address: &str,
+    ) -> Result<bool, sqlx::Error>;
+
+    async fn add_late_payment(
+        &self,
+        account_id: &Uuid,
+        payment_id: Option<&Uuid>,
+        address: &str,
+        transaction_id: &str,
+        amount: f64,
+    ) -> Result<Uuid, sqlx::Error>;
+
+    async fn get_late_payment(&self, id: &Uuid) -> Result<Option<LatePayment>, sqlx::Error>;
+
+    async fn get_late_payments(
+        &self,
+        status: Option<LatePaymentStatus>,
+    ) -> Result<Vec<LatePayment>, sqlx::Error>;
+
+    /// Moves a pending late payment into `status`, returns false when it was no longer pending.
+    async fn set_late_payment_status(
+        &self,
+        id: &Uuid,
+        status: LatePaymentStatus,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Claims a pending late payment and credits it to its order in one transaction, bringing
+    /// the order back first when it has expired. Nothing changes unless it is `Reactivated`.
+    async fn reactivate_late_payment(
+        &self,
+        id: &Uuid,
+    ) -> Result<LatePaymentReactivation, sqlx::Error>;
+
+    /// Requests a refund and moves its late payment, if any, into the refund state in the same
+    /// transaction. `refunded` is what the payment had been refunded when `amount` was worked out.
+    /// Returns `None` without creating anything when another refund of the payment came in since,
+    /// the late payment already has a refund that was not rejected or has been reactivated.
+    async fn create_refund(
+        &self,
+        account_id: &Uuid,
+        payment_id: Option<&Uuid>,
+        late_payment_id: Option<&Uuid>,
+        amount: f64,
+        refunded: f64,
+        address: &str,
+    ) -> Result<Option<Uuid>, sqlx::Error>;
+
+    async fn get_refund(&self, id: &Uuid) -> Result<Option<Refund>, sqlx::Error>;
+
+    /// Lists refunds, limited to a single account when `account_id` is given.
+    async fn get_refunds(
+        &self,
+        account_id: Option<&Uuid>,
+        status: Option<RefundStatus>,
+    ) -> Result<Vec<Refund>, sqlx::Error>;
+
+    /// Sum of the refunds of a payment that have not been rejected.
+    async fn get_refunded_amount(&self, payment_id: &Uuid) -> Result<f64, sqlx::Error>;
+
+    /// Moves a requested refund into processing, returns false when it was not requested.
+    async fn claim_refund(&self, id: &Uuid) -> Result<bool, sqlx::Error>;
+
+    async fn release_refund(&self, id: &Uuid) -> Result<(), sqlx::Error>;
+
+    async fn complete_refund(
+        &self,
+        id: &Uuid,
+        transaction_id: &str,
+        fee_rate: f64,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn reject_refund(&self, id: &Uuid, reason: &str) -> Result<bool, sqlx::Error>;
+
+    /// (address, payment id) of every completed payment.
+    async fn get_completed_payment_addresses(&self) -> Result<Vec<(String, Uuid)>, sqlx::Error>;
+
+    /// Records a broadcast sweep with its (transaction id, vout, payment id) inputs.
+    async fn add_sweep(
+        &self,
+        transaction_id: &str,
+        address: &str,
+        amount: f64,
+        fee: f64,
+        fee_rate: f64,
+        inputs: &[(String, i32, Uuid)],
+    ) -> Result<Uuid, sqlx::Error>;
+
+    /// Creates a pending transfer, returns `None` when the domain already has one.
+    async fn create_transfer(
+        &self,
+        domain: &str,
+        from_account_id: &Uuid,
+        to_account_id: Option<&Uuid>,
+        to_address: Option<&str>,
+        inscribe: bool,
+    ) -> Result<Option<DomainTransfer>, sqlx::Error>;
+
+    async fn get_transfer(&self, id: &Uuid) -> Result<Option<DomainTransfer>, sqlx::Error>;
+
+    /// Transfers the account is either the sender or the recipient of.
+    async fn get_transfers(&self, account_id: &Uuid) -> Result<Vec<DomainTransfer>, sqlx::Error>;
+
+    /// Moves a pending transfer into `status`, returns false when it was no longer pending.
+    async fn close_transfer(&self, id: &Uuid, status: TransferStatus) -> Result<bool, sqlx::Error>;
+
+    /// Cancels the accepted external transfers whose order expired without being paid, returning
+    /// them.
+    async fn cancel_unpaid_transfers(&self) -> Result<Vec<DomainTransfer>, sqlx::Error>;
+
+    /// Retires the sender's private key of every accepted external transfer whose inscription
+    /// completed, returning the transfer ids. The key row keeps the domain registered.
+    async fn retire_transferred_keys(&self) -> Result<Vec<Uuid>, sqlx::Error>;
+
+    /// Rules on `domains` that have not been released yet.
+    async fn get_active_domain_rules(
+        &self,
+        domains: &[String],
+    ) -> Result<Vec<DomainRule>, sqlx::Error>;
+
+    async fn get_domain_rules(
+        &self,
+        list: Option<DomainList>,
+    ) -> Result<Vec<DomainRule>, sqlx::Error>;
+
+    async fn set_domain_rule(
+        &self,
+        domain: &str,
+        list: DomainList,
+        reason: &str,
+        allocated_account_id: Option<&Uuid>,
+        release_at: Option<NaiveDateTime>,
+    ) -> Result<DomainRule, sqlx::Error>;
+
+    /// Returns false when there was no rule for `domain`.
+    async fn delete_domain_rule(&self, domain: &str) -> Result<bool, sqlx::Error>;
+
+    /// The response stored for `key` in the last `retention_hours`.
+    async fn get_idempotent_response(
+        &self,
+        account_id: &Uuid,
+        key: &str,
+        retention_hours: i32,
+    ) -> Result<Option<IdempotentResponse>, sqlx::Error>;
+
+    async fn delete_expired_idempotency_keys(
+        &self,
+        retention_hours: i32,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Payments matching `search` with their domains, newest first.
+    async fn search_payments(
+        &self,
+        search: &PaymentSearch,
+    ) -> Result<Vec<(Payment, Vec<String>)>, sqlx::Error>;
+
+    async fn get_payment_transactions(&self, payment_id: &Uuid)
+        -> Result<Vec<String>, sqlx::Error>;
+
+    async fn get_payment_inscriptions(
+        &self,
+        payment_id: &Uuid,
+    ) -> Result<Vec<PaymentInscription>, sqlx::Error>;
+
+    /// A page of the log entries of an account. Returns `None` when the cursor is not an entry of
+    /// the account.
+    async fn get_logs(
+        &self,
+        account_id: &Uuid,
+        filter: &LogsFilter,
+    ) -> Result<Option<Vec<LogEntry>>, sqlx::Error>;
+
+    /// Expires a payment that has not been completed, releasing its domains and flagging the funds
+    /// it received for a refund. Returns false when it was already completed or expired.
+    async fn cancel_payment(&self, payment_id: &Uuid) -> Result<bool, sqlx::Error>;
+
+    /// Puts an initiated payment back in the inscription queue by marking it uncompleted. The
+    /// inscriptions it made are kept, so a payment with a revealed inscription is never requeued.
+    async fn requeue_inscription(
+        &self,
+        payment_id: &Uuid,
+    ) -> Result<InscriptionRequeue, sqlx::Error>;
 }
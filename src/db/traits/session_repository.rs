@@ -5,4 +5,6 @@ where
     Self: Clone,
 {
     async fn get_session(&self, token: &str) -> Result<Uuid, sqlx::Error>;
+
+    async fn is_admin(&self, account_id: &Uuid) -> Result<bool, sqlx::Error>;
 }
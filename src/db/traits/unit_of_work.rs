@@ -0,0 +1,97 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::db::{log::LogEvent, repositories::models::payment::Payment};
+
+/// Writes that either all become visible on `commit` or not at all. Dropping a unit of work
+/// without committing it rolls it back.
+pub trait UnitOfWork
+where
+    Self: Sized,
+{
+    async fn create_payment(
+        &mut self,
+        account_id: &Uuid,
+        address: &str,
+        amount: f64,
+        expiration_minutes: i32,
+    ) -> Result<Payment, sqlx::Error>;
+
+    /// Attaches the lightning invoice (bolt11, payment hash) to a payment created in this unit of
+    /// work.
+    async fn set_lightning_invoice(
+        &mut self,
+        payment_id: &Uuid,
+        invoice: &str,
+        payment_hash: &str,
+        expires_at: &NaiveDateTime,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Reserves `domains` for the payment, returning the domains that are already reserved by
+    /// another order. Nothing is reserved when there are conflicts.
+    async fn reserve_domains(
+        &mut self,
+        payment_id: &Uuid,
+        domains: &[String],
+    ) -> Result<Vec<String>, sqlx::Error>;
+
+    async fn create_payment_inscription(
+        &mut self,
+        payment_id: &Uuid,
+        target: &str,
+        contents: &str,
+    ) -> Result<Uuid, sqlx::Error>;
+
+    async fn add_private_key(
+        &mut self,
+        account_id: &Uuid,
+        payment_inscription_content_id: &Uuid,
+        domain: &str,
+        private_key: &str,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Marks the inscription content as a `DOMAIN-UPDATE` of `domain`.
+    async fn add_domain_update(
+        &mut self,
+        payment_inscription_content_id: &Uuid,
+        domain: &str,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn add_log(&mut self, account_id: &Uuid, event: &LogEvent) -> Result<(), sqlx::Error>;
+
+    /// Marks a pending transfer as accepted and, for transfers between accounts, moves the
+    /// private key (resetting custodial signing) to the recipient. Returns false when the
+    /// transfer was no longer pending or the sender no longer owns the domain, the caller then
+    /// has to roll back.
+    async fn accept_transfer(&mut self, id: &Uuid) -> Result<bool, sqlx::Error>;
+
+    /// Links the order paying for the transfer inscription to the transfer.
+    async fn set_transfer_payment(
+        &mut self,
+        id: &Uuid,
+        payment_id: &Uuid,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Claims an idempotency key for a request, returns false when another request claimed it
+    /// within the last `retention_hours`. Concurrent claims of the key wait for this unit of work
+    /// to end, which has to store the response before committing.
+    async fn claim_idempotency_key(
+        &mut self,
+        account_id: &Uuid,
+        key: &str,
+        request_hash: &str,
+        retention_hours: i32,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Stores the response for an idempotency key claimed by this unit of work.
+    async fn store_idempotent_response(
+        &mut self,
+        account_id: &Uuid,
+        key: &str,
+        response: &str,
+    ) -> Result<(), sqlx::Error>;
+
+    async fn commit(self) -> Result<(), sqlx::Error>;
+
+    async fn rollback(self) -> Result<(), sqlx::Error>;
+}
@@ -0,0 +1,88 @@
+use std::str::FromStr;
+
+use bitcoincore_rpc::bitcoin::Address;
+use poem_openapi::payload::PlainText;
+use poem_openapi::Object;
+use poem_openapi::{payload::Json, ApiResponse};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::bitcoin::processor::process_onchain_payments;
+use crate::bitcoin::wallet::WalletBackend;
+use crate::db::log::LogEvent;
+use crate::db::PaymentRepository;
+use crate::responses::error::ErrorResponse;
+use crate::CHAIN;
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct RescanAddressData {
+    reason: String,
+}
+
+#[derive(ApiResponse)]
+pub enum RescanAddressResponse {
+    #[oai(status = 200)]
+    Ok(PlainText<String>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+/// Runs payment detection for an issued address without the confirmation cap of the background
+/// processor, picking up outputs it missed.
+pub async fn rescan<R: PaymentRepository, W: WalletBackend>(
+    pool: &R,
+    wallet: &W,
+    admin: &Uuid,
+    address: &str,
+    data: &RescanAddressData,
+) -> RescanAddressResponse {
+    if data.reason.trim().is_empty() {
+        return RescanAddressResponse::BadRequest(Json("A reason is required".into()));
+    }
+
+    let address = match Address::from_str(address)
+        .ok()
+        .and_then(|address| address.require_network(CHAIN.network()).ok())
+    {
+        Some(address) => address,
+        None => return RescanAddressResponse::BadRequest(Json("Invalid address".into())),
+    };
+
+    let account_id = match pool.get_issued_address_account(&address.to_string()).await {
+        Ok(Some(account_id)) => account_id,
+        Ok(None) => return RescanAddressResponse::NotFound(Json("Not found".into())),
+        Err(e) => {
+            error!("Error getting issued address account: {}", e);
+            return RescanAddressResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    };
+
+    if let Err(e) =
+        process_onchain_payments(pool, wallet, std::slice::from_ref(&address), None).await
+    {
+        error!("Error rescanning address {}: {}", address, e);
+        return RescanAddressResponse::InternalServerError(Json("Internal server error".into()));
+    }
+
+    let event = LogEvent::AddressRescanned {
+        address: address.to_string(),
+        admin_id: *admin,
+        reason: data.reason.trim().to_string(),
+    };
+    let res = pool.add_log(&account_id, &event).await;
+
+    if let Err(e) = res {
+        error!("Error adding log: {}", e);
+    }
+
+    RescanAddressResponse::Ok(PlainText("ok".to_string()))
+}
@@ -0,0 +1,152 @@
+use chrono::NaiveDateTime;
+use poem_openapi::payload::PlainText;
+use poem_openapi::Object;
+use poem_openapi::{payload::Json, ApiResponse};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::db::repositories::models::domain_rule::{DomainList, DomainRule};
+use crate::db::PaymentRepository;
+use crate::responses::error::ErrorResponse;
+use crate::utils::domain_name::{self, DomainNameError};
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct SetDomainRuleData {
+    list: DomainList,
+    reason: String,
+    /// Only valid for reserved domains.
+    allocated_account_id: Option<Uuid>,
+    release_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct DomainRulesResponseObject {
+    rules: Vec<DomainRule>,
+}
+
+#[derive(ApiResponse)]
+pub enum DomainRulesResponse {
+    #[oai(status = 200)]
+    Ok(Json<DomainRulesResponseObject>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+#[derive(ApiResponse)]
+pub enum SetDomainRuleResponse {
+    #[oai(status = 200)]
+    Ok(Json<DomainRule>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+#[derive(ApiResponse)]
+pub enum DeleteDomainRuleResponse {
+    #[oai(status = 200)]
+    Ok(PlainText<String>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+/// Rules are kept on the normalized name, the built in reserved labels can not be listed.
+fn normalize(domain: &str) -> Result<String, String> {
+    match domain_name::normalize(domain) {
+        Ok(domain) => Ok(domain.ascii),
+        Err(DomainNameError::Reserved(domain)) => Err(format!(
+            "Domain {} is always reserved and can not be listed",
+            domain
+        )),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+pub async fn domain_rules<R: PaymentRepository>(
+    pool: &R,
+    list: Option<DomainList>,
+) -> DomainRulesResponse {
+    match pool.get_domain_rules(list).await {
+        Ok(rules) => DomainRulesResponse::Ok(Json(DomainRulesResponseObject { rules })),
+        Err(e) => {
+            error!("Error getting domain rules: {}", e);
+            DomainRulesResponse::InternalServerError(Json("Internal server error".into()))
+        }
+    }
+}
+
+pub async fn set<R: PaymentRepository>(
+    pool: &R,
+    admin: &Uuid,
+    domain: &str,
+    data: &SetDomainRuleData,
+) -> SetDomainRuleResponse {
+    let domain = match normalize(domain) {
+        Ok(domain) => domain,
+        Err(e) => return SetDomainRuleResponse::BadRequest(Json(e.as_str().into())),
+    };
+
+    if data.reason.trim().is_empty() {
+        return SetDomainRuleResponse::BadRequest(Json("A reason is required".into()));
+    }
+
+    if data.allocated_account_id.is_some() && data.list != DomainList::Reserved {
+        return SetDomainRuleResponse::BadRequest(Json(
+            "Only reserved domains can be allocated to an account".into(),
+        ));
+    }
+
+    match pool
+        .set_domain_rule(
+            &domain,
+            data.list,
+            data.reason.trim(),
+            data.allocated_account_id.as_ref(),
+            data.release_at,
+        )
+        .await
+    {
+        Ok(rule) => {
+            let list: &str = rule.list.into();
+            info!("Admin {} put {} on the {} list", admin, domain, list);
+            SetDomainRuleResponse::Ok(Json(rule))
+        }
+        Err(e) => {
+            error!("Failed to set domain rule: {}", e);
+            SetDomainRuleResponse::InternalServerError(Json("Internal server error".into()))
+        }
+    }
+}
+
+pub async fn delete<R: PaymentRepository>(
+    pool: &R,
+    admin: &Uuid,
+    domain: &str,
+) -> DeleteDomainRuleResponse {
+    let domain = match normalize(domain) {
+        Ok(domain) => domain,
+        Err(e) => return DeleteDomainRuleResponse::BadRequest(Json(e.as_str().into())),
+    };
+
+    match pool.delete_domain_rule(&domain).await {
+        Ok(true) => {
+            info!("Admin {} removed the rule for {}", admin, domain);
+            DeleteDomainRuleResponse::Ok(PlainText(format!("Rule for {} removed", domain)))
+        }
+        Ok(false) => DeleteDomainRuleResponse::NotFound(Json("Not found".into())),
+        Err(e) => {
+            error!("Failed to delete domain rule: {}", e);
+            DeleteDomainRuleResponse::InternalServerError(Json("Internal server error".into()))
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use poem_openapi::payload::PlainText;
+use poem_openapi::Object;
+use poem_openapi::{payload::Json, ApiResponse};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::db::log::LogEvent;
+use crate::db::repositories::models::late_payment::{LatePayment, LatePaymentStatus};
+use crate::db::traits::repository::LatePaymentReactivation;
+use crate::db::PaymentRepository;
+use crate::responses::error::ErrorResponse;
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct LatePaymentsResponseObject {
+    late_payments: Vec<LatePayment>,
+}
+
+#[derive(ApiResponse)]
+pub enum LatePaymentsResponse {
+    #[oai(status = 200)]
+    Ok(Json<LatePaymentsResponseObject>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+#[derive(ApiResponse)]
+pub enum LatePaymentActionResponse {
+    #[oai(status = 200)]
+    Ok(PlainText<String>),
+
+    #[oai(status = 404)]
+    NotFound(Json<ErrorResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+pub async fn late_payments<R: PaymentRepository>(
+    pool: &R,
+    status: Option<LatePaymentStatus>,
+) -> LatePaymentsResponse {
+    match pool.get_late_payments(status).await {
+        Ok(late_payments) => {
+            LatePaymentsResponse::Ok(Json(LatePaymentsResponseObject { late_payments }))
+        }
+        Err(e) => {
+            error!("Error getting late payments: {}", e);
+            LatePaymentsResponse::InternalServerError(Json("Internal server error".into()))
+        }
+    }
+}
+
+async fn get_pending_late_payment<R: PaymentRepository>(
+    pool: &R,
+    id: &Uuid,
+) -> Result<LatePayment, LatePaymentActionResponse> {
+    match pool.get_late_payment(id).await {
+        Ok(Some(late_payment)) if late_payment.status == LatePaymentStatus::Pending => {
+            Ok(late_payment)
+        }
+        Ok(Some(_)) => Err(LatePaymentActionResponse::Conflict(Json(
+            "Late payment has already been handled".into(),
+        ))),
+        Ok(None) => Err(LatePaymentActionResponse::NotFound(Json(
+            "Not found".into(),
+        ))),
+        Err(e) => {
+            error!("Error getting late payment: {}", e);
+            Err(LatePaymentActionResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            )))
+        }
+    }
+}
+
+/// Credits the late funds to their original order, bringing an expired order back to life when
+/// none of its domains have been taken in the meantime.
+pub async fn reactivate<R: PaymentRepository>(
+    pool: &R,
+    admin: &Uuid,
+    id: &Uuid,
+) -> LatePaymentActionResponse {
+    let late_payment = match get_pending_late_payment(pool, id).await {
+        Ok(late_payment) => late_payment,
+        Err(response) => return response,
+    };
+
+    let payment_id = match late_payment.payment_id {
+        Some(payment_id) => payment_id,
+        None => {
+            return LatePaymentActionResponse::Conflict(Json(
+                "The order of this late payment was deleted, it can only be refunded".into(),
+            ))
+        }
+    };
+
+    let payment = match pool.get_payment(&payment_id).await {
+        Ok(Some(payment)) => payment,
+        Ok(None) => {
+            return LatePaymentActionResponse::Conflict(Json(
+                "The order of this late payment was deleted, it can only be refunded".into(),
+            ))
+        }
+        Err(e) => {
+            error!("Error getting payment: {}", e);
+            return LatePaymentActionResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    };
+
+    if payment.expired {
+        let domains = match pool.get_payment_domains(&payment.id).await {
+            Ok(domains) => domains,
+            Err(e) => {
+                error!("Error getting payment domains: {}", e);
+                return LatePaymentActionResponse::InternalServerError(Json(
+                    "Internal server error".into(),
+                ));
+            }
+        };
+
+        match pool
+            .get_already_owned_domains(&payment.account_id, &domains)
+            .await
+        {
+            Ok(taken) if !taken.is_empty() => {
+                return LatePaymentActionResponse::Conflict(Json(
+                    format!(
+                        "The following domains have been taken since the order expired: {}",
+                        taken.join(", ")
+                    )
+                    .as_str()
+                    .into(),
+                ));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                error!("Failed to run front runner protection check: {}", e);
+                return LatePaymentActionResponse::InternalServerError(Json(
+                    "Internal server error".into(),
+                ));
+            }
+        }
+    }
+
+    // Claiming the late payment, reactivating the order and crediting it happen in one
+    // transaction, so concurrent requests can not credit the funds twice.
+    match pool.reactivate_late_payment(&late_payment.id).await {
+        Ok(LatePaymentReactivation::Reactivated) => {}
+        Ok(LatePaymentReactivation::AlreadyHandled) => {
+            return LatePaymentActionResponse::Conflict(Json(
+                "Late payment has already been handled".into(),
+            ))
+        }
+        Ok(LatePaymentReactivation::OrderDeleted) => {
+            return LatePaymentActionResponse::Conflict(Json(
+                "The order of this late payment was deleted, it can only be refunded".into(),
+            ))
+        }
+        Ok(LatePaymentReactivation::DomainsReserved) => {
+            return LatePaymentActionResponse::Conflict(Json(
+                "Some domains of this order have been reserved since it expired".into(),
+            ))
+        }
+        Err(e) => {
+            error!("Error reactivating late payment: {}", e);
+            return LatePaymentActionResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    }
+
+    let event = LogEvent::LatePaymentReactivated {
+        late_payment_id: late_payment.id,
+        payment_id: payment.id,
+        amount: late_payment.amount,
+        admin_id: *admin,
+    };
+    let res = pool.add_log(&late_payment.account_id, &event).await;
+
+    if let Err(e) = res {
+        error!("Error adding log: {}", e);
+    }
+
+    LatePaymentActionResponse::Ok(PlainText("ok".to_string()))
+}
+
+pub async fn refund<R: PaymentRepository>(
+    pool: &R,
+    admin: &Uuid,
+    id: &Uuid,
+) -> LatePaymentActionResponse {
+    let late_payment = match get_pending_late_payment(pool, id).await {
+        Ok(late_payment) => late_payment,
+        Err(response) => return response,
+    };
+
+    match pool
+        .set_late_payment_status(&late_payment.id, LatePaymentStatus::Refund)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return LatePaymentActionResponse::Conflict(Json(
+                "Late payment has already been handled".into(),
+            ))
+        }
+        Err(e) => {
+            error!("Error updating late payment status: {}", e);
+            return LatePaymentActionResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    }
+
+    let event = LogEvent::LatePaymentRefundApproved {
+        late_payment_id: late_payment.id,
+        payment_id: late_payment.payment_id,
+        amount: late_payment.amount,
+        admin_id: *admin,
+    };
+    let res = pool.add_log(&late_payment.account_id, &event).await;
+
+    if let Err(e) = res {
+        error!("Error adding log: {}", e);
+    }
+
+    LatePaymentActionResponse::Ok(PlainText("ok".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::test_utils::TestServices;
+
+    /// An expired order for `domain` with a late payment of the full amount.
+    async fn late_payment(
+        pool: &InMemoryRepository,
+        services: &TestServices,
+        user: &Uuid,
+        domain: &str,
+    ) -> (Uuid, Uuid) {
+        let order = services.order(pool, user, &[domain]).await;
+        pool.cancel_payment(&order.id()).await.unwrap();
+
+        let late_payment_id = pool
+            .add_late_payment(user, Some(&order.id()), order.address(), "tx", 0.0007)
+            .await
+            .unwrap();
+
+        (order.id(), late_payment_id)
+    }
+
+    #[tokio::test]
+    async fn credits_a_late_payment_once() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let admin = Uuid::new_v4();
+        let (payment_id, late_payment_id) = late_payment(&pool, &services, &user, "alice.o").await;
+
+        assert!(matches!(
+            reactivate(&pool, &admin, &late_payment_id).await,
+            LatePaymentActionResponse::Ok(_)
+        ));
+        assert!(matches!(
+            reactivate(&pool, &admin, &late_payment_id).await,
+            LatePaymentActionResponse::Conflict(_)
+        ));
+        assert!(matches!(
+            refund(&pool, &admin, &late_payment_id).await,
+            LatePaymentActionResponse::Conflict(_)
+        ));
+
+        let payment = pool.get_payment(&payment_id).await.unwrap().unwrap();
+        assert!(payment.initiated);
+        assert!(!payment.expired);
+        assert_eq!(payment.received, 0.0007);
+
+        let late_payment = pool
+            .get_late_payment(&late_payment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(late_payment.status, LatePaymentStatus::Reactivated);
+    }
+
+    #[tokio::test]
+    async fn leaves_the_late_payment_pending_when_domains_were_reserved() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let (payment_id, late_payment_id) = late_payment(&pool, &services, &user, "alice.o").await;
+
+        services.order(&pool, &user, &["alice.o"]).await;
+
+        assert!(matches!(
+            reactivate(&pool, &Uuid::new_v4(), &late_payment_id).await,
+            LatePaymentActionResponse::Conflict(_)
+        ));
+
+        let payment = pool.get_payment(&payment_id).await.unwrap().unwrap();
+        assert!(payment.expired);
+        assert_eq!(payment.received, 0f64);
+
+        let late_payment = pool
+            .get_late_payment(&late_payment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(late_payment.status, LatePaymentStatus::Pending);
+    }
+}
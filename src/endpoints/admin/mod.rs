@@ -0,0 +1,5 @@
+pub mod addresses;
+pub mod domain_rules;
+pub mod late_payments;
+pub mod payments;
+pub mod refunds;
@@ -0,0 +1,710 @@
+use poem_openapi::payload::PlainText;
+use poem_openapi::Object;
+use poem_openapi::{payload::Json, ApiResponse};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::db::log::{LogEvent, PaymentOverride};
+use crate::db::repositories::models::payment::Payment;
+use crate::db::traits::repository::{InscriptionRequeue, LogsFilter, PaymentSearch};
+use crate::db::PaymentRepository;
+use crate::endpoints::logs::AccountLog;
+use crate::responses::error::ErrorResponse;
+use crate::utils::domain_name;
+
+const DEFAULT_SEARCH_LIMIT: u32 = 25;
+const MAX_SEARCH_LIMIT: u32 = 100;
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct AdminPaymentEntry {
+    #[oai(flatten)]
+    payment: Payment,
+    domains: Vec<String>,
+}
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct AdminPaymentsResponseObject {
+    payments: Vec<AdminPaymentEntry>,
+}
+
+#[derive(ApiResponse)]
+pub enum AdminPaymentsResponse {
+    #[oai(status = 200)]
+    Ok(Json<AdminPaymentsResponseObject>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct TimelineInscription {
+    id: Uuid,
+    /// Set when the inscription registers a domain.
+    domain: Option<String>,
+    target: String,
+    content: String,
+    reveal_tx: Option<String>,
+}
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct PaymentTimeline {
+    payment: Payment,
+    transactions: Vec<String>,
+    inscriptions: Vec<TimelineInscription>,
+    /// Log entries about the payment, oldest first.
+    logs: Vec<AccountLog>,
+}
+
+#[derive(ApiResponse)]
+pub enum PaymentTimelineResponse {
+    #[oai(status = 200)]
+    Ok(Json<PaymentTimeline>),
+
+    #[oai(status = 404)]
+    NotFound(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct PaymentOverrideData {
+    reason: String,
+}
+
+#[derive(ApiResponse)]
+pub enum PaymentOverrideResponse {
+    #[oai(status = 200)]
+    Ok(PlainText<String>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<ErrorResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+pub async fn search<R: PaymentRepository>(
+    pool: &R,
+    mut search: PaymentSearch,
+    limit: Option<u32>,
+) -> AdminPaymentsResponse {
+    let limit = limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+
+    if limit == 0 || limit > MAX_SEARCH_LIMIT {
+        return AdminPaymentsResponse::BadRequest(Json(
+            format!("Limit must be between 1 and {}", MAX_SEARCH_LIMIT)
+                .as_str()
+                .into(),
+        ));
+    }
+
+    if search.account_id.is_none()
+        && search.address.is_none()
+        && search.transaction_id.is_none()
+        && search.domain.is_none()
+    {
+        return AdminPaymentsResponse::BadRequest(Json(
+            "Search by account, address, transaction or domain".into(),
+        ));
+    }
+
+    if let Some(domain) = &search.domain {
+        match domain_name::normalize(domain) {
+            Ok(domain) => search.domain = Some(domain.ascii),
+            Err(e) => {
+                return AdminPaymentsResponse::BadRequest(Json(e.to_string().as_str().into()))
+            }
+        }
+    }
+
+    search.limit = limit as i64;
+
+    match pool.search_payments(&search).await {
+        Ok(payments) => AdminPaymentsResponse::Ok(Json(AdminPaymentsResponseObject {
+            payments: payments
+                .into_iter()
+                .map(|(payment, domains)| AdminPaymentEntry { payment, domains })
+                .collect(),
+        })),
+        Err(e) => {
+            error!("Error searching payments: {}", e);
+            AdminPaymentsResponse::InternalServerError(Json("Internal server error".into()))
+        }
+    }
+}
+
+pub async fn timeline<R: PaymentRepository>(pool: &R, id: &Uuid) -> PaymentTimelineResponse {
+    let payment = match pool.get_payment(id).await {
+        Ok(Some(payment)) => payment,
+        Ok(None) => return PaymentTimelineResponse::NotFound(Json("Not found".into())),
+        Err(e) => {
+            error!("Error getting payment: {}", e);
+            return PaymentTimelineResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    };
+
+    let transactions = match pool.get_payment_transactions(&payment.id).await {
+        Ok(transactions) => transactions,
+        Err(e) => {
+            error!("Error getting payment transactions: {}", e);
+            return PaymentTimelineResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    };
+
+    let inscriptions = match pool.get_payment_inscriptions(&payment.id).await {
+        Ok(inscriptions) => inscriptions,
+        Err(e) => {
+            error!("Error getting payment inscriptions: {}", e);
+            return PaymentTimelineResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    };
+
+    let filter = LogsFilter {
+        payment_id: Some(payment.id),
+        ascending: true,
+        ..Default::default()
+    };
+    let logs = match pool.get_logs(&payment.account_id, &filter).await {
+        // Without a cursor every entry of the payment is returned.
+        Ok(logs) => logs
+            .unwrap_or_default()
+            .into_iter()
+            .map(AccountLog::from)
+            .collect(),
+        Err(e) => {
+            error!("Error getting logs: {}", e);
+            return PaymentTimelineResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    };
+
+    PaymentTimelineResponse::Ok(Json(PaymentTimeline {
+        payment,
+        transactions,
+        inscriptions: inscriptions
+            .into_iter()
+            .map(|inscription| TimelineInscription {
+                id: inscription.id,
+                domain: inscription.domain,
+                target: inscription.target,
+                content: inscription.content,
+                reveal_tx: inscription.reveal_tx,
+            })
+            .collect(),
+        logs,
+    }))
+}
+
+async fn get_payment<R: PaymentRepository>(
+    pool: &R,
+    id: &Uuid,
+    data: &PaymentOverrideData,
+) -> Result<Payment, PaymentOverrideResponse> {
+    if data.reason.trim().is_empty() {
+        return Err(PaymentOverrideResponse::BadRequest(Json(
+            "A reason is required".into(),
+        )));
+    }
+
+    match pool.get_payment(id).await {
+        Ok(Some(payment)) => Ok(payment),
+        Ok(None) => Err(PaymentOverrideResponse::NotFound(Json("Not found".into()))),
+        Err(e) => {
+            error!("Error getting payment: {}", e);
+            Err(PaymentOverrideResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            )))
+        }
+    }
+}
+
+async fn add_override_log<R: PaymentRepository>(
+    pool: &R,
+    payment: &Payment,
+    event: fn(PaymentOverride) -> LogEvent,
+    admin: &Uuid,
+    data: &PaymentOverrideData,
+) {
+    let event = event(PaymentOverride {
+        payment_id: payment.id,
+        admin_id: *admin,
+        reason: data.reason.trim().to_string(),
+    });
+    let res = pool.add_log(&payment.account_id, &event).await;
+
+    if let Err(e) = res {
+        error!("Error adding log: {}", e);
+    }
+}
+
+/// Marks an order as paid for, bringing it back first when it expired and none of its domains
+/// have been reserved in the meantime.
+pub async fn initiate<R: PaymentRepository>(
+    pool: &R,
+    admin: &Uuid,
+    id: &Uuid,
+    data: &PaymentOverrideData,
+) -> PaymentOverrideResponse {
+    let payment = match get_payment(pool, id, data).await {
+        Ok(payment) => payment,
+        Err(response) => return response,
+    };
+
+    if payment.initiated && !payment.expired {
+        return PaymentOverrideResponse::Conflict(Json("Payment is already initiated".into()));
+    }
+
+    let res = if payment.expired {
+        pool.reactivate_payment(&payment.id).await
+    } else {
+        pool.initiate_payment(&payment.id).await.map(|_| true)
+    };
+
+    match res {
+        Ok(true) => {}
+        Ok(false) => {
+            return PaymentOverrideResponse::Conflict(Json(
+                "Some domains of this order have been reserved since it expired".into(),
+            ))
+        }
+        Err(e) => {
+            error!("Error initiating payment: {}", e);
+            return PaymentOverrideResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    }
+
+    add_override_log(
+        pool,
+        &payment,
+        LogEvent::PaymentManuallyInitiated,
+        admin,
+        data,
+    )
+    .await;
+
+    PaymentOverrideResponse::Ok(PlainText("ok".to_string()))
+}
+
+pub async fn complete<R: PaymentRepository>(
+    pool: &R,
+    admin: &Uuid,
+    id: &Uuid,
+    data: &PaymentOverrideData,
+) -> PaymentOverrideResponse {
+    let payment = match get_payment(pool, id, data).await {
+        Ok(payment) => payment,
+        Err(response) => return response,
+    };
+
+    if payment.completed {
+        return PaymentOverrideResponse::Conflict(Json("Payment is already completed".into()));
+    }
+
+    if !payment.initiated || payment.expired {
+        return PaymentOverrideResponse::Conflict(Json(
+            "Only initiated payments can be completed".into(),
+        ));
+    }
+
+    if let Err(e) = pool.complete_payment(&payment.id).await {
+        error!("Error completing payment: {}", e);
+        return PaymentOverrideResponse::InternalServerError(Json("Internal server error".into()));
+    }
+
+    add_override_log(
+        pool,
+        &payment,
+        LogEvent::PaymentManuallyCompleted,
+        admin,
+        data,
+    )
+    .await;
+
+    PaymentOverrideResponse::Ok(PlainText("ok".to_string()))
+}
+
+/// Expires an order and releases its domains, funds it already received are flagged for a refund.
+pub async fn cancel<R: PaymentRepository>(
+    pool: &R,
+    admin: &Uuid,
+    id: &Uuid,
+    data: &PaymentOverrideData,
+) -> PaymentOverrideResponse {
+    let payment = match get_payment(pool, id, data).await {
+        Ok(payment) => payment,
+        Err(response) => return response,
+    };
+
+    match pool.cancel_payment(&payment.id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return PaymentOverrideResponse::Conflict(Json(
+                "Completed or expired payments can not be cancelled".into(),
+            ))
+        }
+        Err(e) => {
+            error!("Error cancelling payment: {}", e);
+            return PaymentOverrideResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    }
+
+    add_override_log(pool, &payment, LogEvent::PaymentCancelled, admin, data).await;
+
+    PaymentOverrideResponse::Ok(PlainText("ok".to_string()))
+}
+
+/// Hands an initiated order back to the inscriber, as long as none of its inscriptions made it on
+/// chain.
+pub async fn requeue_inscription<R: PaymentRepository>(
+    pool: &R,
+    admin: &Uuid,
+    id: &Uuid,
+    data: &PaymentOverrideData,
+) -> PaymentOverrideResponse {
+    let payment = match get_payment(pool, id, data).await {
+        Ok(payment) => payment,
+        Err(response) => return response,
+    };
+
+    match pool.requeue_inscription(&payment.id).await {
+        Ok(InscriptionRequeue::Requeued) => {}
+        Ok(InscriptionRequeue::NotInitiated) => {
+            return PaymentOverrideResponse::Conflict(Json(
+                "Only initiated payments can be inscribed".into(),
+            ))
+        }
+        Ok(InscriptionRequeue::AlreadyRevealed) => {
+            return PaymentOverrideResponse::Conflict(Json(
+                "The payment already has inscriptions on chain".into(),
+            ))
+        }
+        Err(e) => {
+            error!("Error requeueing inscription: {}", e);
+            return PaymentOverrideResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    }
+
+    add_override_log(pool, &payment, LogEvent::InscriptionRequeued, admin, data).await;
+
+    PaymentOverrideResponse::Ok(PlainText("ok".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::log::LogTypes;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::test_utils::TestServices;
+
+    fn reason(reason: &str) -> PaymentOverrideData {
+        PaymentOverrideData {
+            reason: reason.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn requires_a_reason_for_every_override() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let admin = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+        let data = reason("  ");
+
+        assert!(matches!(
+            initiate(&pool, &admin, &order.id(), &data).await,
+            PaymentOverrideResponse::BadRequest(_)
+        ));
+        assert!(matches!(
+            complete(&pool, &admin, &order.id(), &data).await,
+            PaymentOverrideResponse::BadRequest(_)
+        ));
+        assert!(matches!(
+            cancel(&pool, &admin, &order.id(), &data).await,
+            PaymentOverrideResponse::BadRequest(_)
+        ));
+        assert!(matches!(
+            requeue_inscription(&pool, &admin, &order.id(), &data).await,
+            PaymentOverrideResponse::BadRequest(_)
+        ));
+
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert!(!payment.initiated);
+        assert!(!payment.expired);
+        assert!(pool
+            .get_logs(&user, &LogsFilter::default())
+            .await
+            .unwrap()
+            .unwrap()
+            .iter()
+            .all(|log| log.log_type == LogTypes::PaymentRequested));
+    }
+
+    #[tokio::test]
+    async fn overrides_unknown_payments_are_not_found() {
+        let pool = InMemoryRepository::default();
+        let admin = Uuid::new_v4();
+
+        assert!(matches!(
+            initiate(&pool, &admin, &Uuid::new_v4(), &reason("paid by wire")).await,
+            PaymentOverrideResponse::NotFound(_)
+        ));
+        assert!(matches!(
+            timeline(&pool, &Uuid::new_v4()).await,
+            PaymentTimelineResponse::NotFound(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn initiates_and_completes_an_order() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let admin = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+
+        assert!(matches!(
+            complete(&pool, &admin, &order.id(), &reason("paid by wire")).await,
+            PaymentOverrideResponse::Conflict(_)
+        ));
+        assert!(matches!(
+            initiate(&pool, &admin, &order.id(), &reason("paid by wire")).await,
+            PaymentOverrideResponse::Ok(_)
+        ));
+        assert!(matches!(
+            initiate(&pool, &admin, &order.id(), &reason("paid by wire")).await,
+            PaymentOverrideResponse::Conflict(_)
+        ));
+        assert!(matches!(
+            complete(&pool, &admin, &order.id(), &reason("inscribed by hand")).await,
+            PaymentOverrideResponse::Ok(_)
+        ));
+        assert!(matches!(
+            cancel(&pool, &admin, &order.id(), &reason("too late")).await,
+            PaymentOverrideResponse::Conflict(_)
+        ));
+
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert!(payment.initiated);
+        assert!(payment.completed);
+        assert!(!payment.expired);
+    }
+
+    #[tokio::test]
+    async fn initiates_an_expired_order_unless_its_domains_were_reserved() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let admin = Uuid::new_v4();
+        let expired = services.order(&pool, &user, &["alice.o"]).await;
+        let reserved = services.order(&pool, &user, &["bob.o"]).await;
+        pool.cancel_payment(&expired.id()).await.unwrap();
+        pool.cancel_payment(&reserved.id()).await.unwrap();
+        services.order(&pool, &Uuid::new_v4(), &["bob.o"]).await;
+
+        assert!(matches!(
+            initiate(&pool, &admin, &expired.id(), &reason("paid by wire")).await,
+            PaymentOverrideResponse::Ok(_)
+        ));
+        assert!(matches!(
+            initiate(&pool, &admin, &reserved.id(), &reason("paid by wire")).await,
+            PaymentOverrideResponse::Conflict(_)
+        ));
+
+        let payment = pool.get_payment(&expired.id()).await.unwrap().unwrap();
+        assert!(payment.initiated);
+        assert!(!payment.expired);
+        let payment = pool.get_payment(&reserved.id()).await.unwrap().unwrap();
+        assert!(payment.expired);
+    }
+
+    #[tokio::test]
+    async fn cancels_an_order_and_releases_its_domains() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let admin = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+
+        assert!(matches!(
+            cancel(&pool, &admin, &order.id(), &reason("customer asked")).await,
+            PaymentOverrideResponse::Ok(_)
+        ));
+        assert!(matches!(
+            cancel(&pool, &admin, &order.id(), &reason("customer asked")).await,
+            PaymentOverrideResponse::Conflict(_)
+        ));
+
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert!(payment.expired);
+
+        services.order(&pool, &Uuid::new_v4(), &["alice.o"]).await;
+    }
+
+    #[tokio::test]
+    async fn requeues_an_order_whose_inscriptions_were_not_revealed() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let admin = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+
+        assert!(matches!(
+            requeue_inscription(&pool, &admin, &order.id(), &reason("stuck")).await,
+            PaymentOverrideResponse::Conflict(_)
+        ));
+
+        pool.initiate_payment(&order.id()).await.unwrap();
+        pool.complete_payment(&order.id()).await.unwrap();
+
+        assert!(matches!(
+            requeue_inscription(&pool, &admin, &order.id(), &reason("stuck")).await,
+            PaymentOverrideResponse::Ok(_)
+        ));
+
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert!(payment.initiated);
+        assert!(!payment.completed);
+    }
+
+    #[tokio::test]
+    async fn refuses_to_requeue_revealed_inscriptions() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let admin = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+        pool.initiate_payment(&order.id()).await.unwrap();
+        pool.complete_payment(&order.id()).await.unwrap();
+        pool.set_reveal_tx(&order.id(), "reveal");
+
+        assert!(matches!(
+            requeue_inscription(&pool, &admin, &order.id(), &reason("stuck")).await,
+            PaymentOverrideResponse::Conflict(_)
+        ));
+
+        let payment = pool.get_payment(&order.id()).await.unwrap().unwrap();
+        assert!(payment.completed);
+
+        let inscriptions = pool.get_payment_inscriptions(&order.id()).await.unwrap();
+        assert_eq!(inscriptions.len(), 1);
+        assert_eq!(inscriptions[0].reveal_tx.as_deref(), Some("reveal"));
+    }
+
+    #[tokio::test]
+    async fn searches_payments() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+        services.order(&pool, &Uuid::new_v4(), &["bob.o"]).await;
+
+        assert!(matches!(
+            search(&pool, PaymentSearch::default(), None).await,
+            AdminPaymentsResponse::BadRequest(_)
+        ));
+        assert!(matches!(
+            search(
+                &pool,
+                PaymentSearch {
+                    account_id: Some(user),
+                    ..Default::default()
+                },
+                Some(0)
+            )
+            .await,
+            AdminPaymentsResponse::BadRequest(_)
+        ));
+
+        let searches = [
+            PaymentSearch {
+                account_id: Some(user),
+                ..Default::default()
+            },
+            PaymentSearch {
+                address: Some(order.address().to_string()),
+                ..Default::default()
+            },
+            PaymentSearch {
+                domain: Some("ALICE".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        for payment_search in searches {
+            match search(&pool, payment_search, None).await {
+                AdminPaymentsResponse::Ok(Json(response)) => {
+                    assert_eq!(response.payments.len(), 1);
+                    assert_eq!(response.payments[0].payment.id, order.id());
+                    assert_eq!(response.payments[0].domains, vec!["alice.o"]);
+                }
+                _ => panic!("search failed"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn shows_the_overrides_in_the_timeline() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let admin = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+
+        initiate(&pool, &admin, &order.id(), &reason("paid by wire")).await;
+        complete(&pool, &admin, &order.id(), &reason("inscribed by hand")).await;
+
+        match timeline(&pool, &order.id()).await {
+            PaymentTimelineResponse::Ok(Json(timeline)) => {
+                assert!(timeline.payment.completed);
+                assert_eq!(timeline.inscriptions.len(), 1);
+                assert_eq!(timeline.inscriptions[0].domain.as_deref(), Some("alice.o"));
+                assert_eq!(timeline.logs.len(), 3);
+            }
+            _ => panic!("timeline failed"),
+        }
+
+        let filter = LogsFilter {
+            payment_id: Some(order.id()),
+            ascending: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            pool.get_logs(&user, &filter)
+                .await
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .map(|log| log.log_type)
+                .collect::<Vec<_>>(),
+            vec![
+                LogTypes::PaymentRequested,
+                LogTypes::PaymentManuallyInitiated,
+                LogTypes::PaymentManuallyCompleted,
+            ]
+        );
+    }
+}
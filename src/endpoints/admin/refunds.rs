@@ -0,0 +1,216 @@
+use std::str::FromStr;
+
+use bitcoincore_rpc::bitcoin::{Address, Amount};
+use poem_openapi::payload::PlainText;
+use poem_openapi::Object;
+use poem_openapi::{payload::Json, ApiResponse};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::bitcoin::transactions::send_with_fee_rate;
+use crate::bitcoin::wallet::WalletBackend;
+use crate::db::log::LogEvent;
+use crate::db::repositories::models::refund::{Refund, RefundStatus};
+use crate::db::PaymentRepository;
+use crate::responses::error::ErrorResponse;
+use crate::CHAIN;
+
+const MAX_REFUND_FEE_RATE: f64 = 1000.0;
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct ApproveRefundData {
+    /// Fee rate in sat/vB, subtracted from the refunded amount.
+    fee_rate: f64,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct RejectRefundData {
+    reason: String,
+}
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct AdminRefundsResponseObject {
+    refunds: Vec<Refund>,
+}
+
+#[derive(ApiResponse)]
+pub enum AdminRefundsResponse {
+    #[oai(status = 200)]
+    Ok(Json<AdminRefundsResponseObject>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+#[derive(ApiResponse)]
+pub enum RefundActionResponse {
+    #[oai(status = 200)]
+    Ok(PlainText<String>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<ErrorResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+pub async fn refunds<R: PaymentRepository>(
+    pool: &R,
+    status: Option<RefundStatus>,
+) -> AdminRefundsResponse {
+    match pool.get_refunds(None, status).await {
+        Ok(refunds) => AdminRefundsResponse::Ok(Json(AdminRefundsResponseObject { refunds })),
+        Err(e) => {
+            error!("Error getting refunds: {}", e);
+            AdminRefundsResponse::InternalServerError(Json("Internal server error".into()))
+        }
+    }
+}
+
+pub async fn approve<R: PaymentRepository, W: WalletBackend>(
+    pool: &R,
+    wallet: &W,
+    admin: &Uuid,
+    id: &Uuid,
+    data: &ApproveRefundData,
+) -> RefundActionResponse {
+    if !(data.fee_rate >= 1.0 && data.fee_rate <= MAX_REFUND_FEE_RATE) {
+        return RefundActionResponse::BadRequest(Json(
+            format!(
+                "Fee rate must be between 1 and {} sat/vB",
+                MAX_REFUND_FEE_RATE
+            )
+            .as_str()
+            .into(),
+        ));
+    }
+
+    let refund = match pool.get_refund(id).await {
+        Ok(Some(refund)) => refund,
+        Ok(None) => return RefundActionResponse::NotFound(Json("Not found".into())),
+        Err(e) => {
+            error!("Error getting refund: {}", e);
+            return RefundActionResponse::InternalServerError(Json("Internal server error".into()));
+        }
+    };
+
+    let address = match Address::from_str(&refund.address)
+        .map(|address| address.require_network(CHAIN.network()))
+    {
+        Ok(Ok(address)) => address,
+        _ => {
+            return RefundActionResponse::BadRequest(Json(
+                "Refund address is not valid on this network".into(),
+            ))
+        }
+    };
+
+    if Amount::from_btc(refund.amount).is_err() {
+        return RefundActionResponse::BadRequest(Json("Invalid refund amount".into()));
+    }
+
+    // Claim the refund first so concurrent approvals can never broadcast it twice.
+    match pool.claim_refund(&refund.id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return RefundActionResponse::Conflict(Json("Refund is not awaiting approval".into()))
+        }
+        Err(e) => {
+            error!("Error claiming refund: {}", e);
+            return RefundActionResponse::InternalServerError(Json("Internal server error".into()));
+        }
+    }
+
+    let txid = match send_with_fee_rate(wallet, &address, refund.amount, data.fee_rate) {
+        Ok(txid) => txid,
+        Err(e) => {
+            error!("Error broadcasting refund {}: {}", refund.id, e);
+
+            if let Err(e) = pool.release_refund(&refund.id).await {
+                error!("Error releasing refund {}: {}", refund.id, e);
+            }
+
+            return RefundActionResponse::InternalServerError(Json(
+                "Failed to broadcast refund transaction".into(),
+            ));
+        }
+    };
+
+    if let Err(e) = pool.complete_refund(&refund.id, &txid, data.fee_rate).await {
+        // The transaction is already out, leave the refund in processing for manual follow-up.
+        error!(
+            "Refund {} was broadcast as {} but could not be recorded: {}",
+            refund.id, txid, e
+        );
+        return RefundActionResponse::InternalServerError(Json(
+            "Refund broadcast but not recorded, please contact a system administrator".into(),
+        ));
+    }
+
+    let event = LogEvent::RefundBroadcast {
+        refund_id: refund.id,
+        payment_id: refund.payment_id,
+        transaction_id: txid.clone(),
+        amount: refund.amount,
+        fee_rate: data.fee_rate,
+        admin_id: *admin,
+    };
+    let res = pool.add_log(&refund.account_id, &event).await;
+
+    if let Err(e) = res {
+        error!("Error adding log: {}", e);
+    }
+
+    RefundActionResponse::Ok(PlainText(txid))
+}
+
+pub async fn reject<R: PaymentRepository>(
+    pool: &R,
+    admin: &Uuid,
+    id: &Uuid,
+    data: &RejectRefundData,
+) -> RefundActionResponse {
+    if data.reason.trim().is_empty() {
+        return RefundActionResponse::BadRequest(Json("A reason is required".into()));
+    }
+
+    let refund = match pool.get_refund(id).await {
+        Ok(Some(refund)) => refund,
+        Ok(None) => return RefundActionResponse::NotFound(Json("Not found".into())),
+        Err(e) => {
+            error!("Error getting refund: {}", e);
+            return RefundActionResponse::InternalServerError(Json("Internal server error".into()));
+        }
+    };
+
+    match pool.reject_refund(&refund.id, data.reason.trim()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return RefundActionResponse::Conflict(Json("Refund is not awaiting approval".into()))
+        }
+        Err(e) => {
+            error!("Error rejecting refund: {}", e);
+            return RefundActionResponse::InternalServerError(Json("Internal server error".into()));
+        }
+    }
+
+    let event = LogEvent::RefundRejected {
+        refund_id: refund.id,
+        payment_id: refund.payment_id,
+        admin_id: *admin,
+        reason: data.reason.trim().to_string(),
+    };
+    let res = pool.add_log(&refund.account_id, &event).await;
+
+    if let Err(e) = res {
+        error!("Error adding log: {}", e);
+    }
+
+    RefundActionResponse::Ok(PlainText("ok".to_string()))
+}
@@ -3,7 +3,7 @@ use poem_openapi::{payload::Json, ApiResponse};
 use tracing::error;
 use uuid::Uuid;
 
-use crate::db::{PaymentRepository, Repository};
+use crate::db::PaymentRepository;
 use crate::responses::error::ErrorResponse;
 
 #[derive(ApiResponse)]
@@ -18,8 +18,12 @@ pub enum DeletePaymentResponse {
     InternalServerError(Json<ErrorResponse>),
 }
 
-pub async fn delete(pool: &Repository, user: &Uuid, payment_id: &Uuid) -> DeletePaymentResponse {
-    let payment = pool.delete_payment(&user, &payment_id).await;
+pub async fn delete<R: PaymentRepository>(
+    pool: &R,
+    user: &Uuid,
+    payment_id: &Uuid,
+) -> DeletePaymentResponse {
+    let payment = pool.delete_payment(user, payment_id).await;
 
     match payment {
         Ok(Ok(())) => DeletePaymentResponse::Ok(PlainText("ok".to_string())),
@@ -30,3 +34,48 @@ pub async fn delete(pool: &Repository, user: &Uuid, payment_id: &Uuid) -> Delete
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::test_utils::TestServices;
+
+    #[tokio::test]
+    async fn deletes_own_uninitiated_payments() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+
+        assert!(matches!(
+            delete(&pool, &Uuid::new_v4(), &order.id()).await,
+            DeletePaymentResponse::NotFound(_)
+        ));
+        assert!(matches!(
+            delete(&pool, &user, &order.id()).await,
+            DeletePaymentResponse::Ok(_)
+        ));
+        assert_eq!(pool.get_payment(&order.id()).await.unwrap(), None);
+        assert!(matches!(
+            delete(&pool, &user, &order.id()).await,
+            DeletePaymentResponse::NotFound(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn keeps_initiated_payments() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+
+        pool.initiate_payment(&order.id()).await.unwrap();
+
+        assert!(matches!(
+            delete(&pool, &user, &order.id()).await,
+            DeletePaymentResponse::NotFound(_)
+        ));
+        assert!(pool.get_payment(&order.id()).await.unwrap().is_some());
+    }
+}
@@ -3,7 +3,7 @@ use poem_openapi::{payload::Json, ApiResponse};
 use tracing::error;
 use uuid::Uuid;
 
-use crate::db::{PaymentRepository, Repository};
+use crate::db::PaymentRepository;
 use crate::responses::error::ErrorResponse;
 
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
@@ -30,7 +30,7 @@ pub enum PaidDomains {
     InternalServerError(Json<ErrorResponse>),
 }
 
-pub async fn domains(pool: &Repository, user: &Uuid) -> PaidDomains {
+pub async fn domains<R: PaymentRepository>(pool: &R, user: &Uuid) -> PaidDomains {
     let domains = pool.get_owned_domains(user).await;
 
     match domains {
@@ -52,3 +52,52 @@ pub async fn domains(pool: &Repository, user: &Uuid) -> PaidDomains {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::test_utils::TestServices;
+
+    async fn paid_domains(pool: &InMemoryRepository, user: &Uuid) -> Vec<PaidDomain> {
+        match domains(pool, user).await {
+            PaidDomains::Ok(Json(response)) => response.domains,
+            _ => panic!("domains could not be listed"),
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_domains_of_initiated_payments() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let paid = services.order(&pool, &user, &["alice.o"]).await;
+        services.order(&pool, &user, &["bob.o"]).await;
+
+        assert_eq!(paid_domains(&pool, &user).await, vec![]);
+
+        pool.initiate_payment(&paid.id()).await.unwrap();
+
+        assert_eq!(
+            paid_domains(&pool, &user).await,
+            vec![PaidDomain {
+                domain: "alice.o".to_string(),
+                payment_completed: false,
+                reveal_tx: None,
+            }]
+        );
+        assert_eq!(paid_domains(&pool, &Uuid::new_v4()).await, vec![]);
+
+        pool.complete_payment(&paid.id()).await.unwrap();
+        pool.set_reveal_tx(&paid.id(), "reveal");
+
+        assert_eq!(
+            paid_domains(&pool, &user).await,
+            vec![PaidDomain {
+                domain: "alice.o".to_string(),
+                payment_completed: true,
+                reveal_tx: Some("reveal".to_string()),
+            }]
+        );
+    }
+}
@@ -3,8 +3,9 @@ use poem_openapi::{payload::Json, ApiResponse};
 use tracing::error;
 use uuid::Uuid;
 
-use crate::db::{PaymentRepository, Repository};
+use crate::db::PaymentRepository;
 use crate::responses::error::ErrorResponse;
+use crate::utils::domain_name;
 
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
 pub struct GetPrivateKeyResponseObject {
@@ -16,6 +17,9 @@ pub enum GetPrivateKeyResponse {
     #[oai(status = 200)]
     Ok(Json<GetPrivateKeyResponseObject>),
 
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
     #[oai(status = 404)]
     NotFound(Json<ErrorResponse>),
 
@@ -23,12 +27,17 @@ pub enum GetPrivateKeyResponse {
     InternalServerError(Json<ErrorResponse>),
 }
 
-pub async fn get_private_key(
-    pool: &Repository,
+pub async fn get_private_key<R: PaymentRepository>(
+    pool: &R,
     user: &Uuid,
     domain: &str,
 ) -> GetPrivateKeyResponse {
-    match pool.get_private_key(&user, &domain).await {
+    let domain = match domain_name::normalize(domain) {
+        Ok(domain) => domain,
+        Err(e) => return GetPrivateKeyResponse::BadRequest(Json(e.to_string().as_str().into())),
+    };
+
+    match pool.get_private_key(user, &domain.ascii).await {
         Ok(Some(private_key)) => {
             GetPrivateKeyResponse::Ok(Json(GetPrivateKeyResponseObject { private_key }))
         }
@@ -41,3 +50,41 @@ pub async fn get_private_key(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::test_utils::TestServices;
+
+    #[tokio::test]
+    async fn returns_the_key_to_the_owner() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        services.order(&pool, &user, &["alice.o"]).await;
+
+        let expected = pool.get_private_key(&user, "alice.o").await.unwrap();
+        assert!(expected.is_some());
+
+        match get_private_key(&pool, &user, "ALICE.o").await {
+            GetPrivateKeyResponse::Ok(Json(response)) => {
+                assert_eq!(Some(response.private_key), expected)
+            }
+            _ => panic!("private key was not returned"),
+        }
+
+        assert!(matches!(
+            get_private_key(&pool, &Uuid::new_v4(), "alice.o").await,
+            GetPrivateKeyResponse::NotFound(_)
+        ));
+        assert!(matches!(
+            get_private_key(&pool, &user, "bob.o").await,
+            GetPrivateKeyResponse::NotFound(_)
+        ));
+        assert!(matches!(
+            get_private_key(&pool, &user, "a.b.o").await,
+            GetPrivateKeyResponse::BadRequest(_)
+        ));
+    }
+}
@@ -0,0 +1,177 @@
+use chrono::NaiveDateTime;
+use poem_openapi::Object;
+use poem_openapi::{payload::Json, ApiResponse};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::db::log::{LogEvent, LogTypes};
+use crate::db::traits::repository::{LogEntry, LogsFilter};
+use crate::db::PaymentRepository;
+use crate::responses::error::ErrorResponse;
+
+const DEFAULT_LOGS_LIMIT: u32 = 50;
+const MAX_LOGS_LIMIT: u32 = 200;
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct AccountLog {
+    id: Uuid,
+    log_type: LogTypes,
+    /// Set for entries about a payment.
+    payment_id: Option<Uuid>,
+    /// The event as versioned JSON, entries written before events were typed hold a sentence.
+    data: Option<String>,
+    created_at: NaiveDateTime,
+}
+
+impl From<LogEntry> for AccountLog {
+    fn from(log: LogEntry) -> Self {
+        Self {
+            id: log.id,
+            log_type: log.log_type,
+            payment_id: log.payment_id,
+            data: log.data,
+            created_at: log.created_at,
+        }
+    }
+}
+
+impl AccountLog {
+    /// The entry as the account holder gets to see it, without the admin who acted nor their
+    /// reason.
+    fn redacted(self) -> Self {
+        Self {
+            data: self.data.as_deref().map(LogEvent::redact_json),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct LogsResponseObject {
+    logs: Vec<AccountLog>,
+    next_cursor: Option<Uuid>,
+}
+
+#[derive(ApiResponse)]
+pub enum LogsResponse {
+    #[oai(status = 200)]
+    Ok(Json<LogsResponseObject>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+/// The decrypted log entries of an account, newest first. Staff details of admin actions are only
+/// kept for `admin` readers.
+pub async fn logs<R: PaymentRepository>(
+    pool: &R,
+    account_id: &Uuid,
+    mut filter: LogsFilter,
+    limit: Option<u32>,
+    admin: bool,
+) -> LogsResponse {
+    let limit = limit.unwrap_or(DEFAULT_LOGS_LIMIT);
+
+    if limit == 0 || limit > MAX_LOGS_LIMIT {
+        return LogsResponse::BadRequest(Json(
+            format!("Limit must be between 1 and {}", MAX_LOGS_LIMIT)
+                .as_str()
+                .into(),
+        ));
+    }
+
+    // Fetch one extra row to know whether there is a next page.
+    filter.limit = Some(limit as i64 + 1);
+
+    match pool.get_logs(account_id, &filter).await {
+        Ok(Some(mut logs)) => {
+            let next_cursor = if logs.len() > limit as usize {
+                logs.truncate(limit as usize);
+                logs.last().map(|log| log.id)
+            } else {
+                None
+            };
+
+            LogsResponse::Ok(Json(LogsResponseObject {
+                logs: logs
+                    .into_iter()
+                    .map(AccountLog::from)
+                    .map(|log| if admin { log } else { log.redacted() })
+                    .collect(),
+                next_cursor,
+            }))
+        }
+        Ok(None) => LogsResponse::BadRequest(Json("Unknown cursor".into())),
+        Err(e) => {
+            error!("Error getting logs: {}", e);
+            LogsResponse::InternalServerError(Json("Internal server error".into()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::log::PaymentOverride;
+    use crate::db::repositories::InMemoryRepository;
+
+    #[tokio::test]
+    async fn hides_staff_details_from_the_account_holder() {
+        let pool = InMemoryRepository::default();
+        let user = Uuid::new_v4();
+        let event = LogEvent::PaymentCancelled(PaymentOverride {
+            payment_id: Uuid::new_v4(),
+            admin_id: Uuid::new_v4(),
+            reason: "suspected fraud".to_string(),
+        });
+        pool.add_log(&user, &event).await.unwrap();
+
+        let data = |response| match response {
+            LogsResponse::Ok(Json(response)) => response.logs[0].data.clone().unwrap(),
+            _ => panic!("logs failed"),
+        };
+
+        let customer = data(logs(&pool, &user, LogsFilter::default(), None, false).await);
+        assert!(!customer.contains("admin_id"));
+        assert!(!customer.contains("suspected fraud"));
+        assert!(customer.contains("payment_cancelled"));
+
+        let admin = data(logs(&pool, &user, LogsFilter::default(), None, true).await);
+        assert_eq!(LogEvent::from_json(&admin), Some(event));
+    }
+
+    #[tokio::test]
+    async fn rejects_cursors_of_other_accounts() {
+        let pool = InMemoryRepository::default();
+        let user = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        let event = LogEvent::PaymentExpired {
+            payment_id: Uuid::new_v4(),
+        };
+        pool.add_log(&user, &event).await.unwrap();
+        pool.add_log(&other, &event).await.unwrap();
+
+        let all = LogsFilter::default();
+        let own = pool.get_logs(&user, &all).await.unwrap().unwrap()[0].id;
+        let foreign = pool.get_logs(&other, &all).await.unwrap().unwrap()[0].id;
+
+        let page = |cursor| {
+            let filter = LogsFilter {
+                cursor: Some(cursor),
+                ..Default::default()
+            };
+            logs(&pool, &user, filter, None, false)
+        };
+
+        match page(own).await {
+            LogsResponse::Ok(Json(response)) => assert!(response.logs.is_empty()),
+            _ => panic!("logs failed"),
+        }
+        for cursor in [Uuid::new_v4(), foreign] {
+            assert!(matches!(page(cursor).await, LogsResponse::BadRequest(_)));
+        }
+    }
+}
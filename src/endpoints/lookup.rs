@@ -0,0 +1,238 @@
+use poem_openapi::{payload::Json, ApiResponse};
+use poem_openapi::{Enum, Object};
+use tracing::error;
+
+use crate::db::PaymentRepository;
+use crate::endpoints::new::check_domain_rules;
+use crate::inscription::verify_domain_inscription;
+use crate::responses::error::ErrorResponse;
+use crate::utils::domain_name::{self, DomainNameError};
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct DomainAvailabilityResponseObject {
+    domain: String,
+    /// The domain with internationalized labels decoded.
+    display_name: String,
+    available: bool,
+}
+
+#[derive(ApiResponse)]
+pub enum DomainAvailabilityResponse {
+    #[oai(status = 200)]
+    Ok(Json<DomainAvailabilityResponseObject>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 429)]
+    TooManyRequests(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+#[oai(rename_all = "snake_case")]
+pub enum DomainLookupStatus {
+    /// Paid for, the inscription has not been revealed yet.
+    Pending,
+    Registered,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct DomainLookupResponseObject {
+    domain: String,
+    /// The domain with internationalized labels decoded.
+    display_name: String,
+    status: DomainLookupStatus,
+    reveal_tx: Option<String>,
+    target: String,
+    /// Algorithm of the validity key, absent for names inscribed before inscriptions were signed.
+    validity_algorithm: Option<String>,
+    validity_public_key: Option<String>,
+}
+
+#[derive(ApiResponse)]
+pub enum DomainLookupResponse {
+    #[oai(status = 200)]
+    Ok(Json<DomainLookupResponseObject>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<ErrorResponse>),
+
+    #[oai(status = 429)]
+    TooManyRequests(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+pub async fn availability<R: PaymentRepository>(
+    pool: &R,
+    name: &str,
+) -> DomainAvailabilityResponse {
+    let domain = match domain_name::normalize(name) {
+        Ok(domain) => domain,
+        Err(DomainNameError::Reserved(domain)) => {
+            return DomainAvailabilityResponse::Ok(Json(DomainAvailabilityResponseObject {
+                domain: domain.clone(),
+                display_name: domain,
+                available: false,
+            }))
+        }
+        Err(e) => {
+            return DomainAvailabilityResponse::BadRequest(Json(e.to_string().as_str().into()))
+        }
+    };
+
+    match check_domain_rules(pool, None, std::slice::from_ref(&domain.ascii)).await {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            return DomainAvailabilityResponse::Ok(Json(DomainAvailabilityResponseObject {
+                domain: domain.ascii,
+                display_name: domain.unicode,
+                available: false,
+            }))
+        }
+        Err(e) => {
+            error!("Error checking domain rules: {}", e);
+            return DomainAvailabilityResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    }
+
+    match pool.get_domain_registration(&domain.ascii).await {
+        Ok(registration) => {
+            DomainAvailabilityResponse::Ok(Json(DomainAvailabilityResponseObject {
+                domain: domain.ascii,
+                display_name: domain.unicode,
+                available: registration.is_none(),
+            }))
+        }
+        Err(e) => {
+            error!("Error getting domain registration: {}", e);
+            DomainAvailabilityResponse::InternalServerError(Json("Internal server error".into()))
+        }
+    }
+}
+
+pub async fn lookup<R: PaymentRepository>(pool: &R, name: &str) -> DomainLookupResponse {
+    let domain = match domain_name::normalize(name) {
+        Ok(domain) => domain,
+        Err(e) => return DomainLookupResponse::BadRequest(Json(e.to_string().as_str().into())),
+    };
+
+    let registration = match pool.get_domain_registration(&domain.ascii).await {
+        // Unpaid orders are not public, they only block the name from being ordered.
+        Ok(Some(registration)) if registration.initiated => registration,
+        Ok(_) => return DomainLookupResponse::NotFound(Json("Domain is not registered".into())),
+        Err(e) => {
+            error!("Error getting domain registration: {}", e);
+            return DomainLookupResponse::InternalServerError(Json("Internal server error".into()));
+        }
+    };
+
+    // Only publish the key of an inscription whose signature checks out.
+    let validity = verify_domain_inscription(&registration.inscription).ok();
+
+    DomainLookupResponse::Ok(Json(DomainLookupResponseObject {
+        domain: registration.domain,
+        display_name: domain.unicode,
+        status: if registration.completed && registration.reveal_tx.is_some() {
+            DomainLookupStatus::Registered
+        } else {
+            DomainLookupStatus::Pending
+        },
+        reveal_tx: registration.reveal_tx,
+        target: registration.target,
+        validity_algorithm: validity
+            .as_ref()
+            .map(|inscription| inscription.algorithm.to_string()),
+        validity_public_key: validity.map(|inscription| hex::encode(inscription.public_key)),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::records::create_update_order;
+    use crate::endpoints::test_utils::TestServices;
+    use crate::inscription::record::{DomainUpdateInscription, RecordUpdate, SignatureAlgorithm};
+
+    async fn lookup_target(pool: &InMemoryRepository, name: &str) -> String {
+        match lookup(pool, name).await {
+            DomainLookupResponse::Ok(Json(response)) => response.target,
+            _ => panic!("{} could not be looked up", name),
+        }
+    }
+
+    async fn move_to(
+        pool: &InMemoryRepository,
+        services: &TestServices,
+        user: &Uuid,
+        target: &str,
+    ) -> Uuid {
+        let update = DomainUpdateInscription {
+            name: "alice.o".to_string(),
+            timestamp: 1,
+            updates: vec![RecordUpdate::Target {
+                address: target.to_string(),
+            }],
+            algorithm: SignatureAlgorithm::Dilithium5,
+            signature: vec![0; 4],
+        };
+
+        create_update_order(
+            pool,
+            &services.addresses,
+            &services.fees,
+            user,
+            target,
+            &update,
+        )
+        .await
+        .unwrap()
+        .id
+    }
+
+    #[tokio::test]
+    async fn resolves_the_target_of_the_latest_inscribed_update() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+        pool.initiate_payment(&order.id()).await.unwrap();
+        pool.complete_payment(&order.id()).await.unwrap();
+        pool.set_reveal_tx(&order.id(), "reveal");
+
+        let registered = lookup_target(&pool, "alice.o").await;
+
+        // Paid but not revealed yet.
+        let first = services.target();
+        let update = move_to(&pool, &services, &user, &first).await;
+        pool.complete_payment(&update).await.unwrap();
+
+        assert_eq!(lookup_target(&pool, "alice.o").await, registered);
+
+        pool.set_reveal_tx(&update, "first");
+
+        assert_eq!(lookup_target(&pool, "alice.o").await, first);
+
+        let second = services.target();
+        let update = move_to(&pool, &services, &user, &second).await;
+        pool.complete_payment(&update).await.unwrap();
+        pool.set_reveal_tx(&update, "second");
+
+        // Unpaid updates are never inscribed.
+        move_to(&pool, &services, &user, &services.target()).await;
+
+        assert_eq!(lookup_target(&pool, "alice.o").await, second);
+    }
+}
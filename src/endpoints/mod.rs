@@ -1,6 +1,16 @@
+pub mod admin;
 pub mod delete;
 pub mod domains;
 pub mod get_private_key;
+pub mod logs;
+pub mod lookup;
 pub mod new;
+pub mod payment_qr;
+pub mod payments;
 pub mod pricing;
+pub mod records;
+pub mod refunds;
 pub mod status;
+#[cfg(test)]
+pub(crate) mod test_utils;
+pub mod transfers;
@@ -1,21 +1,35 @@
 use std::str::FromStr;
 
 use bitcoincore_rpc::bitcoin::Address;
-use bitcoincore_rpc::{json::AddressType, Client, RpcApi};
-use poem_openapi::Object;
+use chrono::NaiveDateTime;
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use poem_openapi::types::{ParseFromJSON, ToJSON};
 use poem_openapi::{payload::Json, ApiResponse};
-use pqcrypto_traits::sign::{PublicKey, SecretKey};
+use poem_openapi::{Enum, Object};
 use tracing::error;
 use uuid::Uuid;
 
-use crate::db::log::LogTypes;
-use crate::db::traits::repository::LoyaltyDiscount;
-use crate::db::{PaymentRepository, Repository};
+use crate::bitcoin::address_provider::AddressProvider;
+use crate::bitcoin::fees::FeeEstimator;
+use crate::db::log::LogEvent;
+use crate::db::repositories::models::domain_rule::DomainList;
+use crate::db::repositories::models::payment::Payment;
+use crate::db::traits::repository::{IdempotentResponse, LoyaltyDiscount};
+use crate::db::traits::UnitOfWork;
+use crate::db::PaymentRepository;
+use crate::inscription::generate_domain_inscription;
+use crate::lightning::{btc_to_msat, Invoice, Lightning, LightningBackend};
 use crate::responses::error::ErrorResponse;
+use crate::utils::bip21::order_payment_uri;
+use crate::utils::domain_name;
 use crate::utils::get_wallets_collections::get_wallets_collections;
-use crate::{CHAIN, DOMAIN_PRICE_BTC, MINIMUM_DOMAIN_PRICE_BTC};
+use crate::{
+    CHAIN, DOMAIN_PRICE_BTC, IDEMPOTENCY_KEY_RETENTION_HOURS, MAX_DOMAINS_PER_ORDER,
+    MINIMUM_DOMAIN_PRICE_BTC, ORDER_EXPIRATION_MINUTES,
+};
 
-const DOMAIN_REGEX: &str = r"^[a-z\d](?:[a-z\d-]{0,251}[a-z\d])?\.?o?$";
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
 
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
 pub struct CreatePaymentDataDomain {
@@ -23,9 +37,18 @@ pub struct CreatePaymentDataDomain {
     target: String,
 }
 
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+#[oai(rename_all = "snake_case")]
+pub enum PaymentMethod {
+    Onchain,
+    /// On-chain payment stays possible, a lightning invoice is issued on top of it.
+    Lightning,
+}
+
 #[derive(Debug, Object, Clone, Eq, PartialEq)]
 pub struct CreatePaymentData {
     domains: Vec<CreatePaymentDataDomain>,
+    payment_method: Option<PaymentMethod>,
 }
 
 #[derive(Debug, Object, Clone, PartialEq)]
@@ -33,6 +56,10 @@ pub struct CreatePaymentResponseObject {
     id: Uuid,
     address: String,
     amount: f64,
+    expires_at: NaiveDateTime,
+    lightning_invoice: Option<String>,
+    /// BIP21 `bitcoin:` URI including the lightning invoice when there is one.
+    payment_uri: String,
 }
 
 #[derive(ApiResponse)]
@@ -46,31 +73,47 @@ pub enum CreatePaymentResponse {
     #[oai(status = 401)]
     Unauthorized(Json<ErrorResponse>),
 
+    #[oai(status = 409)]
+    Conflict(Json<ErrorResponse>),
+
+    /// The idempotency key was already used for a different request.
+    #[oai(status = 422)]
+    UnprocessableEntity(Json<ErrorResponse>),
+
     #[oai(status = 500)]
     InternalServerError(Json<ErrorResponse>),
 }
 
-fn generate_domain_inscription(domain: &str) -> (String, String) {
-    let (public_key, private_key) = pqcrypto_dilithium::dilithium5_keypair();
-    let public_key = hex::encode(public_key.as_bytes());
-    let private_key = hex::encode(private_key.as_bytes());
-
-    let current_epoch = chrono::Utc::now().timestamp_millis() / 1000;
-
-    let domain_fmt = format!("DOMAIN {domain} {current_epoch}");
-    let validity_fmt = format!("DOMAIN-VALIDITY {domain} dilithium5 {public_key}");
-    let signature_fmt = "null null".to_string();
+/// Checks `domains` against the reserved, blocked and trademark hold lists, returning why the
+/// first blocked domain can not be ordered by `user`.
+pub(crate) async fn check_domain_rules<R: PaymentRepository>(
+    pool: &R,
+    user: Option<&Uuid>,
+    domains: &[String],
+) -> Result<Option<String>, sqlx::Error> {
+    let rules = pool.get_active_domain_rules(domains).await?;
 
-    let inscription = [domain_fmt, validity_fmt, signature_fmt].join("\n");
-
-    (inscription, private_key)
+    Ok(rules
+        .iter()
+        .find(|rule| rule.blocks(user))
+        .map(|rule| match rule.list {
+            DomainList::Reserved => format!("Domain {} is reserved", rule.domain),
+            DomainList::Blocked => format!("Domain {} is not available", rule.domain),
+            DomainList::TrademarkHold => {
+                format!("Domain {} is on hold for its trademark owner", rule.domain)
+            }
+        }))
 }
 
-async fn calculate_price(user: &Uuid, amount: u32, pool: &Repository) -> Result<f64, String> {
+async fn calculate_price<R: PaymentRepository>(
+    user: &Uuid,
+    amount: u32,
+    pool: &R,
+) -> Result<f64, String> {
     let mut final_price = amount as f64 * DOMAIN_PRICE_BTC;
 
     let addresses = pool
-        .get_addresses(&user)
+        .get_addresses(user)
         .await
         .map_err(|_| "Failed to get addresses for user.".to_string())?;
 
@@ -138,59 +181,106 @@ async fn calculate_price(user: &Uuid, amount: u32, pool: &Repository) -> Result<
     Ok((final_price * 10000000f64).ceil() / 10000000f64)
 }
 
-pub async fn new(
-    pool: &Repository,
-    rpc: &Client,
+pub async fn new<R: PaymentRepository>(
+    pool: &R,
+    addresses: &AddressProvider,
+    fees: &FeeEstimator,
+    lightning: Option<&Lightning>,
     user: &Uuid,
+    idempotency_key: Option<&str>,
     data: &CreatePaymentData,
 ) -> CreatePaymentResponse {
-    if data.domains.len() == 0 {
+    let idempotency = match idempotency_key.map(str::trim) {
+        Some(key) if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH => {
+            return CreatePaymentResponse::BadRequest(Json(
+                format!(
+                    "Idempotency key must be between 1 and {} characters",
+                    MAX_IDEMPOTENCY_KEY_LENGTH
+                )
+                .as_str()
+                .into(),
+            ))
+        }
+        Some(key) => Some((key.to_string(), request_hash(data))),
+        None => None,
+    };
+
+    if let Some((key, request_hash)) = &idempotency {
+        match pool
+            .get_idempotent_response(user, key, *IDEMPOTENCY_KEY_RETENTION_HOURS)
+            .await
+        {
+            Ok(Some(stored)) => return replay(stored, request_hash),
+            Ok(None) => {}
+            Err(e) => {
+                error!("Failed to get idempotent response: {}", e);
+                return CreatePaymentResponse::InternalServerError(Json(
+                    "Internal server error".into(),
+                ));
+            }
+        }
+    }
+
+    if data.domains.is_empty() {
         return CreatePaymentResponse::BadRequest(Json("No domains provided".into()));
     }
 
-    let address = rpc
-        .get_new_address(None, Some(AddressType::Bech32m))
-        .unwrap()
-        .require_network(CHAIN.network())
-        .unwrap()
-        .to_string();
+    if data.domains.len() > MAX_DOMAINS_PER_ORDER as usize {
+        return CreatePaymentResponse::BadRequest(Json(
+            format!(
+                "No more than {} domains can be ordered at once",
+                MAX_DOMAINS_PER_ORDER
+            )
+            .as_str()
+            .into(),
+        ));
+    }
 
-    let domains = data
-        .domains
-        .iter()
-        .filter(|d| d.domain.len() > 0)
-        .map(|d| {
-            let domain = if d.domain.ends_with(".o") {
-                d.domain.clone()
-            } else {
-                format!("{}.o", d.domain)
-            };
-
-            CreatePaymentDataDomain {
-                domain,
-                target: d.target.clone(),
+    let lightning = match (data.payment_method, lightning) {
+        (Some(PaymentMethod::Lightning), Some(lightning)) => Some(lightning),
+        (Some(PaymentMethod::Lightning), None) => {
+            return CreatePaymentResponse::BadRequest(Json(
+                "Lightning payments are not available".into(),
+            ))
+        }
+        _ => None,
+    };
+
+    let mut domains: Vec<CreatePaymentDataDomain> = Vec::new();
+
+    for d in data.domains.iter().filter(|d| !d.domain.trim().is_empty()) {
+        let domain = match domain_name::normalize(&d.domain) {
+            Ok(domain) => domain.ascii,
+            Err(e) => {
+                return CreatePaymentResponse::BadRequest(Json(e.to_string().as_str().into()))
             }
-        })
-        .collect::<Vec<_>>();
+        };
 
-    for domain in domains.iter() {
-        if !regex::Regex::new(DOMAIN_REGEX)
-            .unwrap()
-            .is_match(&domain.domain)
-        {
+        if domains.iter().any(|existing| existing.domain == domain) {
             return CreatePaymentResponse::BadRequest(Json(
-                format!("Invalid domain: {}", domain.domain).as_str().into(),
+                format!("Domain {} is included more than once", domain)
+                    .as_str()
+                    .into(),
             ));
         }
 
+        domains.push(CreatePaymentDataDomain {
+            domain,
+            target: d.target.clone(),
+        });
+    }
+
+    if domains.is_empty() {
+        return CreatePaymentResponse::BadRequest(Json("No domains provided".into()));
+    }
+
+    for domain in domains.iter() {
         if let Ok(address) = Address::from_str(&domain.target) {
-            if let Err(_) = address.require_network(CHAIN.network()) {
+            if address.require_network(CHAIN.network()).is_err() {
                 return CreatePaymentResponse::BadRequest(Json(
                     format!(
                         "Address {} for domain {} is not on the correct network (should be on {})",
-                        domain.target,
-                        domain.domain,
-                        CHAIN.to_string()
+                        domain.target, domain.domain, CHAIN
                     )
                     .as_str()
                     .into(),
@@ -208,19 +298,33 @@ pub async fn new(
         }
     }
 
+    let domain_names = domains.iter().map(|d| d.domain.clone()).collect::<Vec<_>>();
+
+    match check_domain_rules(pool, Some(user), &domain_names).await {
+        Ok(None) => {}
+        Ok(Some(reason)) => return CreatePaymentResponse::BadRequest(Json(reason.as_str().into())),
+        Err(e) => {
+            error!("Failed to check domain rules: {}", e);
+            return CreatePaymentResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    }
+
     let already_owned = pool
         .get_already_owned_domains(
-            &user,
+            user,
             &domains.iter().map(|d| d.domain.clone()).collect::<Vec<_>>(),
         )
         .await;
 
     match already_owned {
         Ok(already_owned) => {
-            if already_owned.len() > 0 {
+            if !already_owned.is_empty() {
                 return CreatePaymentResponse::BadRequest(Json(
                     format!(
-                        "Front runner protection: the following domains are already owned or being proccessed (try again later ~35 mins max): {}",
+                        "Front runner protection: the following domains are already owned or being proccessed (try again later ~{} mins max): {}",
+                        *ORDER_EXPIRATION_MINUTES,
                         already_owned.join(", ")
                     )
                     .as_str()
@@ -236,77 +340,542 @@ pub async fn new(
         }
     }
 
-    let domains_total_price = match calculate_price(&user, domains.len() as u32, &pool).await {
+    let domains_price = match calculate_price(user, domains.len() as u32, pool).await {
         Ok(price) => price,
         Err(e) => {
             error!("Failed to calculate price: {}", e);
             return CreatePaymentResponse::InternalServerError(Json(e.as_str().into()));
         }
     };
-    if domains_total_price < MINIMUM_DOMAIN_PRICE_BTC {
+    if domains_price < MINIMUM_DOMAIN_PRICE_BTC {
         return CreatePaymentResponse::InternalServerError(
             Json("Incorrect calculation of discounts, please contact a system administrator to get this in order.".into()),
         );
     }
 
-    let id = pool
-        .create_payment(user, &address, domains_total_price)
-        .await;
+    let inscriptions = domains
+        .iter()
+        .map(|domain| generate_domain_inscription(&domain.domain))
+        .collect::<Vec<_>>();
 
-    match id {
-        Ok(id) => {
-            for domain in domains.iter() {
-                let (inscription, private_key) = generate_domain_inscription(&domain.domain);
-
-                let res = pool
-                    .create_payment_inscription(&id, &domain.target, &inscription)
-                    .await;
-
-                if res.is_err() {
-                    error!(
-                        "Failed to create payment inscription: {}",
-                        res.err().unwrap()
-                    );
-                    return CreatePaymentResponse::InternalServerError(Json(
-                        "Internal server error".into(),
-                    ));
-                }
+    let network_fee = match fees.network_fee(
+        &inscriptions
+            .iter()
+            .map(|(inscription, _)| inscription.clone())
+            .collect::<Vec<_>>(),
+    ) {
+        Ok(fee) => fee,
+        Err(e) => {
+            error!("Failed to estimate network fee: {}", e);
+            return CreatePaymentResponse::InternalServerError(Json(
+                "Failed to estimate network fee".into(),
+            ));
+        }
+    };
 
-                let id = res.unwrap();
+    let domains_total_price = ((domains_price + network_fee) * 100000000f64).round() / 100000000f64;
 
-                let res = pool
-                    .add_private_key(&user, &id, &domain.domain, &private_key)
-                    .await;
+    // Only allocate an address once the order has passed validation, so rejected requests don't
+    // use up addresses.
+    let address = match addresses.new_address(pool).await {
+        Ok(address) => address.to_string(),
+        Err(e) => {
+            error!("Failed to allocate payment address: {}", e);
+            return CreatePaymentResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    };
+
+    let order = match pool.begin().await {
+        Ok(mut uow) => {
+            let order = create_order(
+                &mut uow,
+                user,
+                &address,
+                domains_total_price,
+                lightning,
+                domains.iter().zip(inscriptions).collect(),
+                idempotency
+                    .as_ref()
+                    .map(|(key, hash)| (key.as_str(), hash.as_str())),
+            )
+            .await;
+
+            match order {
+                Ok(OrderOutcome::Created(response, invoice)) => match uow.commit().await {
+                    Ok(()) => Ok(OrderOutcome::Created(response, invoice)),
+                    Err(e) => {
+                        cancel_invoice(lightning, invoice.as_ref()).await;
+                        Err(e)
+                    }
+                },
+                Ok(outcome) => uow.rollback().await.map(|_| outcome),
+                Err(e) => {
+                    if let Err(e) = uow.rollback().await {
+                        error!("Failed to roll back order: {}", e);
+                    }
+                    Err(e)
+                }
+            }
+        }
+        Err(e) => Err(e),
+    };
 
-                if let Err(e) = res {
-                    error!("Failed to create payment private key: {}", e);
-                    return CreatePaymentResponse::InternalServerError(Json(
-                        "Internal server error".into(),
-                    ));
+    match order {
+        Ok(OrderOutcome::Created(response, _)) => CreatePaymentResponse::Ok(Json(response)),
+        Ok(OrderOutcome::Conflict(conflicts)) => CreatePaymentResponse::Conflict(Json(
+            format!(
+                "The following domains are already reserved by another order: {}",
+                conflicts.join(", ")
+            )
+            .as_str()
+            .into(),
+        )),
+        Ok(OrderOutcome::InvoiceFailed) => CreatePaymentResponse::InternalServerError(Json(
+            "Failed to create lightning invoice".into(),
+        )),
+        Ok(OrderOutcome::Replay) => {
+            // A concurrent request with the same key won the race, answer with its response.
+            let (key, request_hash) = idempotency.unwrap();
+            match pool
+                .get_idempotent_response(user, &key, *IDEMPOTENCY_KEY_RETENTION_HOURS)
+                .await
+            {
+                Ok(Some(stored)) => replay(stored, &request_hash),
+                Ok(None) => CreatePaymentResponse::Conflict(Json(
+                    "A request with this idempotency key is already being processed".into(),
+                )),
+                Err(e) => {
+                    error!("Failed to get idempotent response: {}", e);
+                    CreatePaymentResponse::InternalServerError(Json("Internal server error".into()))
                 }
             }
+        }
+        Err(e) => {
+            error!("Failed to create payment: {}", e);
+            CreatePaymentResponse::InternalServerError(Json("Internal server error".into()))
+        }
+    }
+}
 
-            let log_data = format!("New payment created: {} for {}", id, user);
-            let log = pool
-                .add_log(user, LogTypes::PaymentRequested, Some(&log_data))
-                .await;
+enum OrderOutcome {
+    /// The order with the lightning invoice created for it, which has to be cancelled when the
+    /// order does not get committed.
+    Created(CreatePaymentResponseObject, Option<Invoice>),
+    /// Domains that are already reserved by another order.
+    Conflict(Vec<String>),
+    /// The lightning invoice for the order could not be created.
+    InvoiceFailed,
+    /// Another request already claimed the idempotency key.
+    Replay,
+}
 
-            if let Err(e) = log {
-                error!("Failed to create payment log: {}", e);
-                return CreatePaymentResponse::InternalServerError(Json(
-                    "Internal server error".into(),
-                ));
+/// Stores the payment, its domain reservations, the inscriptions and keys generated for its
+/// domains, the log and the response for the idempotency key (key, request hash) in `uow`.
+/// The lightning invoice is only created once the domains are reserved and the idempotency key is
+/// claimed, so orders that conflict or replay don't leave payable invoices behind.
+async fn create_order<U: UnitOfWork, L: LightningBackend>(
+    uow: &mut U,
+    user: &Uuid,
+    address: &str,
+    amount: f64,
+    lightning: Option<&L>,
+    domains: Vec<(&CreatePaymentDataDomain, (String, String))>,
+    idempotency: Option<(&str, &str)>,
+) -> Result<OrderOutcome, sqlx::Error> {
+    let payment = uow
+        .create_payment(user, address, amount, *ORDER_EXPIRATION_MINUTES)
+        .await?;
+
+    let domain_names = domains
+        .iter()
+        .map(|(d, _)| d.domain.clone())
+        .collect::<Vec<_>>();
+    let conflicts = uow.reserve_domains(&payment.id, &domain_names).await?;
+
+    if !conflicts.is_empty() {
+        return Ok(OrderOutcome::Conflict(conflicts));
+    }
+
+    if let Some((key, request_hash)) = idempotency {
+        let claimed = uow
+            .claim_idempotency_key(user, key, request_hash, *IDEMPOTENCY_KEY_RETENTION_HOURS)
+            .await?;
+
+        if !claimed {
+            return Ok(OrderOutcome::Replay);
+        }
+    }
+
+    let invoice = match lightning {
+        Some(lightning) => {
+            let memo = format!("Xiler domains: {}", domain_names.join(", "));
+            let expiry_secs = *ORDER_EXPIRATION_MINUTES as u64 * 60;
+
+            match lightning
+                .create_invoice(btc_to_msat(amount), &memo, expiry_secs)
+                .await
+            {
+                Ok(invoice) => Some(invoice),
+                Err(e) => {
+                    error!("Failed to create lightning invoice: {}", e);
+                    return Ok(OrderOutcome::InvoiceFailed);
+                }
             }
+        }
+        None => None,
+    };
 
-            CreatePaymentResponse::Ok(Json(CreatePaymentResponseObject {
-                id,
-                address,
-                amount: domains_total_price,
-            }))
+    let order = store_order(
+        uow,
+        user,
+        &payment,
+        invoice.as_ref(),
+        domains,
+        domain_names,
+        idempotency.map(|(key, _)| key),
+    )
+    .await;
+
+    match order {
+        Ok(response) => Ok(OrderOutcome::Created(response, invoice)),
+        Err(e) => {
+            cancel_invoice(lightning, invoice.as_ref()).await;
+            Err(e)
         }
+    }
+}
+
+/// Writes everything of an order but its payment and reservations, returning its response.
+async fn store_order<U: UnitOfWork>(
+    uow: &mut U,
+    user: &Uuid,
+    payment: &Payment,
+    invoice: Option<&Invoice>,
+    domains: Vec<(&CreatePaymentDataDomain, (String, String))>,
+    domain_names: Vec<String>,
+    idempotency_key: Option<&str>,
+) -> Result<CreatePaymentResponseObject, sqlx::Error> {
+    if let Some(invoice) = invoice {
+        uow.set_lightning_invoice(
+            &payment.id,
+            &invoice.bolt11,
+            &invoice.payment_hash,
+            &invoice.expires_at,
+        )
+        .await?;
+    }
+
+    for (domain, (inscription, private_key)) in domains {
+        let content_id = uow
+            .create_payment_inscription(&payment.id, &domain.target, &inscription)
+            .await?;
+
+        uow.add_private_key(user, &content_id, &domain.domain, &private_key)
+            .await?;
+    }
+
+    let event = LogEvent::PaymentRequested {
+        payment_id: payment.id,
+        amount: payment.amount,
+        domains: domain_names.clone(),
+    };
+    uow.add_log(user, &event).await?;
+
+    let lightning_invoice = invoice.map(|invoice| invoice.bolt11.clone());
+    let payment_uri = order_payment_uri(
+        &payment.id,
+        &payment.address,
+        payment.amount,
+        &domain_names,
+        lightning_invoice.as_deref(),
+    );
+
+    let response = CreatePaymentResponseObject {
+        id: payment.id,
+        address: payment.address.clone(),
+        amount: payment.amount,
+        expires_at: payment.expires_at,
+        lightning_invoice,
+        payment_uri,
+    };
+
+    if let Some(key) = idempotency_key {
+        uow.store_idempotent_response(user, key, &response.to_json_string())
+            .await?;
+    }
+
+    Ok(response)
+}
+
+/// Cancels the invoice of an order that was not stored, so it can't be paid without an order.
+async fn cancel_invoice<L: LightningBackend>(lightning: Option<&L>, invoice: Option<&Invoice>) {
+    if let (Some(lightning), Some(invoice)) = (lightning, invoice) {
+        if let Err(e) = lightning.cancel_invoice(&invoice.payment_hash).await {
+            error!(
+                "Failed to cancel lightning invoice {}: {}",
+                invoice.payment_hash, e
+            );
+        }
+    }
+}
+
+fn request_hash(data: &CreatePaymentData) -> String {
+    let mut hasher = Sha256::new();
+    hasher.input_str(&data.to_json_string());
+    hasher.result_str()
+}
+
+/// Answers with the response stored for an idempotency key, as long as it was stored for the same
+/// request.
+fn replay(stored: IdempotentResponse, request_hash: &str) -> CreatePaymentResponse {
+    if stored.request_hash != request_hash {
+        return CreatePaymentResponse::UnprocessableEntity(Json(
+            "Idempotency key was already used for a different request".into(),
+        ));
+    }
+
+    match CreatePaymentResponseObject::parse_from_json_string(&stored.response) {
+        Ok(response) => CreatePaymentResponse::Ok(Json(response)),
         Err(e) => {
-            error!("Failed to create payment: {}", e);
+            error!("Failed to parse idempotent response: {}", e.into_message());
             CreatePaymentResponse::InternalServerError(Json("Internal server error".into()))
         }
     }
 }
+
+#[cfg(test)]
+impl CreatePaymentData {
+    /// Order data for (domain, target) pairs.
+    pub(crate) fn new(domains: &[(&str, &str)], payment_method: Option<PaymentMethod>) -> Self {
+        Self {
+            domains: domains
+                .iter()
+                .map(|(domain, target)| CreatePaymentDataDomain {
+                    domain: domain.to_string(),
+                    target: target.to_string(),
+                })
+                .collect(),
+            payment_method,
+        }
+    }
+}
+
+#[cfg(test)]
+impl CreatePaymentResponseObject {
+    pub(crate) fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub(crate) fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::test_utils::TestServices;
+
+    async fn create(
+        pool: &InMemoryRepository,
+        services: &TestServices,
+        user: &Uuid,
+        idempotency_key: Option<&str>,
+        domains: &[(&str, &str)],
+    ) -> CreatePaymentResponse {
+        let data = CreatePaymentData::new(domains, None);
+
+        new(
+            pool,
+            &services.addresses,
+            &services.fees,
+            None,
+            user,
+            idempotency_key,
+            &data,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn creates_an_order_for_the_domains() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let target = services.target();
+
+        let response = match create(
+            &pool,
+            &services,
+            &user,
+            None,
+            &[("Alice.o", &target), ("bob.o", &target)],
+        )
+        .await
+        {
+            CreatePaymentResponse::Ok(Json(response)) => response,
+            _ => panic!("order was not created"),
+        };
+
+        assert_eq!(response.amount, 2f64 * DOMAIN_PRICE_BTC);
+        assert!(response.payment_uri.starts_with("bitcoin:"));
+
+        let payment = pool.get_payment(&response.id).await.unwrap().unwrap();
+        assert_eq!(payment.account_id, user);
+        assert_eq!(payment.address, response.address);
+        assert!(!payment.initiated);
+
+        let domains = pool.get_payment_domains(&response.id).await.unwrap();
+        assert_eq!(domains, vec!["alice.o".to_string(), "bob.o".to_string()]);
+
+        assert_eq!(
+            pool.logs(&user),
+            vec![LogEvent::PaymentRequested {
+                payment_id: response.id,
+                amount: response.amount,
+                domains,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_orders() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let target = services.target();
+
+        let invalid: [&[(&str, &str)]; 4] = [
+            &[],
+            &[("alice.o", "not an address")],
+            &[("alice.o", &target), ("ALICE.o", &target)],
+            &[("xiler.o", &target)],
+        ];
+
+        for domains in invalid {
+            assert!(matches!(
+                create(&pool, &services, &user, None, domains).await,
+                CreatePaymentResponse::BadRequest(_)
+            ));
+        }
+
+        let names = (0..=MAX_DOMAINS_PER_ORDER)
+            .map(|i| format!("domain{}.o", i))
+            .collect::<Vec<_>>();
+        let too_many = names
+            .iter()
+            .map(|name| (name.as_str(), target.as_str()))
+            .collect::<Vec<_>>();
+
+        assert!(matches!(
+            create(&pool, &services, &user, None, &too_many).await,
+            CreatePaymentResponse::BadRequest(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_blocked_domains() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let target = services.target();
+
+        pool.set_domain_rule("alice.o", DomainList::Blocked, "abuse", None, None)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            create(
+                &pool,
+                &services,
+                &Uuid::new_v4(),
+                None,
+                &[("alice.o", &target)]
+            )
+            .await,
+            CreatePaymentResponse::BadRequest(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_domains_ordered_by_another_account() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let target = services.target();
+
+        services.order(&pool, &Uuid::new_v4(), &["alice.o"]).await;
+
+        assert!(matches!(
+            create(
+                &pool,
+                &services,
+                &Uuid::new_v4(),
+                None,
+                &[("alice.o", &target)]
+            )
+            .await,
+            CreatePaymentResponse::BadRequest(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn replays_the_response_of_an_idempotency_key() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let target = services.target();
+        let domains = [("alice.o", target.as_str())];
+
+        let first = match create(&pool, &services, &user, Some("key"), &domains).await {
+            CreatePaymentResponse::Ok(Json(response)) => response,
+            _ => panic!("order was not created"),
+        };
+
+        match create(&pool, &services, &user, Some("key"), &domains).await {
+            CreatePaymentResponse::Ok(Json(replayed)) => assert_eq!(replayed, first),
+            _ => panic!("order was not replayed"),
+        }
+
+        assert!(matches!(
+            create(&pool, &services, &user, Some("key"), &[("bob.o", &target)]).await,
+            CreatePaymentResponse::UnprocessableEntity(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn creates_no_invoice_while_the_idempotency_key_is_claimed() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let target = services.target();
+        let lightning = Lightning::Mock(services.lightning.clone());
+        let data = CreatePaymentData::new(
+            &[("alice.o", target.as_str())],
+            Some(PaymentMethod::Lightning),
+        );
+
+        let mut uow = pool.begin().await.unwrap();
+        assert!(uow
+            .claim_idempotency_key(&user, "key", "hash", *IDEMPOTENCY_KEY_RETENTION_HOURS)
+            .await
+            .unwrap());
+
+        let response = new(
+            &pool,
+            &services.addresses,
+            &services.fees,
+            Some(&lightning),
+            &user,
+            Some("key"),
+            &data,
+        )
+        .await;
+
+        assert!(matches!(response, CreatePaymentResponse::Conflict(_)));
+        assert_eq!(services.lightning.open_invoices(), 0);
+
+        uow.rollback().await.unwrap();
+    }
+}
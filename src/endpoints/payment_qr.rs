@@ -0,0 +1,80 @@
+use poem_openapi::payload::{Binary, PlainText};
+use poem_openapi::{payload::Json, ApiResponse, Enum};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::db::PaymentRepository;
+use crate::responses::error::ErrorResponse;
+use crate::utils::bip21::order_payment_uri;
+use crate::utils::qr_code::{render_png, render_svg};
+
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+#[oai(rename_all = "lowercase")]
+pub enum QrCodeFormat {
+    Png,
+    Svg,
+}
+
+#[derive(ApiResponse)]
+pub enum PaymentQrCodeResponse {
+    #[oai(status = 200, content_type = "image/png")]
+    Png(Binary<Vec<u8>>),
+
+    #[oai(status = 200, content_type = "image/svg+xml")]
+    Svg(PlainText<String>),
+
+    #[oai(status = 404)]
+    NotFound(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+pub async fn payment_qr_code<R: PaymentRepository>(
+    pool: &R,
+    user: &Uuid,
+    payment_id: &Uuid,
+    format: QrCodeFormat,
+) -> PaymentQrCodeResponse {
+    let payment = match pool.get_payment(payment_id).await {
+        Ok(Some(payment)) if payment.account_id == *user => payment,
+        Ok(_) => return PaymentQrCodeResponse::NotFound(Json("Not found".into())),
+        Err(e) => {
+            error!("Error getting payment: {}", e);
+            return PaymentQrCodeResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    };
+
+    let domains = match pool.get_payment_domains(&payment.id).await {
+        Ok(domains) => domains,
+        Err(e) => {
+            error!("Error getting payment domains: {}", e);
+            return PaymentQrCodeResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    };
+
+    let uri = order_payment_uri(
+        &payment.id,
+        &payment.address,
+        payment.amount,
+        &domains,
+        payment.lightning_invoice.as_deref(),
+    );
+
+    let res = match format {
+        QrCodeFormat::Png => render_png(&uri).map(|png| PaymentQrCodeResponse::Png(Binary(png))),
+        QrCodeFormat::Svg => render_svg(&uri).map(|svg| PaymentQrCodeResponse::Svg(PlainText(svg))),
+    };
+
+    match res {
+        Ok(response) => response,
+        Err(e) => {
+            error!("Error rendering QR code for payment {}: {}", payment.id, e);
+            PaymentQrCodeResponse::InternalServerError(Json("Internal server error".into()))
+        }
+    }
+}
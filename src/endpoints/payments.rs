@@ -0,0 +1,197 @@
+use poem_openapi::{payload::Json, ApiResponse};
+use poem_openapi::{Enum, Object};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::db::repositories::models::payment::Payment;
+use crate::db::traits::repository::PaymentsFilter;
+use crate::db::PaymentRepository;
+use crate::responses::error::ErrorResponse;
+
+const DEFAULT_PAYMENTS_LIMIT: u32 = 25;
+const MAX_PAYMENTS_LIMIT: u32 = 100;
+
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+#[oai(rename_all = "lowercase")]
+pub enum PaymentsOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct PaymentHistoryEntry {
+    #[oai(flatten)]
+    payment: Payment,
+    domains: Vec<String>,
+}
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct PaymentsResponseObject {
+    payments: Vec<PaymentHistoryEntry>,
+    next_cursor: Option<Uuid>,
+}
+
+#[derive(ApiResponse)]
+pub enum PaymentsResponse {
+    #[oai(status = 200)]
+    Ok(Json<PaymentsResponseObject>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+pub async fn payments<R: PaymentRepository>(
+    pool: &R,
+    user: &Uuid,
+    mut filter: PaymentsFilter,
+    limit: Option<u32>,
+) -> PaymentsResponse {
+    let limit = limit.unwrap_or(DEFAULT_PAYMENTS_LIMIT);
+
+    if limit == 0 || limit > MAX_PAYMENTS_LIMIT {
+        return PaymentsResponse::BadRequest(Json(
+            format!("Limit must be between 1 and {}", MAX_PAYMENTS_LIMIT)
+                .as_str()
+                .into(),
+        ));
+    }
+
+    if let (Some(from), Some(to)) = (filter.from, filter.to) {
+        if from >= to {
+            return PaymentsResponse::BadRequest(Json("`from` must be before `to`".into()));
+        }
+    }
+
+    // Fetch one extra row to know whether there is a next page.
+    filter.limit = limit as i64 + 1;
+
+    match pool.get_payments(user, &filter).await {
+        Ok(Some(mut payments)) => {
+            let next_cursor = if payments.len() > limit as usize {
+                payments.truncate(limit as usize);
+                payments.last().map(|(payment, _)| payment.id)
+            } else {
+                None
+            };
+
+            let payments = payments
+                .into_iter()
+                .map(|(payment, domains)| PaymentHistoryEntry { payment, domains })
+                .collect::<Vec<_>>();
+
+            PaymentsResponse::Ok(Json(PaymentsResponseObject {
+                payments,
+                next_cursor,
+            }))
+        }
+        Ok(None) => PaymentsResponse::BadRequest(Json("Unknown cursor".into())),
+        Err(e) => {
+            error!("Error getting payments: {}", e);
+            PaymentsResponse::InternalServerError(Json("Internal server error".into()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::records::create_update_order;
+    use crate::endpoints::test_utils::TestServices;
+    use crate::inscription::record::{DomainUpdateInscription, RecordUpdate, SignatureAlgorithm};
+
+    #[tokio::test]
+    async fn lists_the_domains_of_registrations_and_updates() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o", "bob.o"]).await;
+
+        let target = services.target();
+        let update = DomainUpdateInscription {
+            name: "alice.o".to_string(),
+            timestamp: 1,
+            updates: vec![RecordUpdate::Target {
+                address: target.clone(),
+            }],
+            algorithm: SignatureAlgorithm::Dilithium5,
+            signature: vec![0; 4],
+        };
+        let update = create_update_order(
+            &pool,
+            &services.addresses,
+            &services.fees,
+            &user,
+            &target,
+            &update,
+        )
+        .await
+        .unwrap();
+
+        let filter = PaymentsFilter {
+            ascending: true,
+            ..Default::default()
+        };
+        let payments = match payments(&pool, &user, filter, None).await {
+            PaymentsResponse::Ok(Json(response)) => response.payments,
+            _ => panic!("payments could not be listed"),
+        };
+
+        assert_eq!(
+            payments
+                .into_iter()
+                .map(|entry| (entry.payment.id, entry.domains))
+                .collect::<Vec<_>>(),
+            vec![
+                (order.id(), vec!["alice.o".to_string(), "bob.o".to_string()]),
+                (update.id, vec!["alice.o".to_string()]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn pages_with_the_cursor_of_the_account() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let first = services.order(&pool, &user, &["alice.o"]).await;
+        let second = services.order(&pool, &user, &["bob.o"]).await;
+        let other = services.order(&pool, &Uuid::new_v4(), &["carol.o"]).await;
+
+        let page = |cursor| {
+            let filter = PaymentsFilter {
+                cursor,
+                ascending: true,
+                ..Default::default()
+            };
+            payments(&pool, &user, filter, Some(1))
+        };
+
+        let next_cursor = match page(None).await {
+            PaymentsResponse::Ok(Json(response)) => {
+                assert_eq!(response.payments[0].payment.id, first.id());
+                response.next_cursor
+            }
+            _ => panic!("payments could not be listed"),
+        };
+        assert_eq!(next_cursor, Some(first.id()));
+
+        match page(next_cursor).await {
+            PaymentsResponse::Ok(Json(response)) => {
+                assert_eq!(response.payments[0].payment.id, second.id());
+                assert_eq!(response.next_cursor, None);
+            }
+            _ => panic!("payments could not be listed"),
+        }
+
+        for cursor in [Uuid::new_v4(), other.id()] {
+            assert!(matches!(
+                page(Some(cursor)).await,
+                PaymentsResponse::BadRequest(_)
+            ));
+        }
+    }
+}
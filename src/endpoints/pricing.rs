@@ -1,13 +1,29 @@
+use lazy_static::lazy_static;
 use poem_openapi::Object;
 use poem_openapi::{payload::Json, ApiResponse};
 use tracing::error;
 use uuid::Uuid;
 
+use crate::bitcoin::fees::{inscription_vbytes, FeeEstimator};
 use crate::db::traits::repository::LoyaltyDiscount;
-use crate::db::{PaymentRepository, Repository};
+use crate::db::PaymentRepository;
+use crate::endpoints::new::check_domain_rules;
+use crate::inscription::generate_domain_inscription;
 use crate::responses::error::ErrorResponse;
+use crate::utils::domain_name;
 use crate::utils::get_wallets_collections::get_wallets_collections;
-use crate::{DOMAIN_PRICE_BTC, MINIMUM_DOMAIN_PRICE_BTC};
+use crate::{DOMAIN_PRICE_BTC, MAX_DOMAINS_PER_ORDER, MINIMUM_DOMAIN_PRICE_BTC};
+
+/// Stand-in for the domains that are not known yet when quoting a price, the real
+/// network fee is calculated on the actual inscriptions when the order is created.
+const PRICING_SAMPLE_DOMAIN: &str = "xxxxxxxxxxxx.o";
+
+lazy_static! {
+    /// Every inscription has the same size apart from the domain, so the sample only has to
+    /// be generated and signed once.
+    static ref PRICING_SAMPLE_VBYTES: u64 =
+        inscription_vbytes(generate_domain_inscription(PRICING_SAMPLE_DOMAIN).0.as_bytes());
+}
 
 #[derive(Debug, Object, Clone, PartialEq)]
 pub struct LoyaltyDiscountResponseObject {
@@ -24,6 +40,10 @@ pub struct PricingResponseObject {
     non_stackable_loyalty_discount: f64,
     non_stackable_loyalty_discount_currency: String,
 
+    /// Estimated fee rate in sat/vB, only present when the dynamic network fee is enabled.
+    network_fee_rate: Option<f64>,
+    network_fee: f64,
+
     final_price: f64,
 }
 
@@ -42,12 +62,51 @@ pub enum PricingResponse {
     InternalServerError(Json<ErrorResponse>),
 }
 
-pub async fn get_price(pool: &Repository, user: &Uuid, amount: u32) -> PricingResponse {
+pub async fn get_price<R: PaymentRepository>(
+    pool: &R,
+    fees: &FeeEstimator,
+    user: &Uuid,
+    amount: u32,
+    domains: Option<&str>,
+) -> PricingResponse {
     if amount == 0 {
         return PricingResponse::BadRequest(Json("Can not calculate price for 0 domains.".into()));
     }
 
-    let addresses = pool.get_addresses(&user).await;
+    if amount > MAX_DOMAINS_PER_ORDER {
+        return PricingResponse::BadRequest(Json(
+            format!(
+                "Can not calculate price for more than {} domains.",
+                MAX_DOMAINS_PER_ORDER
+            )
+            .as_str()
+            .into(),
+        ));
+    }
+
+    if let Some(domains) = domains {
+        let mut domain_names = Vec::new();
+
+        for domain in domains.split(',').filter(|d| !d.trim().is_empty()) {
+            match domain_name::normalize(domain) {
+                Ok(domain) => domain_names.push(domain.ascii),
+                Err(e) => return PricingResponse::BadRequest(Json(e.to_string().as_str().into())),
+            }
+        }
+
+        match check_domain_rules(pool, Some(user), &domain_names).await {
+            Ok(None) => {}
+            Ok(Some(reason)) => return PricingResponse::BadRequest(Json(reason.as_str().into())),
+            Err(e) => {
+                error!("get_price - Failed to check domain rules: {}", e);
+                return PricingResponse::InternalServerError(Json(
+                    "Failed to check domain rules.".into(),
+                ));
+            }
+        }
+    }
+
+    let addresses = pool.get_addresses(user).await;
 
     if let Err(e) = addresses {
         error!("get_price - Failed to get addresses for user: {}", e);
@@ -146,11 +205,109 @@ pub async fn get_price(pool: &Repository, user: &Uuid, amount: u32) -> PricingRe
         );
     }
 
+    let (network_fee_rate, network_fee) = if fees.enabled() {
+        match (
+            fees.fee_rate(),
+            fees.network_fee_for(*PRICING_SAMPLE_VBYTES, amount),
+        ) {
+            (Ok(fee_rate), Ok(fee)) => (Some(fee_rate), fee),
+            (Err(e), _) | (_, Err(e)) => {
+                error!("get_price - Failed to estimate network fee: {}", e);
+                return PricingResponse::InternalServerError(Json(
+                    "Failed to estimate network fee.".into(),
+                ));
+            }
+        }
+    } else {
+        (None, 0f64)
+    };
+
+    final_price = ((final_price + network_fee) * 100000000f64).round() / 100000000f64;
+
     PricingResponse::Ok(Json(PricingResponseObject {
         stackable_loyalty_discounts,
         non_stackable_loyalty_discounts,
         non_stackable_loyalty_discount,
         non_stackable_loyalty_discount_currency,
+        network_fee_rate,
+        network_fee,
         final_price,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repositories::models::domain_rule::DomainList;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::test_utils::TestServices;
+
+    async fn price(
+        pool: &InMemoryRepository,
+        services: &TestServices,
+        amount: u32,
+        domains: Option<&str>,
+    ) -> PricingResponse {
+        get_price(pool, &services.fees, &Uuid::new_v4(), amount, domains).await
+    }
+
+    #[tokio::test]
+    async fn prices_domains_without_network_fee() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+
+        match price(&pool, &services, 3, None).await {
+            PricingResponse::Ok(Json(pricing)) => {
+                assert_eq!(pricing.final_price, 3f64 * DOMAIN_PRICE_BTC);
+                assert_eq!(pricing.network_fee, 0f64);
+                assert_eq!(pricing.network_fee_rate, None);
+            }
+            _ => panic!("price was not calculated"),
+        }
+    }
+
+    #[tokio::test]
+    async fn adds_the_network_fee_per_domain() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::with_network_fee(10.0);
+
+        let (one, two) = match (
+            price(&pool, &services, 1, None).await,
+            price(&pool, &services, 2, None).await,
+        ) {
+            (PricingResponse::Ok(Json(one)), PricingResponse::Ok(Json(two))) => (one, two),
+            _ => panic!("price was not calculated"),
+        };
+
+        assert_eq!(one.network_fee_rate, Some(10.0));
+        assert!(one.network_fee > 0f64);
+        assert!((two.network_fee - 2f64 * one.network_fee).abs() < 0.00000002);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_requests() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+
+        pool.set_domain_rule("alice.o", DomainList::Blocked, "abuse", None, None)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            price(&pool, &services, 0, None).await,
+            PricingResponse::BadRequest(_)
+        ));
+        assert!(matches!(
+            price(&pool, &services, MAX_DOMAINS_PER_ORDER + 1, None).await,
+            PricingResponse::BadRequest(_)
+        ));
+        assert!(matches!(
+            price(&pool, &services, 1, Some("a.b.o")).await,
+            PricingResponse::BadRequest(_)
+        ));
+        assert!(matches!(
+            price(&pool, &services, 1, Some("bob.o,alice.o")).await,
+            PricingResponse::BadRequest(_)
+        ));
+    }
+}
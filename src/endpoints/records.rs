@@ -0,0 +1,744 @@
+use std::str::FromStr;
+
+use bitcoincore_rpc::bitcoin::Address;
+use chrono::NaiveDateTime;
+use poem_openapi::{payload::Json, ApiResponse};
+use poem_openapi::{Enum, Object};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::bitcoin::address_provider::AddressProvider;
+use crate::bitcoin::fees::FeeEstimator;
+use crate::db::log::LogEvent;
+use crate::db::traits::UnitOfWork;
+use crate::db::PaymentRepository;
+use crate::inscription::record::{DomainUpdateInscription, Record, RecordUpdate};
+use crate::inscription::{sign_domain_update, verify_domain_inscription, verify_domain_update};
+use crate::responses::error::ErrorResponse;
+use crate::utils::bip21::order_payment_uri;
+use crate::utils::domain_name;
+use crate::{CHAIN, ORDER_EXPIRATION_MINUTES, RECORD_UPDATE_PRICE_BTC};
+
+#[derive(Debug, Enum, Clone, Copy, Eq, PartialEq)]
+#[oai(rename_all = "snake_case")]
+pub enum RecordKind {
+    Target,
+    Text,
+    Content,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct RecordUpdateData {
+    kind: RecordKind,
+    /// Only used by text records.
+    key: Option<String>,
+    value: String,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct UpdateRecordsData {
+    records: Vec<RecordUpdateData>,
+    /// Required without custodial signing, must be the timestamp that was signed.
+    timestamp: Option<i64>,
+    /// Hex encoded signature over the unsigned update, required without custodial signing.
+    signature: Option<String>,
+}
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct UpdateRecordsResponseObject {
+    pub id: Uuid,
+    pub address: String,
+    pub amount: f64,
+    pub expires_at: NaiveDateTime,
+    pub payment_uri: String,
+    pub inscription: String,
+}
+
+#[derive(ApiResponse)]
+pub enum UpdateRecordsResponse {
+    #[oai(status = 200)]
+    Ok(Json<UpdateRecordsResponseObject>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<ErrorResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct CustodialSigningData {
+    enabled: bool,
+}
+
+#[derive(ApiResponse)]
+pub enum CustodialSigningResponse {
+    #[oai(status = 200)]
+    Ok,
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<ErrorResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+/// Round trips through the parser so only updates that indexers can read get inscribed.
+fn checked(update: RecordUpdate) -> Result<RecordUpdate, String> {
+    match update.to_string().parse::<Record>() {
+        Ok(Record::Update(parsed)) if parsed == update => Ok(update),
+        _ => Err(format!("Invalid record: {}", update)),
+    }
+}
+
+pub(crate) fn target_update(address: &str) -> Result<RecordUpdate, String> {
+    let parsed =
+        Address::from_str(address).map_err(|_| format!("Invalid target address: {}", address))?;
+
+    if parsed.require_network(CHAIN.network()).is_err() {
+        return Err(format!(
+            "Address {} is not on the correct network (should be on {})",
+            address, CHAIN
+        ));
+    }
+
+    checked(RecordUpdate::Target {
+        address: address.to_string(),
+    })
+}
+
+fn to_record_update(data: &RecordUpdateData) -> Result<RecordUpdate, String> {
+    let update = match data.kind {
+        RecordKind::Target => return target_update(&data.value),
+        RecordKind::Text => RecordUpdate::Text {
+            key: data
+                .key
+                .clone()
+                .ok_or_else(|| "Text records require a key".to_string())?,
+            value: data.value.clone(),
+        },
+        RecordKind::Content => RecordUpdate::Content {
+            inscription_id: data.value.clone(),
+        },
+    };
+
+    checked(update)
+}
+
+pub async fn update_records<R: PaymentRepository>(
+    pool: &R,
+    addresses: &AddressProvider,
+    fees: &FeeEstimator,
+    user: &Uuid,
+    domain: &str,
+    data: &UpdateRecordsData,
+) -> UpdateRecordsResponse {
+    if data.records.is_empty() {
+        return UpdateRecordsResponse::BadRequest(Json("No records provided".into()));
+    }
+
+    let domain = match domain_name::normalize(domain) {
+        Ok(domain) => domain.ascii,
+        Err(e) => return UpdateRecordsResponse::BadRequest(Json(e.to_string().as_str().into())),
+    };
+
+    let owned = match pool.get_owned_domain(user, &domain).await {
+        Ok(Some(owned)) => owned,
+        Ok(None) => {
+            return UpdateRecordsResponse::NotFound(Json(
+                "You do not own this domain or it does not exist!".into(),
+            ))
+        }
+        Err(e) => {
+            error!("Failed to get owned domain: {}", e);
+            return UpdateRecordsResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    };
+
+    if !owned.completed {
+        return UpdateRecordsResponse::BadRequest(Json(
+            "The domain has not been inscribed yet".into(),
+        ));
+    }
+
+    let domain_inscription = match verify_domain_inscription(&owned.inscription) {
+        Ok(inscription) => inscription,
+        Err(e) => {
+            error!("Stored inscription of {} does not verify: {}", domain, e);
+            return UpdateRecordsResponse::BadRequest(Json(
+                "This domain was inscribed without a signing key and can not be updated".into(),
+            ));
+        }
+    };
+
+    let mut updates = Vec::new();
+
+    for record in data.records.iter() {
+        match to_record_update(record) {
+            Ok(update) => updates.push(update),
+            Err(e) => return UpdateRecordsResponse::BadRequest(Json(e.as_str().into())),
+        }
+    }
+
+    let mut update = DomainUpdateInscription {
+        name: owned.domain.clone(),
+        timestamp: data
+            .timestamp
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis() / 1000),
+        updates,
+        algorithm: domain_inscription.algorithm,
+        signature: Vec::new(),
+    };
+
+    match (&data.signature, owned.custodial_signing) {
+        (Some(signature), _) => {
+            if data.timestamp.is_none() {
+                return UpdateRecordsResponse::BadRequest(Json(
+                    "A signed update requires the signed timestamp".into(),
+                ));
+            }
+
+            update.signature = match hex::decode(signature) {
+                Ok(signature) => signature,
+                Err(_) => {
+                    return UpdateRecordsResponse::BadRequest(Json(
+                        "Signature is not valid hex".into(),
+                    ))
+                }
+            };
+        }
+        (None, true) => {
+            if let Err(e) = sign_domain_update(&mut update, &owned.private_key) {
+                error!("Failed to sign record update for {}: {}", domain, e);
+                return UpdateRecordsResponse::InternalServerError(Json(
+                    "Internal server error".into(),
+                ));
+            }
+        }
+        (None, false) => {
+            return UpdateRecordsResponse::BadRequest(Json(
+                "Sign the update or opt into custodial signing for this domain".into(),
+            ))
+        }
+    }
+
+    if let Err(e) = verify_domain_update(&update, &domain_inscription) {
+        return UpdateRecordsResponse::BadRequest(Json(
+            format!("Invalid update signature: {}", e).as_str().into(),
+        ));
+    }
+
+    // The update goes to whoever holds the domain now, which later updates may have changed.
+    let target = match pool.get_domain_registration(&owned.domain).await {
+        Ok(Some(registration)) => registration.target,
+        Ok(None) => {
+            return UpdateRecordsResponse::NotFound(Json(
+                "You do not own this domain or it does not exist!".into(),
+            ))
+        }
+        Err(e) => {
+            error!("Failed to get domain registration: {}", e);
+            return UpdateRecordsResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    };
+
+    match create_update_order(pool, addresses, fees, user, &target, &update).await {
+        Ok(order) => UpdateRecordsResponse::Ok(Json(order)),
+        Err(e) => UpdateRecordsResponse::InternalServerError(Json(e.as_str().into())),
+    }
+}
+
+/// A priced record update order with its payment address, written by `UpdateOrder::create`.
+pub(crate) struct UpdateOrder<'a> {
+    update: &'a DomainUpdateInscription,
+    target: &'a str,
+    inscription: String,
+    address: String,
+    amount: f64,
+}
+
+/// Prices the order that gets `update` inscribed to `target` and allocates its payment address.
+pub(crate) async fn prepare_update_order<'a, R: PaymentRepository>(
+    pool: &R,
+    addresses: &AddressProvider,
+    fees: &FeeEstimator,
+    target: &'a str,
+    update: &'a DomainUpdateInscription,
+) -> Result<UpdateOrder<'a>, String> {
+    let inscription = update.to_string();
+
+    let network_fee = fees
+        .network_fee(std::slice::from_ref(&inscription))
+        .map_err(|e| {
+            error!("Failed to estimate network fee: {}", e);
+            "Failed to estimate network fee".to_string()
+        })?;
+
+    let amount = ((RECORD_UPDATE_PRICE_BTC + network_fee) * 100000000f64).round() / 100000000f64;
+
+    let address = addresses
+        .new_address(pool)
+        .await
+        .map_err(|e| {
+            error!("Failed to allocate payment address: {}", e);
+            "Internal server error".to_string()
+        })?
+        .to_string();
+
+    Ok(UpdateOrder {
+        update,
+        target,
+        inscription,
+        address,
+        amount,
+    })
+}
+
+impl UpdateOrder<'_> {
+    /// Writes the payment, its inscription and the request log within `uow`.
+    pub(crate) async fn create<U: UnitOfWork>(
+        self,
+        uow: &mut U,
+        user: &Uuid,
+    ) -> Result<UpdateRecordsResponseObject, sqlx::Error> {
+        let payment = uow
+            .create_payment(user, &self.address, self.amount, *ORDER_EXPIRATION_MINUTES)
+            .await?;
+
+        let content_id = uow
+            .create_payment_inscription(&payment.id, self.target, &self.inscription)
+            .await?;
+
+        uow.add_domain_update(&content_id, &self.update.name)
+            .await?;
+
+        let event = LogEvent::RecordUpdateRequested {
+            payment_id: payment.id,
+            domain: self.update.name.clone(),
+        };
+        uow.add_log(user, &event).await?;
+
+        let payment_uri = order_payment_uri(
+            &payment.id,
+            &self.address,
+            self.amount,
+            std::slice::from_ref(&self.update.name),
+            None,
+        );
+
+        Ok(UpdateRecordsResponseObject {
+            id: payment.id,
+            address: self.address,
+            amount: self.amount,
+            expires_at: payment.expires_at,
+            payment_uri,
+            inscription: self.inscription,
+        })
+    }
+}
+
+/// Creates the payment that gets `update` inscribed to `target` once it is paid.
+pub(crate) async fn create_update_order<R: PaymentRepository>(
+    pool: &R,
+    addresses: &AddressProvider,
+    fees: &FeeEstimator,
+    user: &Uuid,
+    target: &str,
+    update: &DomainUpdateInscription,
+) -> Result<UpdateRecordsResponseObject, String> {
+    let order = prepare_update_order(pool, addresses, fees, target, update).await?;
+
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to create record update order: {}", e);
+        "Internal server error".to_string()
+    };
+
+    let mut uow = pool.begin().await.map_err(internal_error)?;
+    let order = order.create(&mut uow, user).await.map_err(internal_error)?;
+    uow.commit().await.map_err(internal_error)?;
+
+    Ok(order)
+}
+
+pub async fn set_custodial_signing<R: PaymentRepository>(
+    pool: &R,
+    user: &Uuid,
+    domain: &str,
+    data: &CustodialSigningData,
+) -> CustodialSigningResponse {
+    let domain = match domain_name::normalize(domain) {
+        Ok(domain) => domain.ascii,
+        Err(e) => return CustodialSigningResponse::BadRequest(Json(e.to_string().as_str().into())),
+    };
+
+    match pool
+        .set_custodial_signing(user, &domain, data.enabled)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return CustodialSigningResponse::NotFound(Json(
+                "You do not own this domain or it does not exist!".into(),
+            ))
+        }
+        Err(e) => {
+            error!("Failed to set custodial signing: {}", e);
+            return CustodialSigningResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            ));
+        }
+    }
+
+    let event = LogEvent::CustodialSigningChanged {
+        domain,
+        enabled: data.enabled,
+    };
+
+    if let Err(e) = pool.add_log(user, &event).await {
+        error!("Failed to create custodial signing log: {}", e);
+        return CustodialSigningResponse::InternalServerError(Json("Internal server error".into()));
+    }
+
+    CustodialSigningResponse::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::test_utils::TestServices;
+    use crate::inscription::generate_domain_inscription;
+    use crate::inscription::record::SignatureAlgorithm;
+    use crate::DOMAIN_PRICE_BTC;
+
+    #[tokio::test]
+    async fn creates_the_update_order_with_its_inscription() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let target = services.target();
+        let update = DomainUpdateInscription {
+            name: "alice.o".to_string(),
+            timestamp: 1,
+            updates: vec![RecordUpdate::Target {
+                address: target.clone(),
+            }],
+            algorithm: SignatureAlgorithm::Dilithium5,
+            signature: vec![0; 4],
+        };
+
+        let order = create_update_order(
+            &pool,
+            &services.addresses,
+            &services.fees,
+            &user,
+            &target,
+            &update,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(order.amount, RECORD_UPDATE_PRICE_BTC);
+        assert_eq!(order.inscription, update.to_string());
+
+        let payment = pool.get_payment(&order.id).await.unwrap().unwrap();
+        assert_eq!(payment.account_id, user);
+        assert_eq!(payment.address, order.address);
+
+        let inscriptions = pool.get_payment_inscriptions(&order.id).await.unwrap();
+        assert_eq!(inscriptions.len(), 1);
+        assert_eq!(inscriptions[0].target, target);
+        assert_eq!(inscriptions[0].content, order.inscription);
+
+        assert_eq!(
+            pool.logs(&user),
+            [LogEvent::RecordUpdateRequested {
+                payment_id: order.id,
+                domain: "alice.o".to_string(),
+            }]
+        );
+    }
+
+    async fn inscribed_domain(
+        pool: &InMemoryRepository,
+        services: &TestServices,
+        user: &Uuid,
+    ) -> Uuid {
+        let order = services.order(pool, user, &["alice.o"]).await;
+        inscribe(pool, &order.id()).await;
+
+        order.id()
+    }
+
+    async fn inscribe(pool: &InMemoryRepository, payment_id: &Uuid) {
+        pool.initiate_payment(payment_id).await.unwrap();
+        pool.complete_payment(payment_id).await.unwrap();
+        pool.set_reveal_tx(payment_id, "reveal");
+    }
+
+    fn target_records(target: &str) -> Vec<RecordUpdateData> {
+        vec![RecordUpdateData {
+            kind: RecordKind::Target,
+            key: None,
+            value: target.to_string(),
+        }]
+    }
+
+    async fn update(
+        pool: &InMemoryRepository,
+        services: &TestServices,
+        user: &Uuid,
+        data: &UpdateRecordsData,
+    ) -> UpdateRecordsResponse {
+        update_records(
+            pool,
+            &services.addresses,
+            &services.fees,
+            user,
+            "alice.o",
+            data,
+        )
+        .await
+    }
+
+    /// The update a client would sign for `target` at `timestamp`, with its hex signature.
+    async fn client_signature(
+        pool: &InMemoryRepository,
+        user: &Uuid,
+        target: &str,
+        timestamp: i64,
+    ) -> String {
+        let owned = pool
+            .get_owned_domain(user, "alice.o")
+            .await
+            .unwrap()
+            .unwrap();
+        let mut update = DomainUpdateInscription {
+            name: "alice.o".to_string(),
+            timestamp,
+            updates: vec![RecordUpdate::Target {
+                address: target.to_string(),
+            }],
+            algorithm: SignatureAlgorithm::Dilithium5,
+            signature: Vec::new(),
+        };
+        sign_domain_update(&mut update, &owned.private_key).unwrap();
+
+        hex::encode(update.signature)
+    }
+
+    #[tokio::test]
+    async fn signs_updates_of_custodial_domains() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        inscribed_domain(&pool, &services, &user).await;
+        let target = services.target();
+        let data = UpdateRecordsData {
+            records: target_records(&target),
+            timestamp: None,
+            signature: None,
+        };
+
+        assert!(matches!(
+            update(&pool, &services, &user, &data).await,
+            UpdateRecordsResponse::BadRequest(_)
+        ));
+
+        pool.set_custodial_signing(&user, "alice.o", true)
+            .await
+            .unwrap();
+
+        let order = match update(&pool, &services, &user, &data).await {
+            UpdateRecordsResponse::Ok(Json(order)) => order,
+            _ => panic!("the update was not accepted"),
+        };
+
+        let owned = pool
+            .get_owned_domain(&user, "alice.o")
+            .await
+            .unwrap()
+            .unwrap();
+        let domain_inscription = verify_domain_inscription(&owned.inscription).unwrap();
+        let inscribed = order
+            .inscription
+            .parse::<DomainUpdateInscription>()
+            .unwrap();
+        assert_eq!(inscribed.target(), Some(target.as_str()));
+        assert!(verify_domain_update(&inscribed, &domain_inscription).is_ok());
+    }
+
+    #[tokio::test]
+    async fn accepts_updates_signed_by_the_client() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        inscribed_domain(&pool, &services, &user).await;
+        let target = services.target();
+        let signature = client_signature(&pool, &user, &target, 1700000000).await;
+
+        let unstamped = UpdateRecordsData {
+            records: target_records(&target),
+            timestamp: None,
+            signature: Some(signature.clone()),
+        };
+        assert!(matches!(
+            update(&pool, &services, &user, &unstamped).await,
+            UpdateRecordsResponse::BadRequest(_)
+        ));
+
+        let signed = UpdateRecordsData {
+            timestamp: Some(1700000000),
+            ..unstamped
+        };
+        let order = match update(&pool, &services, &user, &signed).await {
+            UpdateRecordsResponse::Ok(Json(order)) => order,
+            _ => panic!("the update was not accepted"),
+        };
+
+        let inscribed = order
+            .inscription
+            .parse::<DomainUpdateInscription>()
+            .unwrap();
+        assert_eq!(inscribed.timestamp, 1700000000);
+        assert_eq!(hex::encode(inscribed.signature), signature);
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_signatures() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        inscribed_domain(&pool, &services, &user).await;
+        let target = services.target();
+        let signature = client_signature(&pool, &user, &target, 1700000000).await;
+
+        let invalid = [("not hex".to_string(), 1700000000), (signature, 1700000001)];
+
+        for (signature, timestamp) in invalid {
+            let data = UpdateRecordsData {
+                records: target_records(&target),
+                timestamp: Some(timestamp),
+                signature: Some(signature),
+            };
+
+            assert!(matches!(
+                update(&pool, &services, &user, &data).await,
+                UpdateRecordsResponse::BadRequest(_)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn only_the_owner_updates_records() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        inscribed_domain(&pool, &services, &user).await;
+        pool.set_custodial_signing(&user, "alice.o", true)
+            .await
+            .unwrap();
+        let data = UpdateRecordsData {
+            records: target_records(&services.target()),
+            timestamp: None,
+            signature: None,
+        };
+
+        assert!(matches!(
+            update(&pool, &services, &Uuid::new_v4(), &data).await,
+            UpdateRecordsResponse::NotFound(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_updates_of_unverifiable_inscriptions() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let (_, private_key) = generate_domain_inscription("alice.o");
+
+        let mut uow = pool.begin().await.unwrap();
+        let payment = uow
+            .create_payment(&user, "address", DOMAIN_PRICE_BTC, 35)
+            .await
+            .unwrap();
+        let content_id = uow
+            .create_payment_inscription(&payment.id, &services.target(), "DOMAIN alice.o")
+            .await
+            .unwrap();
+        uow.add_private_key(&user, &content_id, "alice.o", &private_key)
+            .await
+            .unwrap();
+        uow.commit().await.unwrap();
+        inscribe(&pool, &payment.id).await;
+
+        pool.set_custodial_signing(&user, "alice.o", true)
+            .await
+            .unwrap();
+        let data = UpdateRecordsData {
+            records: target_records(&services.target()),
+            timestamp: None,
+            signature: None,
+        };
+
+        match update(&pool, &services, &user, &data).await {
+            UpdateRecordsResponse::BadRequest(Json(e)) => assert_eq!(
+                e,
+                "This domain was inscribed without a signing key and can not be updated".into()
+            ),
+            _ => panic!("the update was accepted"),
+        }
+    }
+
+    #[tokio::test]
+    async fn inscribes_updates_to_the_current_target() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        inscribed_domain(&pool, &services, &user).await;
+        pool.set_custodial_signing(&user, "alice.o", true)
+            .await
+            .unwrap();
+        let moved_to = services.target();
+
+        let data = UpdateRecordsData {
+            records: target_records(&moved_to),
+            timestamp: None,
+            signature: None,
+        };
+        let first = match update(&pool, &services, &user, &data).await {
+            UpdateRecordsResponse::Ok(Json(order)) => order,
+            _ => panic!("the update was not accepted"),
+        };
+        inscribe(&pool, &first.id).await;
+
+        let data = UpdateRecordsData {
+            records: target_records(&services.target()),
+            timestamp: None,
+            signature: None,
+        };
+        let second = match update(&pool, &services, &user, &data).await {
+            UpdateRecordsResponse::Ok(Json(order)) => order,
+            _ => panic!("the update was not accepted"),
+        };
+
+        let inscriptions = pool.get_payment_inscriptions(&second.id).await.unwrap();
+        assert_eq!(inscriptions[0].target, moved_to);
+    }
+}
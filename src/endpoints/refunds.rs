@@ -0,0 +1,352 @@
+use std::str::FromStr;
+
+use bitcoincore_rpc::bitcoin::Address;
+use poem_openapi::Object;
+use poem_openapi::{payload::Json, ApiResponse};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::db::log::LogEvent;
+use crate::db::repositories::models::late_payment::LatePaymentStatus;
+use crate::db::repositories::models::refund::Refund;
+use crate::db::PaymentRepository;
+use crate::responses::error::ErrorResponse;
+use crate::CHAIN;
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct CreateRefundData {
+    payment_id: Option<Uuid>,
+    late_payment_id: Option<Uuid>,
+    address: String,
+}
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct CreateRefundResponseObject {
+    id: Uuid,
+    amount: f64,
+}
+
+#[derive(ApiResponse)]
+pub enum CreateRefundResponse {
+    #[oai(status = 200)]
+    Ok(Json<CreateRefundResponseObject>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<ErrorResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct RefundsResponseObject {
+    refunds: Vec<Refund>,
+}
+
+#[derive(ApiResponse)]
+pub enum RefundsResponse {
+    #[oai(status = 200)]
+    Ok(Json<RefundsResponseObject>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+/// Works out how much of the given source is refundable to `user`, together with what has been
+/// refunded of it already.
+async fn refundable_amount<R: PaymentRepository>(
+    pool: &R,
+    user: &Uuid,
+    data: &CreateRefundData,
+) -> Result<(f64, f64), CreateRefundResponse> {
+    match (&data.payment_id, &data.late_payment_id) {
+        (None, Some(late_payment_id)) => match pool.get_late_payment(late_payment_id).await {
+            Ok(Some(late_payment)) if late_payment.account_id == *user => {
+                if late_payment.status == LatePaymentStatus::Reactivated {
+                    return Err(CreateRefundResponse::Conflict(Json(
+                        "This late payment has been credited to its order".into(),
+                    )));
+                }
+
+                Ok((late_payment.amount, 0f64))
+            }
+            Ok(_) => Err(CreateRefundResponse::NotFound(Json("Not found".into()))),
+            Err(e) => {
+                error!("Error getting late payment: {}", e);
+                Err(CreateRefundResponse::InternalServerError(Json(
+                    "Internal server error".into(),
+                )))
+            }
+        },
+        (Some(payment_id), None) => match pool.get_payment(payment_id).await {
+            Ok(Some(payment)) if payment.account_id == *user => {
+                let refunded = match pool.get_refunded_amount(payment_id).await {
+                    Ok(refunded) => refunded,
+                    Err(e) => {
+                        error!("Error getting refunded amount: {}", e);
+                        return Err(CreateRefundResponse::InternalServerError(Json(
+                            "Internal server error".into(),
+                        )));
+                    }
+                };
+
+                // Orders that were cancelled, for example because their inscription failed, are
+                // refunded in full, fulfilled orders only for what was paid on top. What was
+                // refunded before, like the overpayment of an order cancelled later, is deducted.
+                let refundable = if payment.refund_required || payment.expired {
+                    payment.received
+                } else if payment.initiated {
+                    payment.received - payment.amount
+                } else {
+                    0f64
+                };
+                // Amounts are whole sats, rounding drops the float noise of the subtraction.
+                let refundable = ((refundable - refunded) * 100000000f64).round() / 100000000f64;
+
+                if refundable <= 0f64 {
+                    return Err(CreateRefundResponse::Conflict(Json(
+                        "Nothing to refund for this payment".into(),
+                    )));
+                }
+
+                Ok((refundable, refunded))
+            }
+            Ok(_) => Err(CreateRefundResponse::NotFound(Json("Not found".into()))),
+            Err(e) => {
+                error!("Error getting payment: {}", e);
+                Err(CreateRefundResponse::InternalServerError(Json(
+                    "Internal server error".into(),
+                )))
+            }
+        },
+        _ => Err(CreateRefundResponse::BadRequest(Json(
+            "Provide either a payment_id or a late_payment_id".into(),
+        ))),
+    }
+}
+
+pub async fn create<R: PaymentRepository>(
+    pool: &R,
+    user: &Uuid,
+    data: &CreateRefundData,
+) -> CreateRefundResponse {
+    match Address::from_str(&data.address) {
+        Ok(address) => {
+            if address.require_network(CHAIN.network()).is_err() {
+                return CreateRefundResponse::BadRequest(Json(
+                    format!(
+                        "Refund address {} is not on the correct network (should be on {})",
+                        data.address, CHAIN
+                    )
+                    .as_str()
+                    .into(),
+                ));
+            }
+        }
+        Err(_) => {
+            return CreateRefundResponse::BadRequest(Json(
+                format!("Invalid refund address: {}", data.address)
+                    .as_str()
+                    .into(),
+            ));
+        }
+    }
+
+    let (amount, refunded) = match refundable_amount(pool, user, data).await {
+        Ok(amounts) => amounts,
+        Err(response) => return response,
+    };
+
+    // The late payment moves into the refund state together with the refund, so it can no
+    // longer be credited to its order.
+    let id = match pool
+        .create_refund(
+            user,
+            data.payment_id.as_ref(),
+            data.late_payment_id.as_ref(),
+            amount,
+            refunded,
+            &data.address,
+        )
+        .await
+    {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            return CreateRefundResponse::Conflict(Json(
+                "This payment is already being refunded or has been credited to its order".into(),
+            ))
+        }
+        Err(e) => {
+            error!("Error creating refund: {}", e);
+            return CreateRefundResponse::InternalServerError(Json("Internal server error".into()));
+        }
+    };
+
+    let event = LogEvent::RefundRequested {
+        refund_id: id,
+        payment_id: data.payment_id,
+        late_payment_id: data.late_payment_id,
+        amount,
+    };
+    let res = pool.add_log(user, &event).await;
+
+    if let Err(e) = res {
+        error!("Error adding log: {}", e);
+    }
+
+    CreateRefundResponse::Ok(Json(CreateRefundResponseObject { id, amount }))
+}
+
+pub async fn refunds<R: PaymentRepository>(pool: &R, user: &Uuid) -> RefundsResponse {
+    match pool.get_refunds(Some(user), None).await {
+        Ok(refunds) => RefundsResponse::Ok(Json(RefundsResponseObject { refunds })),
+        Err(e) => {
+            error!("Error getting refunds: {}", e);
+            RefundsResponse::InternalServerError(Json("Internal server error".into()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::test_utils::TestServices;
+
+    async fn request(
+        pool: &InMemoryRepository,
+        services: &TestServices,
+        user: &Uuid,
+        payment_id: Option<Uuid>,
+        late_payment_id: Option<Uuid>,
+    ) -> CreateRefundResponse {
+        let data = CreateRefundData {
+            payment_id,
+            late_payment_id,
+            address: services.target(),
+        };
+
+        create(pool, user, &data).await
+    }
+
+    fn refunded_amount(response: CreateRefundResponse) -> f64 {
+        match response {
+            CreateRefundResponse::Ok(Json(refund)) => refund.amount,
+            _ => panic!("refund was not created"),
+        }
+    }
+
+    #[tokio::test]
+    async fn refunds_only_the_overpayment_of_fulfilled_orders() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+
+        pool.initiate_payment(&order.id()).await.unwrap();
+        pool.add_payment_received(&order.id(), 0.0007, "tx")
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            request(&pool, &services, &user, Some(order.id()), None).await,
+            CreateRefundResponse::Conflict(_)
+        ));
+
+        pool.add_payment_received(&order.id(), 0.0002, "tx2")
+            .await
+            .unwrap();
+
+        let amount =
+            refunded_amount(request(&pool, &services, &user, Some(order.id()), None).await);
+        assert!((amount - 0.0002).abs() < 0.00000001);
+    }
+
+    #[tokio::test]
+    async fn refunds_cancelled_orders_in_full() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+
+        pool.initiate_payment(&order.id()).await.unwrap();
+        pool.add_payment_received(&order.id(), 0.0007, "tx")
+            .await
+            .unwrap();
+        pool.cancel_payment(&order.id()).await.unwrap();
+
+        let amount =
+            refunded_amount(request(&pool, &services, &user, Some(order.id()), None).await);
+        assert_eq!(amount, 0.0007);
+
+        assert!(matches!(
+            request(&pool, &services, &user, Some(order.id()), None).await,
+            CreateRefundResponse::Conflict(_)
+        ));
+        assert!(matches!(
+            request(&pool, &services, &Uuid::new_v4(), Some(order.id()), None).await,
+            CreateRefundResponse::NotFound(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn refunds_the_rest_of_orders_cancelled_after_an_overpayment_refund() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+
+        pool.initiate_payment(&order.id()).await.unwrap();
+        pool.add_payment_received(&order.id(), 0.0009, "tx")
+            .await
+            .unwrap();
+
+        let overpayment =
+            refunded_amount(request(&pool, &services, &user, Some(order.id()), None).await);
+        assert!((overpayment - 0.0002).abs() < 0.00000001);
+
+        pool.cancel_payment(&order.id()).await.unwrap();
+
+        let rest = refunded_amount(request(&pool, &services, &user, Some(order.id()), None).await);
+        assert!((rest - 0.0007).abs() < 0.00000001);
+
+        assert!(matches!(
+            request(&pool, &services, &user, Some(order.id()), None).await,
+            CreateRefundResponse::Conflict(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn moves_refunded_late_payments_out_of_pending() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+        let late_payment_id = pool
+            .add_late_payment(&user, Some(&order.id()), order.address(), "tx", 0.0005)
+            .await
+            .unwrap();
+
+        let amount =
+            refunded_amount(request(&pool, &services, &user, None, Some(late_payment_id)).await);
+        assert_eq!(amount, 0.0005);
+
+        let late_payment = pool
+            .get_late_payment(&late_payment_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(late_payment.status, LatePaymentStatus::Refund);
+
+        assert!(matches!(
+            request(&pool, &services, &user, None, Some(late_payment_id)).await,
+            CreateRefundResponse::Conflict(_)
+        ));
+    }
+}
@@ -3,7 +3,7 @@ use tracing::error;
 use uuid::Uuid;
 
 use crate::db::repositories::models::payment::Payment;
-use crate::db::{PaymentRepository, Repository};
+use crate::db::PaymentRepository;
 use crate::responses::error::ErrorResponse;
 
 #[derive(ApiResponse)]
@@ -18,7 +18,11 @@ pub enum PaymentStatusResponse {
     InternalServerError(Json<ErrorResponse>),
 }
 
-pub async fn status(pool: &Repository, user: &Uuid, payment_id: &Uuid) -> PaymentStatusResponse {
+pub async fn status<R: PaymentRepository>(
+    pool: &R,
+    user: &Uuid,
+    payment_id: &Uuid,
+) -> PaymentStatusResponse {
     let payment = pool.get_payment(payment_id).await;
 
     match payment {
@@ -36,3 +40,35 @@ pub async fn status(pool: &Repository, user: &Uuid, payment_id: &Uuid) -> Paymen
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::test_utils::TestServices;
+
+    #[tokio::test]
+    async fn returns_own_payments_only() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let user = Uuid::new_v4();
+        let order = services.order(&pool, &user, &["alice.o"]).await;
+
+        match status(&pool, &user, &order.id()).await {
+            PaymentStatusResponse::Ok(Json(payment)) => {
+                assert_eq!(payment.id, order.id());
+                assert_eq!(payment.address, order.address());
+            }
+            _ => panic!("payment was not found"),
+        }
+
+        assert!(matches!(
+            status(&pool, &Uuid::new_v4(), &order.id()).await,
+            PaymentStatusResponse::NotFound(_)
+        ));
+        assert!(matches!(
+            status(&pool, &user, &Uuid::new_v4()).await,
+            PaymentStatusResponse::NotFound(_)
+        ));
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use poem_openapi::payload::Json;
+use uuid::Uuid;
+
+use crate::bitcoin::address_provider::AddressProvider;
+use crate::bitcoin::fees::{FeeEstimator, NetworkFeeConfig};
+use crate::bitcoin::wallet::{FakeWallet, Wallet, WalletBackend};
+use crate::db::repositories::InMemoryRepository;
+use crate::endpoints::new::{
+    new, CreatePaymentData, CreatePaymentResponse, CreatePaymentResponseObject, PaymentMethod,
+};
+use crate::lightning::{Lightning, MockBackend};
+
+/// The wallet backed services the endpoints need, all sharing one fake chain, and a mock
+/// lightning node.
+pub struct TestServices {
+    pub wallet: FakeWallet,
+    pub addresses: AddressProvider,
+    pub fees: FeeEstimator,
+    pub lightning: MockBackend,
+}
+
+impl TestServices {
+    /// Services with the dynamic network fee disabled.
+    pub fn new() -> Self {
+        Self::with_fee_config(None)
+    }
+
+    /// Services charging the network fee at `fee_rate` sat/vB.
+    pub fn with_network_fee(fee_rate: f64) -> Self {
+        let services = Self::with_fee_config(Some(NetworkFeeConfig {
+            conf_target: 6,
+            cache_ttl: Duration::from_secs(60),
+            fallback_fee_rate: fee_rate,
+            cap: 1.0,
+        }));
+        services.wallet.set_fee_rate(Some(fee_rate));
+
+        services
+    }
+
+    fn with_fee_config(config: Option<NetworkFeeConfig>) -> Self {
+        let wallet = FakeWallet::default();
+
+        Self {
+            addresses: AddressProvider::wallet_generated(Wallet::Fake(wallet.clone())),
+            fees: FeeEstimator::new(Wallet::Fake(wallet.clone()), config),
+            wallet,
+            lightning: MockBackend::default(),
+        }
+    }
+
+    /// An address on the configured network to point domains at.
+    pub fn target(&self) -> String {
+        self.wallet.new_address().unwrap().to_string()
+    }
+
+    /// Places an on-chain order for `domains`, panicking when it is not accepted.
+    pub async fn order(
+        &self,
+        pool: &InMemoryRepository,
+        user: &Uuid,
+        domains: &[&str],
+    ) -> CreatePaymentResponseObject {
+        self.place_order(pool, user, domains, None).await
+    }
+
+    /// Places an order for `domains` with a lightning invoice, panicking when it is not accepted.
+    pub async fn lightning_order(
+        &self,
+        pool: &InMemoryRepository,
+        user: &Uuid,
+        domains: &[&str],
+    ) -> CreatePaymentResponseObject {
+        self.place_order(pool, user, domains, Some(PaymentMethod::Lightning))
+            .await
+    }
+
+    async fn place_order(
+        &self,
+        pool: &InMemoryRepository,
+        user: &Uuid,
+        domains: &[&str],
+        payment_method: Option<PaymentMethod>,
+    ) -> CreatePaymentResponseObject {
+        let target = self.target();
+        let data = CreatePaymentData::new(
+            &domains
+                .iter()
+                .map(|domain| (*domain, target.as_str()))
+                .collect::<Vec<_>>(),
+            payment_method,
+        );
+        let lightning = Lightning::Mock(self.lightning.clone());
+
+        match new(
+            pool,
+            &self.addresses,
+            &self.fees,
+            Some(&lightning),
+            user,
+            None,
+            &data,
+        )
+        .await
+        {
+            CreatePaymentResponse::Ok(Json(response)) => response,
+            _ => panic!("order for {:?} was not accepted", domains),
+        }
+    }
+}
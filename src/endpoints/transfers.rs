@@ -0,0 +1,742 @@
+use poem_openapi::Object;
+use poem_openapi::{payload::Json, ApiResponse};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::bitcoin::address_provider::AddressProvider;
+use crate::bitcoin::fees::FeeEstimator;
+use crate::db::log::LogEvent;
+use crate::db::repositories::models::transfer::{DomainTransfer, TransferStatus};
+use crate::db::traits::repository::OwnedDomain;
+use crate::db::traits::UnitOfWork;
+use crate::db::PaymentRepository;
+use crate::endpoints::records::{prepare_update_order, target_update, UpdateRecordsResponseObject};
+use crate::inscription::record::DomainUpdateInscription;
+use crate::inscription::{sign_domain_update, verify_domain_inscription};
+use crate::responses::error::ErrorResponse;
+use crate::utils::domain_name;
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct CreateTransferData {
+    /// Xiler account receiving the domain, it has to accept the transfer.
+    to_account_id: Option<Uuid>,
+    /// External address receiving the domain, always inscribed and accepted right away. The
+    /// transfer gets cancelled when its order expires unpaid.
+    to_address: Option<String>,
+    /// Inscribe a `TARGET` update once the transfer is accepted.
+    inscribe: Option<bool>,
+}
+
+#[derive(Debug, Object, Clone, Eq, PartialEq)]
+pub struct AcceptTransferData {
+    /// Target address of the transfer inscription, required when the transfer is inscribed.
+    target: Option<String>,
+}
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct TransferResponseObject {
+    transfer: DomainTransfer,
+    /// The order paying for the transfer inscription.
+    order: Option<UpdateRecordsResponseObject>,
+}
+
+#[derive(ApiResponse)]
+pub enum TransferResponse {
+    #[oai(status = 200)]
+    Ok(Json<Box<TransferResponseObject>>),
+
+    #[oai(status = 400)]
+    BadRequest(Json<ErrorResponse>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<ErrorResponse>),
+
+    #[oai(status = 404)]
+    NotFound(Json<ErrorResponse>),
+
+    #[oai(status = 409)]
+    Conflict(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+#[derive(Debug, Object, Clone, PartialEq)]
+pub struct TransfersResponseObject {
+    transfers: Vec<DomainTransfer>,
+}
+
+#[derive(ApiResponse)]
+pub enum TransfersResponse {
+    #[oai(status = 200)]
+    Ok(Json<TransfersResponseObject>),
+
+    #[oai(status = 401)]
+    Unauthorized(Json<ErrorResponse>),
+
+    #[oai(status = 500)]
+    InternalServerError(Json<ErrorResponse>),
+}
+
+async fn log<R: PaymentRepository>(pool: &R, accounts: &[&Uuid], event: &LogEvent) {
+    for account in accounts {
+        if let Err(e) = pool.add_log(account, event).await {
+            error!("Failed to create transfer log for {}: {}", account, e);
+        }
+    }
+}
+
+async fn get_transfer<R: PaymentRepository>(
+    pool: &R,
+    id: &Uuid,
+) -> Result<DomainTransfer, TransferResponse> {
+    match pool.get_transfer(id).await {
+        Ok(Some(transfer)) => Ok(transfer),
+        Ok(None) => Err(TransferResponse::NotFound(Json("Not found".into()))),
+        Err(e) => {
+            error!("Error getting transfer: {}", e);
+            Err(TransferResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            )))
+        }
+    }
+}
+
+/// The `TARGET` update moving `owned` to `address`, signed with the stored key.
+fn transfer_update(
+    owned: &OwnedDomain,
+    address: &str,
+) -> Result<DomainUpdateInscription, TransferResponse> {
+    let target = target_update(address)
+        .map_err(|e| TransferResponse::BadRequest(Json(e.as_str().into())))?;
+
+    let domain_inscription = verify_domain_inscription(&owned.inscription).map_err(|e| {
+        error!("Stored inscription of {} does not verify: {}", owned.domain, e);
+        TransferResponse::BadRequest(Json(
+            "This domain was inscribed without a signing key and can not be inscribed as transferred"
+                .into(),
+        ))
+    })?;
+
+    let mut update = DomainUpdateInscription {
+        name: owned.domain.clone(),
+        timestamp: chrono::Utc::now().timestamp_millis() / 1000,
+        updates: vec![target],
+        algorithm: domain_inscription.algorithm,
+        signature: Vec::new(),
+    };
+
+    sign_domain_update(&mut update, &owned.private_key).map_err(|e| {
+        error!("Failed to sign transfer of {}: {}", owned.domain, e);
+        TransferResponse::InternalServerError(Json("Internal server error".into()))
+    })?;
+
+    Ok(update)
+}
+
+async fn get_transferable_domain<R: PaymentRepository>(
+    pool: &R,
+    user: &Uuid,
+    domain: &str,
+) -> Result<OwnedDomain, TransferResponse> {
+    match pool.get_owned_domain(user, domain).await {
+        Ok(Some(owned)) => Ok(owned),
+        Ok(None) => Err(TransferResponse::NotFound(Json(
+            "You do not own this domain or it does not exist!".into(),
+        ))),
+        Err(e) => {
+            error!("Failed to get owned domain: {}", e);
+            Err(TransferResponse::InternalServerError(Json(
+                "Internal server error".into(),
+            )))
+        }
+    }
+}
+
+/// Accepts `transfer` on behalf of `user`. When the transfer is inscribed to `address`, the
+/// order paying for the inscription is created in the same transaction, so an accepted transfer
+/// always has its order.
+async fn accept_transfer<R: PaymentRepository>(
+    pool: &R,
+    addresses: &AddressProvider,
+    fees: &FeeEstimator,
+    user: &Uuid,
+    transfer: &mut DomainTransfer,
+    inscription: Option<(&OwnedDomain, &str)>,
+) -> Result<Option<UpdateRecordsResponseObject>, TransferResponse> {
+    let update = match inscription {
+        Some((owned, address)) => Some((transfer_update(owned, address)?, address)),
+        None => None,
+    };
+
+    let order = match &update {
+        Some((update, address)) => Some(
+            prepare_update_order(pool, addresses, fees, address, update)
+                .await
+                .map_err(|e| TransferResponse::InternalServerError(Json(e.as_str().into())))?,
+        ),
+        None => None,
+    };
+
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to accept transfer {}: {}", transfer.id, e);
+        TransferResponse::InternalServerError(Json("Internal server error".into()))
+    };
+
+    let mut uow = pool.begin().await.map_err(internal_error)?;
+
+    if !uow
+        .accept_transfer(&transfer.id)
+        .await
+        .map_err(internal_error)?
+    {
+        uow.rollback().await.map_err(internal_error)?;
+        return Err(TransferResponse::Conflict(Json(
+            "The transfer is no longer pending".into(),
+        )));
+    }
+
+    let order = match order {
+        Some(order) => {
+            let order = order.create(&mut uow, user).await.map_err(internal_error)?;
+            uow.set_transfer_payment(&transfer.id, &order.id)
+                .await
+                .map_err(internal_error)?;
+            Some(order)
+        }
+        None => None,
+    };
+
+    let event = LogEvent::DomainTransferAccepted {
+        transfer_id: transfer.id,
+        domain: transfer.domain.clone(),
+        from_account_id: transfer.from_account_id,
+        accepted_by: *user,
+    };
+    let mut accounts = vec![&transfer.from_account_id];
+    if *user != transfer.from_account_id {
+        accounts.push(user);
+    }
+    for account in accounts {
+        uow.add_log(account, &event).await.map_err(internal_error)?;
+    }
+
+    uow.commit().await.map_err(internal_error)?;
+
+    transfer.status = TransferStatus::Accepted;
+    transfer.payment_id = order.as_ref().map(|order| order.id);
+
+    Ok(order)
+}
+
+pub async fn create<R: PaymentRepository>(
+    pool: &R,
+    addresses: &AddressProvider,
+    fees: &FeeEstimator,
+    user: &Uuid,
+    domain: &str,
+    data: &CreateTransferData,
+) -> TransferResponse {
+    match (&data.to_account_id, &data.to_address) {
+        (Some(to_account_id), None) if to_account_id == user => {
+            return TransferResponse::BadRequest(Json(
+                "You can not transfer a domain to yourself".into(),
+            ))
+        }
+        (Some(_), None) | (None, Some(_)) => {}
+        _ => {
+            return TransferResponse::BadRequest(Json(
+                "Provide either an account or an address to transfer to".into(),
+            ))
+        }
+    }
+
+    let domain = match domain_name::normalize(domain) {
+        Ok(domain) => domain.ascii,
+        Err(e) => return TransferResponse::BadRequest(Json(e.to_string().as_str().into())),
+    };
+
+    let owned = match get_transferable_domain(pool, user, &domain).await {
+        Ok(owned) => owned,
+        Err(e) => return e,
+    };
+
+    let inscribe = data.to_address.is_some() || data.inscribe.unwrap_or(false);
+
+    if inscribe && !owned.completed {
+        return TransferResponse::BadRequest(Json("The domain has not been inscribed yet".into()));
+    }
+
+    // Check the address before anything is stored.
+    if let Some(address) = &data.to_address {
+        if let Err(e) = transfer_update(&owned, address) {
+            return e;
+        }
+    }
+
+    let mut transfer = match pool
+        .create_transfer(
+            &owned.domain,
+            user,
+            data.to_account_id.as_ref(),
+            data.to_address.as_deref(),
+            inscribe,
+        )
+        .await
+    {
+        Ok(Some(transfer)) => transfer,
+        Ok(None) => {
+            return TransferResponse::Conflict(Json(
+                "There already is a pending transfer for this domain".into(),
+            ))
+        }
+        Err(e) => {
+            error!("Failed to create transfer: {}", e);
+            return TransferResponse::InternalServerError(Json("Internal server error".into()));
+        }
+    };
+
+    let event = LogEvent::DomainTransferInitiated {
+        transfer_id: transfer.id,
+        domain: transfer.domain.clone(),
+        from_account_id: *user,
+        to_account_id: data.to_account_id,
+        to_address: data.to_address.clone(),
+    };
+    let mut accounts = vec![user];
+    accounts.extend(data.to_account_id.as_ref());
+    log(pool, &accounts, &event).await;
+
+    let address = match &data.to_address {
+        Some(address) => address,
+        None => {
+            return TransferResponse::Ok(Json(Box::new(TransferResponseObject {
+                transfer,
+                order: None,
+            })))
+        }
+    };
+
+    // Nobody has to accept a transfer leaving Xiler, the sender pays for the inscription.
+    let inscription = Some((&owned, address.as_str()));
+    match accept_transfer(pool, addresses, fees, user, &mut transfer, inscription).await {
+        Ok(order) => {
+            TransferResponse::Ok(Json(Box::new(TransferResponseObject { transfer, order })))
+        }
+        Err(e) => {
+            // Nobody else can act on the transfer, free the domain for another attempt.
+            match pool
+                .close_transfer(&transfer.id, TransferStatus::Cancelled)
+                .await
+            {
+                Ok(true) => {
+                    let event = LogEvent::DomainTransferCancelled {
+                        transfer_id: transfer.id,
+                        domain: transfer.domain.clone(),
+                        closed_by: *user,
+                    };
+                    log(pool, &[user], &event).await;
+                }
+                Ok(false) => {}
+                Err(e) => error!("Failed to cancel transfer {}: {}", transfer.id, e),
+            }
+
+            e
+        }
+    }
+}
+
+pub async fn transfers<R: PaymentRepository>(pool: &R, user: &Uuid) -> TransfersResponse {
+    match pool.get_transfers(user).await {
+        Ok(transfers) => TransfersResponse::Ok(Json(TransfersResponseObject { transfers })),
+        Err(e) => {
+            error!("Error getting transfers: {}", e);
+            TransfersResponse::InternalServerError(Json("Internal server error".into()))
+        }
+    }
+}
+
+pub async fn accept<R: PaymentRepository>(
+    pool: &R,
+    addresses: &AddressProvider,
+    fees: &FeeEstimator,
+    user: &Uuid,
+    id: &Uuid,
+    data: &AcceptTransferData,
+) -> TransferResponse {
+    let mut transfer = match get_transfer(pool, id).await {
+        Ok(transfer) if transfer.to_account_id.as_ref() == Some(user) => transfer,
+        Ok(_) => return TransferResponse::NotFound(Json("Not found".into())),
+        Err(e) => return e,
+    };
+
+    if transfer.status != TransferStatus::Pending {
+        return TransferResponse::Conflict(Json("The transfer is no longer pending".into()));
+    }
+
+    // The key has to be read before it changes hands.
+    let owned = match get_transferable_domain(pool, &transfer.from_account_id, &transfer.domain)
+        .await
+    {
+        Ok(owned) => owned,
+        Err(TransferResponse::NotFound(_)) => {
+            return TransferResponse::Conflict(Json("The sender no longer owns this domain".into()))
+        }
+        Err(e) => return e,
+    };
+
+    let target = match (transfer.inscribe, &data.target) {
+        (true, Some(target)) => match transfer_update(&owned, target) {
+            Ok(_) => Some(target),
+            Err(e) => return e,
+        },
+        (true, None) => {
+            return TransferResponse::BadRequest(Json(
+                "This transfer is inscribed, provide a target address".into(),
+            ))
+        }
+        (false, _) => None,
+    };
+
+    let inscription = target.map(|target| (&owned, target.as_str()));
+    match accept_transfer(pool, addresses, fees, user, &mut transfer, inscription).await {
+        Ok(order) => {
+            TransferResponse::Ok(Json(Box::new(TransferResponseObject { transfer, order })))
+        }
+        Err(e) => e,
+    }
+}
+
+/// Rejects (by the recipient) or cancels (by the sender) a pending transfer.
+pub async fn close<R: PaymentRepository>(
+    pool: &R,
+    user: &Uuid,
+    id: &Uuid,
+    status: TransferStatus,
+) -> TransferResponse {
+    let mut transfer = match get_transfer(pool, id).await {
+        Ok(transfer) => transfer,
+        Err(e) => return e,
+    };
+
+    let allowed = match status {
+        TransferStatus::Rejected => transfer.to_account_id.as_ref() == Some(user),
+        TransferStatus::Cancelled => transfer.from_account_id == *user,
+        _ => panic!("Transfers can only be closed as rejected or cancelled"),
+    };
+
+    if !allowed {
+        return TransferResponse::NotFound(Json("Not found".into()));
+    }
+
+    match pool.close_transfer(&transfer.id, status).await {
+        Ok(true) => transfer.status = status,
+        Ok(false) => {
+            return TransferResponse::Conflict(Json("The transfer is no longer pending".into()))
+        }
+        Err(e) => {
+            error!("Failed to close transfer {}: {}", transfer.id, e);
+            return TransferResponse::InternalServerError(Json("Internal server error".into()));
+        }
+    }
+
+    let transfer_id = transfer.id;
+    let domain = transfer.domain.clone();
+    let closed_by = *user;
+    let event = match status {
+        TransferStatus::Rejected => LogEvent::DomainTransferRejected {
+            transfer_id,
+            domain,
+            closed_by,
+        },
+        _ => LogEvent::DomainTransferCancelled {
+            transfer_id,
+            domain,
+            closed_by,
+        },
+    };
+    let mut accounts = vec![&transfer.from_account_id];
+    accounts.extend(transfer.to_account_id.as_ref());
+    log(pool, &accounts, &event).await;
+
+    TransferResponse::Ok(Json(Box::new(TransferResponseObject {
+        transfer,
+        order: None,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::repositories::InMemoryRepository;
+    use crate::endpoints::test_utils::TestServices;
+
+    async fn inscribed_domain(pool: &InMemoryRepository, services: &TestServices, user: &Uuid) {
+        let order = services.order(pool, user, &["alice.o"]).await;
+        pool.initiate_payment(&order.id()).await.unwrap();
+        pool.complete_payment(&order.id()).await.unwrap();
+        pool.set_reveal_tx(&order.id(), "reveal");
+    }
+
+    fn transfer_of(response: TransferResponse) -> TransferResponseObject {
+        match response {
+            TransferResponse::Ok(Json(response)) => *response,
+            _ => panic!("the transfer failed"),
+        }
+    }
+
+    #[tokio::test]
+    async fn accepts_inscribed_transfers_with_their_order() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let (sender, recipient) = (Uuid::new_v4(), Uuid::new_v4());
+        inscribed_domain(&pool, &services, &sender).await;
+
+        let data = CreateTransferData {
+            to_account_id: Some(recipient),
+            to_address: None,
+            inscribe: Some(true),
+        };
+        let created = transfer_of(
+            create(
+                &pool,
+                &services.addresses,
+                &services.fees,
+                &sender,
+                "alice.o",
+                &data,
+            )
+            .await,
+        );
+        assert_eq!(created.order, None);
+
+        let data = AcceptTransferData {
+            target: Some(services.target()),
+        };
+        let accepted = transfer_of(
+            accept(
+                &pool,
+                &services.addresses,
+                &services.fees,
+                &recipient,
+                &created.transfer.id,
+                &data,
+            )
+            .await,
+        );
+        let order = accepted.order.unwrap();
+
+        let stored = pool
+            .get_transfer(&created.transfer.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.status, TransferStatus::Accepted);
+        assert_eq!(stored.payment_id, Some(order.id));
+        assert_eq!(
+            pool.get_payment(&order.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .account_id,
+            recipient
+        );
+        assert!(pool
+            .get_owned_domain(&recipient, "alice.o")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(pool
+            .get_owned_domain(&sender, "alice.o")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn creates_external_transfers_with_their_order() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let sender = Uuid::new_v4();
+        inscribed_domain(&pool, &services, &sender).await;
+
+        let data = CreateTransferData {
+            to_account_id: None,
+            to_address: Some(services.target()),
+            inscribe: None,
+        };
+        let created = transfer_of(
+            create(
+                &pool,
+                &services.addresses,
+                &services.fees,
+                &sender,
+                "alice.o",
+                &data,
+            )
+            .await,
+        );
+        let order = created.order.unwrap();
+
+        let stored = pool
+            .get_transfer(&created.transfer.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.status, TransferStatus::Accepted);
+        assert_eq!(stored.payment_id, Some(order.id));
+        assert_eq!(
+            pool.get_payment(&order.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .account_id,
+            sender
+        );
+    }
+
+    #[tokio::test]
+    async fn retires_the_sender_key_once_the_external_transfer_is_inscribed() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let sender = Uuid::new_v4();
+        inscribed_domain(&pool, &services, &sender).await;
+
+        let data = CreateTransferData {
+            to_account_id: None,
+            to_address: Some(services.target()),
+            inscribe: None,
+        };
+        let created = transfer_of(
+            create(
+                &pool,
+                &services.addresses,
+                &services.fees,
+                &sender,
+                "alice.o",
+                &data,
+            )
+            .await,
+        );
+        let order = created.order.unwrap();
+
+        pool.initiate_payment(&order.id).await.unwrap();
+        pool.complete_payment(&order.id).await.unwrap();
+
+        assert!(pool.retire_transferred_keys().await.unwrap().is_empty());
+        assert!(pool
+            .get_owned_domain(&sender, "alice.o")
+            .await
+            .unwrap()
+            .is_some());
+
+        pool.set_reveal_tx(&order.id, "transfer");
+
+        assert_eq!(
+            pool.retire_transferred_keys().await.unwrap(),
+            vec![created.transfer.id]
+        );
+        assert!(pool.retire_transferred_keys().await.unwrap().is_empty());
+        assert!(pool
+            .get_owned_domain(&sender, "alice.o")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            pool.get_private_key(&sender, "alice.o").await.unwrap(),
+            None
+        );
+
+        // The domain stays registered, the sender can not order it again.
+        assert!(pool
+            .get_domain_registration("alice.o")
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            pool.get_already_owned_domains(&sender, &["alice.o".to_string()])
+                .await
+                .unwrap(),
+            vec!["alice.o".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn allows_one_pending_transfer_per_domain() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let sender = Uuid::new_v4();
+        inscribed_domain(&pool, &services, &sender).await;
+
+        let data = CreateTransferData {
+            to_account_id: Some(Uuid::new_v4()),
+            to_address: None,
+            inscribe: None,
+        };
+        let create = || {
+            create(
+                &pool,
+                &services.addresses,
+                &services.fees,
+                &sender,
+                "alice.o",
+                &data,
+            )
+        };
+
+        let first = transfer_of(create().await);
+        assert!(matches!(create().await, TransferResponse::Conflict(_)));
+
+        close(
+            &pool,
+            &sender,
+            &first.transfer.id,
+            TransferStatus::Cancelled,
+        )
+        .await;
+        transfer_of(create().await);
+    }
+
+    #[tokio::test]
+    async fn cancels_external_transfers_whose_order_expired() {
+        let pool = InMemoryRepository::default();
+        let services = TestServices::new();
+        let sender = Uuid::new_v4();
+        inscribed_domain(&pool, &services, &sender).await;
+
+        let data = CreateTransferData {
+            to_account_id: None,
+            to_address: Some(services.target()),
+            inscribe: None,
+        };
+        let created = transfer_of(
+            create(
+                &pool,
+                &services.addresses,
+                &services.fees,
+                &sender,
+                "alice.o",
+                &data,
+            )
+            .await,
+        );
+        let order = created.order.unwrap();
+
+        assert!(pool.cancel_unpaid_transfers().await.unwrap().is_empty());
+
+        pool.cancel_payment(&order.id).await.unwrap();
+
+        let cancelled = pool.cancel_unpaid_transfers().await.unwrap();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].id, created.transfer.id);
+        assert_eq!(cancelled[0].status, TransferStatus::Cancelled);
+        assert!(pool.cancel_unpaid_transfers().await.unwrap().is_empty());
+
+        // The sender still owns the domain.
+        assert!(pool
+            .get_owned_domain(&sender, "alice.o")
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
@@ -0,0 +1,242 @@
+use std::fmt;
+
+use pqcrypto_dilithium::dilithium5;
+use pqcrypto_traits::sign::{DetachedSignature, PublicKey, SecretKey};
+
+use self::record::{DomainInscription, DomainUpdateInscription, ParseError, SignatureAlgorithm};
+
+pub mod record;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationError {
+    Malformed(ParseError),
+    InvalidPublicKey,
+    InvalidSecretKey,
+    InvalidSignature,
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationError::Malformed(e) => write!(f, "malformed inscription: {}", e),
+            VerificationError::InvalidPublicKey => write!(f, "invalid public key"),
+            VerificationError::InvalidSecretKey => write!(f, "invalid secret key"),
+            VerificationError::InvalidSignature => write!(f, "invalid signature"),
+        }
+    }
+}
+
+impl From<ParseError> for VerificationError {
+    fn from(e: ParseError) -> Self {
+        VerificationError::Malformed(e)
+    }
+}
+
+/// Builds the inscription for `domain` with a fresh dilithium5 key pair and signs it.
+/// Returns the inscription and the hex encoded secret key.
+pub fn generate_domain_inscription(domain: &str) -> (String, String) {
+    let (public_key, secret_key) = pqcrypto_dilithium::dilithium5_keypair();
+
+    let mut inscription = DomainInscription {
+        name: domain.to_string(),
+        timestamp: chrono::Utc::now().timestamp_millis() / 1000,
+        algorithm: SignatureAlgorithm::Dilithium5,
+        public_key: public_key.as_bytes().to_vec(),
+        signature: Vec::new(),
+    };
+
+    inscription.signature = dilithium5::detached_sign(&inscription.signing_message(), &secret_key)
+        .as_bytes()
+        .to_vec();
+
+    (inscription.to_string(), hex::encode(secret_key.as_bytes()))
+}
+
+/// Checks that the signature of a domain inscription was made by the key in its
+/// `DOMAIN-VALIDITY` record.
+pub fn verify_domain_inscription(
+    inscription: &str,
+) -> Result<DomainInscription, VerificationError> {
+    let inscription = inscription.parse::<DomainInscription>()?;
+
+    match inscription.algorithm {
+        SignatureAlgorithm::Dilithium5 => {
+            let public_key = dilithium5::PublicKey::from_bytes(&inscription.public_key)
+                .map_err(|_| VerificationError::InvalidPublicKey)?;
+            let signature = dilithium5::DetachedSignature::from_bytes(&inscription.signature)
+                .map_err(|_| VerificationError::InvalidSignature)?;
+
+            dilithium5::verify_detached_signature(
+                &signature,
+                &inscription.signing_message(),
+                &public_key,
+            )
+            .map_err(|_| VerificationError::InvalidSignature)?;
+        }
+    }
+
+    Ok(inscription)
+}
+
+/// Signs `update` with the hex encoded secret key stored for its domain.
+pub fn sign_domain_update(
+    update: &mut DomainUpdateInscription,
+    secret_key: &str,
+) -> Result<(), VerificationError> {
+    match update.algorithm {
+        SignatureAlgorithm::Dilithium5 => {
+            let secret_key = hex::decode(secret_key)
+                .ok()
+                .and_then(|bytes| dilithium5::SecretKey::from_bytes(&bytes).ok())
+                .ok_or(VerificationError::InvalidSecretKey)?;
+
+            update.signature = dilithium5::detached_sign(&update.signing_message(), &secret_key)
+                .as_bytes()
+                .to_vec();
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that `update` was signed by the key of the domain inscription it updates.
+pub fn verify_domain_update(
+    update: &DomainUpdateInscription,
+    domain: &DomainInscription,
+) -> Result<(), VerificationError> {
+    if update.name != domain.name {
+        return Err(VerificationError::Malformed(ParseError::DomainMismatch(
+            domain.name.clone(),
+            update.name.clone(),
+        )));
+    }
+
+    if update.algorithm != domain.algorithm {
+        return Err(VerificationError::Malformed(
+            ParseError::UnsupportedAlgorithm(update.algorithm.to_string()),
+        ));
+    }
+
+    match domain.algorithm {
+        SignatureAlgorithm::Dilithium5 => {
+            let public_key = dilithium5::PublicKey::from_bytes(&domain.public_key)
+                .map_err(|_| VerificationError::InvalidPublicKey)?;
+            let signature = dilithium5::DetachedSignature::from_bytes(&update.signature)
+                .map_err(|_| VerificationError::InvalidSignature)?;
+
+            dilithium5::verify_detached_signature(
+                &signature,
+                &update.signing_message(),
+                &public_key,
+            )
+            .map_err(|_| VerificationError::InvalidSignature)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inscription::record::RecordUpdate;
+
+    #[test]
+    fn generated_inscriptions_verify() {
+        let (inscription, secret_key) = generate_domain_inscription("alice.o");
+
+        let parsed = inscription.parse::<DomainInscription>().unwrap();
+        assert_eq!(parsed.to_string(), inscription);
+
+        let verified = verify_domain_inscription(&inscription).unwrap();
+        assert_eq!(verified, parsed);
+        assert_eq!(verified.name, "alice.o");
+        assert_eq!(
+            hex::decode(secret_key).unwrap().len(),
+            dilithium5::secret_key_bytes()
+        );
+    }
+
+    #[test]
+    fn rejects_a_tampered_signature() {
+        let (inscription, _) = generate_domain_inscription("alice.o");
+        let mut parsed = inscription.parse::<DomainInscription>().unwrap();
+        parsed.signature[0] ^= 1;
+
+        assert_eq!(
+            verify_domain_inscription(&parsed.to_string()),
+            Err(VerificationError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn rejects_a_tampered_domain() {
+        let (inscription, _) = generate_domain_inscription("alice.o");
+        let mut parsed = inscription.parse::<DomainInscription>().unwrap();
+        parsed.timestamp += 1;
+
+        assert_eq!(
+            verify_domain_inscription(&parsed.to_string()),
+            Err(VerificationError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn rejects_a_signature_by_another_key() {
+        let (inscription, _) = generate_domain_inscription("alice.o");
+        let (other, _) = generate_domain_inscription("alice.o");
+        let mut parsed = inscription.parse::<DomainInscription>().unwrap();
+        parsed.public_key = other.parse::<DomainInscription>().unwrap().public_key;
+
+        assert_eq!(
+            verify_domain_inscription(&parsed.to_string()),
+            Err(VerificationError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_inscriptions() {
+        let (inscription, _) = generate_domain_inscription("alice.o");
+        let unsigned = inscription.rsplit_once('\n').unwrap().0;
+
+        assert_eq!(
+            verify_domain_inscription(&format!("{}\nnull null", unsigned)),
+            Err(VerificationError::Malformed(
+                ParseError::UnsupportedAlgorithm("null".to_string())
+            ))
+        );
+        assert_eq!(
+            verify_domain_inscription(unsigned),
+            Err(VerificationError::Malformed(ParseError::LineCount(2)))
+        );
+    }
+
+    #[test]
+    fn signed_updates_verify_against_the_domain_key() {
+        let (inscription, secret_key) = generate_domain_inscription("alice.o");
+        let domain = verify_domain_inscription(&inscription).unwrap();
+
+        let mut update = DomainUpdateInscription {
+            name: domain.name.clone(),
+            timestamp: domain.timestamp + 1,
+            updates: vec![RecordUpdate::Text {
+                key: "email".to_string(),
+                value: "owner@example.com".to_string(),
+            }],
+            algorithm: domain.algorithm,
+            signature: Vec::new(),
+        };
+        sign_domain_update(&mut update, &secret_key).unwrap();
+
+        let parsed = update
+            .to_string()
+            .parse::<DomainUpdateInscription>()
+            .unwrap();
+        assert_eq!(verify_domain_update(&parsed, &domain), Ok(()));
+
+        let (_, other_key) = generate_domain_inscription("alice.o");
+        sign_domain_update(&mut update, &other_key).unwrap();
+        assert_eq!(
+            verify_domain_update(&update, &domain),
+            Err(VerificationError::InvalidSignature)
+        );
+    }
+}
@@ -0,0 +1,705 @@
+use std::{fmt, str::FromStr};
+
+use crate::utils::domain_name;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureAlgorithm {
+    Dilithium5,
+}
+
+impl FromStr for SignatureAlgorithm {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dilithium5" => Ok(SignatureAlgorithm::Dilithium5),
+            _ => Err(ParseError::UnsupportedAlgorithm(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SignatureAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureAlgorithm::Dilithium5 => write!(f, "dilithium5"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The inscription does not consist of the expected amount of lines.
+    LineCount(usize),
+    EmptyRecord,
+    UnknownRecord(String),
+    /// The record has the wrong amount of fields, `(record, expected, actual)`.
+    FieldCount(&'static str, usize, usize),
+    InvalidDomain(String),
+    InvalidTimestamp(String),
+    InvalidField(&'static str),
+    InvalidHex(&'static str),
+    /// A `DOMAIN-UPDATE` inscription without any record updates.
+    NoUpdates,
+    UnsupportedAlgorithm(String),
+    /// A record showed up where another kind was expected, `(expected, line)`.
+    UnexpectedRecord(&'static str, usize),
+    DomainMismatch(String, String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::LineCount(count) => write!(f, "unexpected amount of lines: {}", count),
+            ParseError::EmptyRecord => write!(f, "empty record"),
+            ParseError::UnknownRecord(record) => write!(f, "unknown record: {}", record),
+            ParseError::FieldCount(record, expected, actual) => write!(
+                f,
+                "{} record takes {} fields, got {}",
+                record, expected, actual
+            ),
+            ParseError::InvalidDomain(domain) => write!(f, "invalid domain: {}", domain),
+            ParseError::InvalidTimestamp(timestamp) => {
+                write!(f, "invalid timestamp: {}", timestamp)
+            }
+            ParseError::InvalidField(field) => write!(f, "invalid {}", field),
+            ParseError::InvalidHex(field) => write!(f, "{} is not lowercase hex", field),
+            ParseError::NoUpdates => write!(f, "update inscription without records"),
+            ParseError::UnsupportedAlgorithm(algorithm) => {
+                write!(f, "unsupported signature algorithm: {}", algorithm)
+            }
+            ParseError::UnexpectedRecord(expected, line) => {
+                write!(f, "expected {} record on line {}", expected, line)
+            }
+            ParseError::DomainMismatch(domain, validity_domain) => write!(
+                f,
+                "validity record is for {} while the inscription is for {}",
+                validity_domain, domain
+            ),
+        }
+    }
+}
+
+/// A change to the records of a domain, carried by a `DOMAIN-UPDATE` inscription.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordUpdate {
+    /// `TARGET <address>`
+    Target { address: String },
+    /// `TXT <key> <value>`, the value runs until the end of the line and may contain spaces.
+    Text { key: String, value: String },
+    /// `CONTENT <inscription id>`
+    Content { inscription_id: String },
+}
+
+impl fmt::Display for RecordUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordUpdate::Target { address } => write!(f, "TARGET {}", address),
+            RecordUpdate::Text { key, value } => write!(f, "TXT {} {}", key, value),
+            RecordUpdate::Content { inscription_id } => write!(f, "CONTENT {}", inscription_id),
+        }
+    }
+}
+
+/// A single line of a Xiler inscription.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Record {
+    /// `DOMAIN <name> <epoch>`
+    Domain {
+        name: String,
+        timestamp: i64,
+    },
+    /// `DOMAIN-VALIDITY <name> <algorithm> <public key>`
+    DomainValidity {
+        name: String,
+        algorithm: SignatureAlgorithm,
+        public_key: Vec<u8>,
+    },
+    /// `DOMAIN-UPDATE <name> <epoch>`
+    DomainUpdate {
+        name: String,
+        timestamp: i64,
+    },
+    Update(RecordUpdate),
+    /// `<algorithm> <signature>`
+    Signature {
+        algorithm: SignatureAlgorithm,
+        signature: Vec<u8>,
+    },
+}
+
+impl Record {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Record::Domain { .. } => "DOMAIN",
+            Record::DomainValidity { .. } => "DOMAIN-VALIDITY",
+            Record::DomainUpdate { .. } => "DOMAIN-UPDATE",
+            Record::Update(RecordUpdate::Target { .. }) => "TARGET",
+            Record::Update(RecordUpdate::Text { .. }) => "TXT",
+            Record::Update(RecordUpdate::Content { .. }) => "CONTENT",
+            Record::Signature { .. } => "signature",
+        }
+    }
+}
+
+/// Inscriptions carry the normalized form of a domain: a lowercase ASCII or punycode label
+/// followed by the `.o` suffix. Anything that normalizes differently is rejected, so every name
+/// on chain maps to exactly one registered domain.
+fn parse_domain(name: &str) -> Result<String, ParseError> {
+    match domain_name::normalize(name) {
+        Ok(domain) if domain.ascii == name => Ok(name.to_string()),
+        _ => Err(ParseError::InvalidDomain(name.to_string())),
+    }
+}
+
+/// Only the canonical encoding of a timestamp is accepted, so records survive a
+/// serialize/parse round trip byte for byte.
+fn parse_timestamp(timestamp: &str) -> Result<i64, ParseError> {
+    match timestamp.parse::<i64>() {
+        Ok(value) if value >= 0 && value.to_string() == timestamp => Ok(value),
+        _ => Err(ParseError::InvalidTimestamp(timestamp.to_string())),
+    }
+}
+
+fn parse_field(value: &str, field: &'static str) -> Result<String, ParseError> {
+    if value.is_empty() || value.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(ParseError::InvalidField(field));
+    }
+
+    Ok(value.to_string())
+}
+
+fn parse_hex(value: &str, field: &'static str) -> Result<Vec<u8>, ParseError> {
+    if value.is_empty() || value.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err(ParseError::InvalidHex(field));
+    }
+
+    hex::decode(value).map_err(|_| ParseError::InvalidHex(field))
+}
+
+impl FromStr for Record {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(text) = s.strip_prefix("TXT ") {
+            return match text.split_once(' ') {
+                Some((key, value))
+                    if !value.is_empty() && !value.chars().any(|c| c.is_control()) =>
+                {
+                    Ok(Record::Update(RecordUpdate::Text {
+                        key: parse_field(key, "TXT key")?,
+                        value: value.to_string(),
+                    }))
+                }
+                Some(_) => Err(ParseError::InvalidField("TXT value")),
+                None => Err(ParseError::FieldCount("TXT", 2, 1)),
+            };
+        }
+
+        let fields = s.split(' ').collect::<Vec<_>>();
+
+        match fields[..] {
+            [""] => Err(ParseError::EmptyRecord),
+            ["DOMAIN", ..] => match fields[1..] {
+                [name, timestamp] => Ok(Record::Domain {
+                    name: parse_domain(name)?,
+                    timestamp: parse_timestamp(timestamp)?,
+                }),
+                _ => Err(ParseError::FieldCount("DOMAIN", 2, fields.len() - 1)),
+            },
+            ["DOMAIN-VALIDITY", ..] => match fields[1..] {
+                [name, algorithm, public_key] => Ok(Record::DomainValidity {
+                    name: parse_domain(name)?,
+                    algorithm: algorithm.parse()?,
+                    public_key: parse_hex(public_key, "public key")?,
+                }),
+                _ => Err(ParseError::FieldCount(
+                    "DOMAIN-VALIDITY",
+                    3,
+                    fields.len() - 1,
+                )),
+            },
+            ["DOMAIN-UPDATE", ..] => match fields[1..] {
+                [name, timestamp] => Ok(Record::DomainUpdate {
+                    name: parse_domain(name)?,
+                    timestamp: parse_timestamp(timestamp)?,
+                }),
+                _ => Err(ParseError::FieldCount("DOMAIN-UPDATE", 2, fields.len() - 1)),
+            },
+            ["TARGET", ..] => match fields[1..] {
+                [address] => Ok(Record::Update(RecordUpdate::Target {
+                    address: parse_field(address, "TARGET address")?,
+                })),
+                _ => Err(ParseError::FieldCount("TARGET", 1, fields.len() - 1)),
+            },
+            ["CONTENT", ..] => match fields[1..] {
+                [inscription_id] => Ok(Record::Update(RecordUpdate::Content {
+                    inscription_id: parse_field(inscription_id, "CONTENT inscription id")?,
+                })),
+                _ => Err(ParseError::FieldCount("CONTENT", 1, fields.len() - 1)),
+            },
+            [algorithm, signature] => Ok(Record::Signature {
+                algorithm: algorithm.parse()?,
+                signature: parse_hex(signature, "signature")?,
+            }),
+            _ => Err(ParseError::UnknownRecord(fields[0].to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Record::Domain { name, timestamp } => write!(f, "DOMAIN {} {}", name, timestamp),
+            Record::DomainValidity {
+                name,
+                algorithm,
+                public_key,
+            } => write!(
+                f,
+                "DOMAIN-VALIDITY {} {} {}",
+                name,
+                algorithm,
+                hex::encode(public_key)
+            ),
+            Record::DomainUpdate { name, timestamp } => {
+                write!(f, "DOMAIN-UPDATE {} {}", name, timestamp)
+            }
+            Record::Update(update) => write!(f, "{}", update),
+            Record::Signature {
+                algorithm,
+                signature,
+            } => write!(f, "{} {}", algorithm, hex::encode(signature)),
+        }
+    }
+}
+
+/// A complete domain inscription: the `DOMAIN` record, its `DOMAIN-VALIDITY` record and the
+/// signature over both, one record per line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainInscription {
+    pub name: String,
+    pub timestamp: i64,
+    pub algorithm: SignatureAlgorithm,
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl DomainInscription {
+    pub fn domain_record(&self) -> Record {
+        Record::Domain {
+            name: self.name.clone(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    pub fn validity_record(&self) -> Record {
+        Record::DomainValidity {
+            name: self.name.clone(),
+            algorithm: self.algorithm,
+            public_key: self.public_key.clone(),
+        }
+    }
+
+    pub fn signature_record(&self) -> Record {
+        Record::Signature {
+            algorithm: self.algorithm,
+            signature: self.signature.clone(),
+        }
+    }
+
+    /// The bytes covered by the signature: the `DOMAIN` and `DOMAIN-VALIDITY` records joined
+    /// by a single `\n`, without a trailing newline.
+    pub fn signing_message(&self) -> Vec<u8> {
+        format!("{}\n{}", self.domain_record(), self.validity_record()).into_bytes()
+    }
+}
+
+impl FromStr for DomainInscription {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines = s.split('\n').collect::<Vec<_>>();
+
+        let [domain, validity, signature] = lines[..] else {
+            return Err(ParseError::LineCount(lines.len()));
+        };
+
+        let (name, timestamp) = match domain.parse()? {
+            Record::Domain { name, timestamp } => (name, timestamp),
+            _ => return Err(ParseError::UnexpectedRecord("DOMAIN", 1)),
+        };
+
+        let (algorithm, public_key) = match validity.parse()? {
+            Record::DomainValidity {
+                name: validity_name,
+                algorithm,
+                public_key,
+            } => {
+                if validity_name != name {
+                    return Err(ParseError::DomainMismatch(name, validity_name));
+                }
+
+                (algorithm, public_key)
+            }
+            _ => return Err(ParseError::UnexpectedRecord("DOMAIN-VALIDITY", 2)),
+        };
+
+        let signature = match signature.parse()? {
+            Record::Signature {
+                algorithm: signature_algorithm,
+                signature,
+            } if signature_algorithm == algorithm => signature,
+            Record::Signature {
+                algorithm: signature_algorithm,
+                ..
+            } => {
+                return Err(ParseError::UnsupportedAlgorithm(
+                    signature_algorithm.to_string(),
+                ))
+            }
+            _ => return Err(ParseError::UnexpectedRecord("signature", 3)),
+        };
+
+        Ok(Self {
+            name,
+            timestamp,
+            algorithm,
+            public_key,
+            signature,
+        })
+    }
+}
+
+impl fmt::Display for DomainInscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\n{}\n{}",
+            self.domain_record(),
+            self.validity_record(),
+            self.signature_record()
+        )
+    }
+}
+
+/// Record changes for a domain that is already inscribed. The `DOMAIN-UPDATE` record comes
+/// first, followed by one line per update and the signature over all preceding lines, made
+/// with the key from the domain's `DOMAIN-VALIDITY` record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainUpdateInscription {
+    pub name: String,
+    pub timestamp: i64,
+    pub updates: Vec<RecordUpdate>,
+    pub algorithm: SignatureAlgorithm,
+    pub signature: Vec<u8>,
+}
+
+impl DomainUpdateInscription {
+    pub fn update_record(&self) -> Record {
+        Record::DomainUpdate {
+            name: self.name.clone(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    pub fn signature_record(&self) -> Record {
+        Record::Signature {
+            algorithm: self.algorithm,
+            signature: self.signature.clone(),
+        }
+    }
+
+    pub fn signing_message(&self) -> Vec<u8> {
+        let mut lines = vec![self.update_record().to_string()];
+        lines.extend(self.updates.iter().map(|update| update.to_string()));
+
+        lines.join("\n").into_bytes()
+    }
+
+    /// The address set by the last `TARGET` update, if any.
+    pub fn target(&self) -> Option<&str> {
+        self.updates.iter().rev().find_map(|update| match update {
+            RecordUpdate::Target { address } => Some(address.as_str()),
+            _ => None,
+        })
+    }
+}
+
+impl FromStr for DomainUpdateInscription {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines = s.split('\n').collect::<Vec<_>>();
+
+        if lines.len() < 3 {
+            return Err(ParseError::LineCount(lines.len()));
+        }
+
+        let (name, timestamp) = match lines[0].parse()? {
+            Record::DomainUpdate { name, timestamp } => (name, timestamp),
+            _ => return Err(ParseError::UnexpectedRecord("DOMAIN-UPDATE", 1)),
+        };
+
+        let mut updates = Vec::new();
+
+        for (index, line) in lines[1..lines.len() - 1].iter().enumerate() {
+            match line.parse()? {
+                Record::Update(update) => updates.push(update),
+                _ => return Err(ParseError::UnexpectedRecord("record update", index + 2)),
+            }
+        }
+
+        if updates.is_empty() {
+            return Err(ParseError::NoUpdates);
+        }
+
+        let (algorithm, signature) = match lines[lines.len() - 1].parse()? {
+            Record::Signature {
+                algorithm,
+                signature,
+            } => (algorithm, signature),
+            _ => return Err(ParseError::UnexpectedRecord("signature", lines.len())),
+        };
+
+        Ok(Self {
+            name,
+            timestamp,
+            updates,
+            algorithm,
+            signature,
+        })
+    }
+}
+
+impl fmt::Display for DomainUpdateInscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\n{}",
+            String::from_utf8_lossy(&self.signing_message()),
+            self.signature_record()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    fn token() -> impl Strategy<Value = String> {
+        "[^\\s\\p{C}]{1,24}"
+    }
+
+    fn domain() -> impl Strategy<Value = String> {
+        prop_oneof![
+            "[a-z0-9]{1,24}",
+            Just("xn--wgv71a".to_string()),
+            Just("xn--zckzah".to_string()),
+        ]
+        .prop_map(|label| format!("{}.o", label))
+        .prop_filter("reserved domain", |name| {
+            domain_name::normalize(name).is_ok()
+        })
+    }
+
+    fn bytes() -> impl Strategy<Value = Vec<u8>> {
+        vec(any::<u8>(), 1..96)
+    }
+
+    fn record_update() -> impl Strategy<Value = RecordUpdate> {
+        prop_oneof![
+            token().prop_map(|address| RecordUpdate::Target { address }),
+            (token(), "[^\\p{C}]{1,48}").prop_map(|(key, value)| RecordUpdate::Text { key, value }),
+            token().prop_map(|inscription_id| RecordUpdate::Content { inscription_id }),
+        ]
+    }
+
+    fn domain_inscription() -> impl Strategy<Value = DomainInscription> {
+        (domain(), 0..=i64::MAX, bytes(), bytes()).prop_map(
+            |(name, timestamp, public_key, signature)| DomainInscription {
+                name,
+                timestamp,
+                algorithm: SignatureAlgorithm::Dilithium5,
+                public_key,
+                signature,
+            },
+        )
+    }
+
+    fn domain_update_inscription() -> impl Strategy<Value = DomainUpdateInscription> {
+        (domain(), 0..=i64::MAX, vec(record_update(), 1..6), bytes()).prop_map(
+            |(name, timestamp, updates, signature)| DomainUpdateInscription {
+                name,
+                timestamp,
+                updates,
+                algorithm: SignatureAlgorithm::Dilithium5,
+                signature,
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn record_updates_round_trip(update in record_update()) {
+            let record = Record::Update(update);
+            prop_assert_eq!(record.to_string().parse::<Record>(), Ok(record));
+        }
+
+        #[test]
+        fn domain_inscriptions_round_trip(inscription in domain_inscription()) {
+            prop_assert_eq!(inscription.to_string().parse::<DomainInscription>(), Ok(inscription));
+        }
+
+        #[test]
+        fn domain_update_inscriptions_round_trip(inscription in domain_update_inscription()) {
+            prop_assert_eq!(
+                inscription.to_string().parse::<DomainUpdateInscription>(),
+                Ok(inscription)
+            );
+        }
+
+        #[test]
+        fn rejects_non_canonical_timestamps(timestamp in 0..=i64::MAX / 10) {
+            let padded = format!("0{}", timestamp);
+            prop_assert_eq!(
+                format!("DOMAIN alice.o {}", padded).parse::<Record>(),
+                Err(ParseError::InvalidTimestamp(padded))
+            );
+        }
+
+        #[test]
+        fn rejects_uppercase_hex(inscription in domain_inscription()) {
+            let signature = hex::encode_upper(&inscription.signature);
+            prop_assume!(signature.chars().any(|c| c.is_ascii_uppercase()));
+
+            let text = format!(
+                "{}\n{}\ndilithium5 {}",
+                inscription.domain_record(),
+                inscription.validity_record(),
+                signature
+            );
+            prop_assert_eq!(
+                text.parse::<DomainInscription>(),
+                Err(ParseError::InvalidHex("signature"))
+            );
+        }
+
+        #[test]
+        fn rejects_trailing_newlines(inscription in domain_inscription()) {
+            prop_assert_eq!(
+                format!("{}\n", inscription).parse::<DomainInscription>(),
+                Err(ParseError::LineCount(4))
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_records() {
+        let cases = [
+            ("", ParseError::EmptyRecord),
+            ("DOMAIN alice.o", ParseError::FieldCount("DOMAIN", 2, 1)),
+            ("DOMAIN alice.o 1 2", ParseError::FieldCount("DOMAIN", 2, 3)),
+            (
+                "DOMAIN alice.o -1",
+                ParseError::InvalidTimestamp("-1".to_string()),
+            ),
+            ("DOMAIN  1", ParseError::InvalidDomain(String::new())),
+            (
+                "DOMAIN-VALIDITY alice.o rsa 00",
+                ParseError::UnsupportedAlgorithm("rsa".to_string()),
+            ),
+            (
+                "DOMAIN-VALIDITY alice.o dilithium5 0",
+                ParseError::InvalidHex("public key"),
+            ),
+            ("TARGET", ParseError::FieldCount("TARGET", 1, 0)),
+            ("TARGET a b", ParseError::FieldCount("TARGET", 1, 2)),
+            ("TXT key", ParseError::FieldCount("TXT", 2, 1)),
+            ("TXT key ", ParseError::InvalidField("TXT value")),
+            ("TXT key a\tb", ParseError::InvalidField("TXT value")),
+            (
+                "NAME alice.o 1",
+                ParseError::UnknownRecord("NAME".to_string()),
+            ),
+            (
+                "null null",
+                ParseError::UnsupportedAlgorithm("null".to_string()),
+            ),
+        ];
+
+        for (line, error) in cases {
+            assert_eq!(line.parse::<Record>(), Err(error), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn rejects_domains_that_are_not_normalized() {
+        let names = [
+            "alice",
+            "alice.btc",
+            "Alice.o",
+            "ALICE.O",
+            "alice.o.o",
+            "bob.alice.o",
+            "-alice.o",
+            "al_ice.o",
+            "日本.o",
+            "xn--.o",
+            "example.o",
+            "аlice.o",
+        ];
+
+        for name in names {
+            assert_eq!(
+                format!("DOMAIN {} 1", name).parse::<Record>(),
+                Err(ParseError::InvalidDomain(name.to_string())),
+                "{:?}",
+                name
+            );
+        }
+
+        assert_eq!(
+            "DOMAIN xn--wgv71a.o 1".parse::<Record>(),
+            Ok(Record::Domain {
+                name: "xn--wgv71a.o".to_string(),
+                timestamp: 1
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_inscriptions() {
+        assert_eq!(
+            "DOMAIN alice.o 1\nDOMAIN-VALIDITY bob.o dilithium5 00\ndilithium5 00"
+                .parse::<DomainInscription>(),
+            Err(ParseError::DomainMismatch(
+                "alice.o".to_string(),
+                "bob.o".to_string()
+            ))
+        );
+        assert_eq!(
+            "DOMAIN-VALIDITY alice.o dilithium5 00\nDOMAIN alice.o 1\ndilithium5 00"
+                .parse::<DomainInscription>(),
+            Err(ParseError::UnexpectedRecord("DOMAIN", 1))
+        );
+        assert_eq!(
+            "DOMAIN alice.o 1\nDOMAIN-VALIDITY alice.o dilithium5 00".parse::<DomainInscription>(),
+            Err(ParseError::LineCount(2))
+        );
+        assert_eq!(
+            "DOMAIN-UPDATE alice.o 1\ndilithium5 00".parse::<DomainUpdateInscription>(),
+            Err(ParseError::LineCount(2))
+        );
+        assert_eq!(
+            "DOMAIN-UPDATE alice.o 1\nDOMAIN alice.o 1\ndilithium5 00"
+                .parse::<DomainUpdateInscription>(),
+            Err(ParseError::UnexpectedRecord("record update", 2))
+        );
+        assert_eq!(
+            "DOMAIN-UPDATE alice.o 1\nTARGET bc1q\nTARGET bc1p".parse::<DomainUpdateInscription>(),
+            Err(ParseError::UnexpectedRecord("signature", 3))
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::env;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use super::{http_client, Invoice, InvoiceState, LightningBackend, LightningError};
+
+/// Core Lightning through the `clnrest` plugin, authenticated with a rune.
+#[derive(Clone)]
+pub struct ClnRestBackend {
+    client: reqwest::Client,
+    url: String,
+    rune: String,
+}
+
+#[derive(Debug, Serialize)]
+struct InvoiceRequest<'a> {
+    amount_msat: u64,
+    label: &'a str,
+    description: &'a str,
+    expiry: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct InvoiceResponse {
+    bolt11: String,
+    payment_hash: String,
+    expires_at: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct ListInvoicesRequest<'a> {
+    payment_hash: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListedInvoice {
+    label: String,
+    status: String,
+    amount_received_msat: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListInvoicesResponse {
+    invoices: Vec<ListedInvoice>,
+}
+
+#[derive(Debug, Serialize)]
+struct DelInvoiceRequest<'a> {
+    label: &'a str,
+    status: &'a str,
+}
+
+impl ClnRestBackend {
+    pub fn from_env() -> Self {
+        Self {
+            client: http_client(),
+            url: env::var("LIGHTNING_REST_URL").expect("LIGHTNING_REST_URL not set"),
+            rune: env::var("CLN_RUNE").expect("CLN_RUNE not set"),
+        }
+    }
+
+    async fn find_invoice(&self, payment_hash: &str) -> Result<ListedInvoice, LightningError> {
+        let res = self
+            .client
+            .post(format!("{}/v1/listinvoices", self.url))
+            .header("Rune", &self.rune)
+            .json(&ListInvoicesRequest { payment_hash })
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<ListInvoicesResponse>()
+            .await?;
+
+        res.invoices
+            .into_iter()
+            .next()
+            .ok_or_else(|| LightningError::Backend(format!("invoice {} not found", payment_hash)))
+    }
+}
+
+impl LightningBackend for ClnRestBackend {
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        memo: &str,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LightningError> {
+        // Labels have to be unique per node, the memo alone is not.
+        let label = format!(
+            "{} {}",
+            memo,
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        );
+
+        let res = self
+            .client
+            .post(format!("{}/v1/invoice", self.url))
+            .header("Rune", &self.rune)
+            .json(&InvoiceRequest {
+                amount_msat,
+                label: &label,
+                description: memo,
+                expiry: expiry_secs,
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<InvoiceResponse>()
+            .await?;
+
+        let expires_at = NaiveDateTime::from_timestamp_opt(res.expires_at, 0)
+            .ok_or_else(|| LightningError::Backend("invalid expires_at".to_string()))?;
+
+        Ok(Invoice {
+            bolt11: res.bolt11,
+            payment_hash: res.payment_hash,
+            expires_at,
+        })
+    }
+
+    async fn invoice_state(&self, payment_hash: &str) -> Result<InvoiceState, LightningError> {
+        let invoice = self.find_invoice(payment_hash).await?;
+
+        match invoice.status.as_str() {
+            "unpaid" => Ok(InvoiceState::Open),
+            "paid" => Ok(InvoiceState::Settled {
+                amount_msat: invoice.amount_received_msat.unwrap_or_default(),
+            }),
+            "expired" => Ok(InvoiceState::Cancelled),
+            status => Err(LightningError::Backend(format!(
+                "unknown invoice status: {}",
+                status
+            ))),
+        }
+    }
+
+    async fn cancel_invoice(&self, payment_hash: &str) -> Result<(), LightningError> {
+        // Invoices are deleted by label, and only while they are still unpaid.
+        let invoice = self.find_invoice(payment_hash).await?;
+
+        self.client
+            .post(format!("{}/v1/delinvoice", self.url))
+            .header("Rune", &self.rune)
+            .json(&DelInvoiceRequest {
+                label: &invoice.label,
+                status: "unpaid",
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use std::env;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+use super::{http_client, Invoice, InvoiceState, LightningBackend, LightningError};
+
+/// LND through its REST proxy, authenticated with an invoice macaroon.
+#[derive(Clone)]
+pub struct LndRestBackend {
+    client: reqwest::Client,
+    url: String,
+    macaroon: String,
+}
+
+#[derive(Debug, Serialize)]
+struct AddInvoiceRequest<'a> {
+    value_msat: String,
+    memo: &'a str,
+    expiry: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddInvoiceResponse {
+    r_hash: String,
+    payment_request: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LookupInvoiceResponse {
+    state: String,
+    #[serde(default)]
+    amt_paid_msat: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CancelInvoiceRequest {
+    payment_hash: String,
+}
+
+impl LndRestBackend {
+    pub fn from_env() -> Self {
+        Self {
+            client: http_client(),
+            url: env::var("LIGHTNING_REST_URL").expect("LIGHTNING_REST_URL not set"),
+            macaroon: env::var("LND_MACAROON").expect("LND_MACAROON not set"),
+        }
+    }
+}
+
+impl LightningBackend for LndRestBackend {
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        memo: &str,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LightningError> {
+        let res = self
+            .client
+            .post(format!("{}/v1/invoices", self.url))
+            .header("Grpc-Metadata-macaroon", &self.macaroon)
+            .json(&AddInvoiceRequest {
+                value_msat: amount_msat.to_string(),
+                memo,
+                expiry: expiry_secs.to_string(),
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<AddInvoiceResponse>()
+            .await?;
+
+        let payment_hash = STANDARD
+            .decode(&res.r_hash)
+            .map_err(|e| LightningError::Backend(format!("invalid r_hash: {}", e)))?;
+
+        Ok(Invoice {
+            bolt11: res.payment_request,
+            payment_hash: hex::encode(payment_hash),
+            expires_at: chrono::Utc::now().naive_utc()
+                + chrono::Duration::seconds(expiry_secs as i64),
+        })
+    }
+
+    async fn invoice_state(&self, payment_hash: &str) -> Result<InvoiceState, LightningError> {
+        let res = self
+            .client
+            .get(format!("{}/v1/invoice/{}", self.url, payment_hash))
+            .header("Grpc-Metadata-macaroon", &self.macaroon)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<LookupInvoiceResponse>()
+            .await?;
+
+        match res.state.as_str() {
+            "OPEN" | "ACCEPTED" => Ok(InvoiceState::Open),
+            "SETTLED" => Ok(InvoiceState::Settled {
+                amount_msat: res.amt_paid_msat.parse().unwrap_or_default(),
+            }),
+            "CANCELED" => Ok(InvoiceState::Cancelled),
+            state => Err(LightningError::Backend(format!(
+                "unknown invoice state: {}",
+                state
+            ))),
+        }
+    }
+
+    async fn cancel_invoice(&self, payment_hash: &str) -> Result<(), LightningError> {
+        let payment_hash = hex::decode(payment_hash)
+            .map_err(|e| LightningError::Backend(format!("invalid payment hash: {}", e)))?;
+
+        self.client
+            .post(format!("{}/v2/invoices/cancel", self.url))
+            .header("Grpc-Metadata-macaroon", &self.macaroon)
+            .json(&CancelInvoiceRequest {
+                payment_hash: STANDARD.encode(payment_hash),
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use super::{Invoice, InvoiceState, LightningBackend, LightningError};
+
+/// In-memory backend for local development and tests, invoices only settle through [`settle`].
+///
+/// [`settle`]: MockBackend::settle
+#[derive(Clone, Default)]
+pub struct MockBackend {
+    invoices: Arc<Mutex<HashMap<String, (u64, InvoiceState)>>>,
+}
+
+impl MockBackend {
+    pub fn settle(&self, payment_hash: &str) -> bool {
+        let mut invoices = self.invoices.lock().unwrap();
+
+        match invoices.get_mut(payment_hash) {
+            Some((amount_msat, state)) if *state == InvoiceState::Open => {
+                *state = InvoiceState::Settled {
+                    amount_msat: *amount_msat,
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn open_invoices(&self) -> usize {
+        self.invoices
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(_, state)| *state == InvoiceState::Open)
+            .count()
+    }
+}
+
+impl LightningBackend for MockBackend {
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        _memo: &str,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LightningError> {
+        let mut invoices = self.invoices.lock().unwrap();
+
+        let payment_hash = format!("{:064x}", invoices.len() + 1);
+        invoices.insert(payment_hash.clone(), (amount_msat, InvoiceState::Open));
+
+        Ok(Invoice {
+            bolt11: format!("lnbcrt{}n1mock{}", amount_msat / 100, &payment_hash[48..]),
+            payment_hash,
+            expires_at: chrono::Utc::now().naive_utc()
+                + chrono::Duration::seconds(expiry_secs as i64),
+        })
+    }
+
+    async fn invoice_state(&self, payment_hash: &str) -> Result<InvoiceState, LightningError> {
+        self.invoices
+            .lock()
+            .unwrap()
+            .get(payment_hash)
+            .map(|(_, state)| *state)
+            .ok_or_else(|| LightningError::Backend(format!("invoice {} not found", payment_hash)))
+    }
+
+    async fn cancel_invoice(&self, payment_hash: &str) -> Result<(), LightningError> {
+        let mut invoices = self.invoices.lock().unwrap();
+
+        match invoices.get_mut(payment_hash) {
+            Some((_, state)) if *state == InvoiceState::Open => {
+                *state = InvoiceState::Cancelled;
+                Ok(())
+            }
+            Some(_) => Err(LightningError::Backend(format!(
+                "invoice {} is not open",
+                payment_hash
+            ))),
+            None => Err(LightningError::Backend(format!(
+                "invoice {} not found",
+                payment_hash
+            ))),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::{env, fmt};
+
+use chrono::NaiveDateTime;
+
+pub mod cln;
+pub mod lnd;
+pub mod mock;
+
+pub use cln::ClnRestBackend;
+pub use lnd::LndRestBackend;
+pub use mock::MockBackend;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invoice {
+    pub bolt11: String,
+    /// Hex encoded payment hash.
+    pub payment_hash: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvoiceState {
+    Open,
+    Settled { amount_msat: u64 },
+    Cancelled,
+}
+
+#[derive(Debug)]
+pub enum LightningError {
+    Request(reqwest::Error),
+    Backend(String),
+}
+
+impl fmt::Display for LightningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LightningError::Request(e) => write!(f, "lightning request failed: {}", e),
+            LightningError::Backend(e) => write!(f, "lightning backend error: {}", e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for LightningError {
+    fn from(e: reqwest::Error) -> Self {
+        LightningError::Request(e)
+    }
+}
+
+pub trait LightningBackend
+where
+    Self: Clone,
+{
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        memo: &str,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LightningError>;
+
+    async fn invoice_state(&self, payment_hash: &str) -> Result<InvoiceState, LightningError>;
+
+    /// Makes an open invoice unpayable, for orders that were not stored after all.
+    async fn cancel_invoice(&self, payment_hash: &str) -> Result<(), LightningError>;
+}
+
+/// The configured lightning backend, selected through `LIGHTNING_BACKEND`.
+#[derive(Clone)]
+pub enum Lightning {
+    Lnd(LndRestBackend),
+    Cln(ClnRestBackend),
+    Mock(MockBackend),
+}
+
+impl Lightning {
+    /// Returns `None` when lightning payments are not configured.
+    pub fn from_env() -> Option<Self> {
+        let backend = env::var("LIGHTNING_BACKEND").ok()?;
+
+        match backend.as_str() {
+            "lnd" => Some(Self::Lnd(LndRestBackend::from_env())),
+            "cln" => Some(Self::Cln(ClnRestBackend::from_env())),
+            "mock" => Some(Self::Mock(MockBackend::default())),
+            _ => panic!("Invalid LIGHTNING_BACKEND: {}", backend),
+        }
+    }
+}
+
+impl LightningBackend for Lightning {
+    async fn create_invoice(
+        &self,
+        amount_msat: u64,
+        memo: &str,
+        expiry_secs: u64,
+    ) -> Result<Invoice, LightningError> {
+        match self {
+            Self::Lnd(backend) => backend.create_invoice(amount_msat, memo, expiry_secs).await,
+            Self::Cln(backend) => backend.create_invoice(amount_msat, memo, expiry_secs).await,
+            Self::Mock(backend) => backend.create_invoice(amount_msat, memo, expiry_secs).await,
+        }
+    }
+
+    async fn invoice_state(&self, payment_hash: &str) -> Result<InvoiceState, LightningError> {
+        match self {
+            Self::Lnd(backend) => backend.invoice_state(payment_hash).await,
+            Self::Cln(backend) => backend.invoice_state(payment_hash).await,
+            Self::Mock(backend) => backend.invoice_state(payment_hash).await,
+        }
+    }
+
+    async fn cancel_invoice(&self, payment_hash: &str) -> Result<(), LightningError> {
+        match self {
+            Self::Lnd(backend) => backend.cancel_invoice(payment_hash).await,
+            Self::Cln(backend) => backend.cancel_invoice(payment_hash).await,
+            Self::Mock(backend) => backend.cancel_invoice(payment_hash).await,
+        }
+    }
+}
+
+pub fn btc_to_msat(amount: f64) -> u64 {
+    (amount * 100000000000f64).round() as u64
+}
+
+pub fn msat_to_btc(amount_msat: u64) -> f64 {
+    amount_msat as f64 / 100000000000f64
+}
+
+fn read_tls_certificate() -> Option<reqwest::Certificate> {
+    let path = env::var("LIGHTNING_TLS_CERT").ok()?;
+    let pem = std::fs::read(&path).expect("LIGHTNING_TLS_CERT could not be read");
+
+    Some(reqwest::Certificate::from_pem(&pem).expect("LIGHTNING_TLS_CERT is not a valid PEM"))
+}
+
+fn http_client() -> reqwest::Client {
+    let mut builder = reqwest::Client::builder();
+
+    if let Some(certificate) = read_tls_certificate() {
+        builder = builder.add_root_certificate(certificate);
+    }
+
+    builder.build().unwrap()
+}
@@ -1,38 +1,86 @@
 #![feature(async_fn_in_trait)]
 use std::{env, str::FromStr, sync::Arc};
 
-use bitcoin::chain::Chain;
+use bitcoin::{
+    address_provider::AddressProvider,
+    chain::Chain,
+    fees::{FeeEstimator, NetworkFeeConfig},
+    processor::{process_lightning_payments, process_onchain_payments},
+    sweep::{sweep, SweepConfig},
+    wallet::{RpcWallet, Wallet},
+};
 use bitcoincore_rpc::{
     bitcoin::{address::NetworkChecked, Address},
     Auth, Client, RpcApi,
 };
-use db::{traits::SessionRepository, PaymentRepository, Repository};
+use chrono::NaiveDateTime;
+use db::{
+    traits::{
+        repository::{LogsFilter, PaymentSearch, PaymentsFilter},
+        SessionRepository,
+    },
+    PaymentRepository, Repository,
+};
 use endpoints::{
+    admin::{
+        addresses::{RescanAddressData, RescanAddressResponse},
+        domain_rules::{
+            DeleteDomainRuleResponse, DomainRulesResponse, SetDomainRuleData, SetDomainRuleResponse,
+        },
+        late_payments::{LatePaymentActionResponse, LatePaymentsResponse},
+        payments::{
+            AdminPaymentsResponse, PaymentOverrideData, PaymentOverrideResponse,
+            PaymentTimelineResponse,
+        },
+        refunds::{
+            AdminRefundsResponse, ApproveRefundData, RefundActionResponse, RejectRefundData,
+        },
+    },
     delete::DeletePaymentResponse,
     domains::PaidDomains,
     get_private_key::GetPrivateKeyResponse,
+    logs::LogsResponse,
+    lookup::{DomainAvailabilityResponse, DomainLookupResponse},
     new::{CreatePaymentData, CreatePaymentResponse},
+    payment_qr::{PaymentQrCodeResponse, QrCodeFormat},
+    payments::{PaymentsOrder, PaymentsResponse},
     pricing::PricingResponse,
+    records::{
+        CustodialSigningData, CustodialSigningResponse, UpdateRecordsData, UpdateRecordsResponse,
+    },
+    refunds::{CreateRefundData, CreateRefundResponse, RefundsResponse},
     status::PaymentStatusResponse,
+    transfers::{AcceptTransferData, CreateTransferData, TransferResponse, TransfersResponse},
 };
+use lazy_static::lazy_static;
+use lightning::Lightning;
 use poem::{
     listener::TcpListener, middleware::Cors, web::Data, EndpointExt, Request, Route, Server,
 };
 use poem_openapi::{
     auth::Bearer,
-    param::{Path, Query},
+    param::{Header, Path, Query},
     payload::Json,
     OpenApi, OpenApiService, SecurityScheme,
 };
 use std::ops::Deref;
 use tracing::{error, info};
+use utils::rate_limit::RateLimiter;
 use uuid::Uuid;
 
-use crate::db::log::LogTypes;
+use crate::db::{
+    log::{LogEvent, LogTypes},
+    repositories::models::{
+        domain_rule::DomainList, late_payment::LatePaymentStatus, refund::RefundStatus,
+        transfer::TransferStatus,
+    },
+};
 
 pub mod bitcoin;
 pub mod db;
 pub mod endpoints;
+pub mod inscription;
+pub mod lightning;
 pub mod responses;
 pub mod utils;
 
@@ -44,14 +92,41 @@ pub const CHAIN: Chain = Chain::Mainnet;
 
 pub const DOMAIN_PRICE_BTC: f64 = 0.0007;
 pub const MINIMUM_DOMAIN_PRICE_BTC: f64 = 0.0004;
+pub const RECORD_UPDATE_PRICE_BTC: f64 = 0.00005;
+pub const MAX_DOMAINS_PER_ORDER: u32 = 100;
+/// How long invoices keep being checked after they expired, so a settlement just before the
+/// expiry is still picked up.
+pub const LIGHTNING_EXPIRY_GRACE_HOURS: i32 = 1;
 
 #[cfg(debug_assertions)]
 const BITCOIN_WALLET_NAME: &str = "ord";
 
 #[cfg(not(debug_assertions))]
 const BITCOIN_WALLET_NAME: &str = "xiler";
+const DEFAULT_WATCH_ONLY_WALLET_NAME: &str = "xiler-watch";
+#[cfg(not(debug_assertions))]
 const COOKIE_LOCATION: &str = "/home/bitcheck/.bitcoin/.cookie";
 const CONFIRMATIONS_REQUIRED: u32 = 1;
+const DEFAULT_ORDER_EXPIRATION_MINUTES: i32 = 35;
+const DEFAULT_IDEMPOTENCY_KEY_RETENTION_HOURS: i32 = 24;
+
+lazy_static! {
+    /// Minutes an order may stay uninitiated before it expires, configurable through
+    /// `ORDER_EXPIRATION_MINUTES`.
+    pub static ref ORDER_EXPIRATION_MINUTES: i32 = env::var("ORDER_EXPIRATION_MINUTES")
+        .ok()
+        .and_then(|minutes| minutes.parse().ok())
+        .filter(|minutes| *minutes > 0)
+        .unwrap_or(DEFAULT_ORDER_EXPIRATION_MINUTES);
+
+    /// Hours the response of `/new` is kept for its idempotency key, configurable through
+    /// `IDEMPOTENCY_KEY_RETENTION_HOURS`.
+    pub static ref IDEMPOTENCY_KEY_RETENTION_HOURS: i32 = env::var("IDEMPOTENCY_KEY_RETENTION_HOURS")
+        .ok()
+        .and_then(|hours| hours.parse().ok())
+        .filter(|hours| *hours > 0)
+        .unwrap_or(DEFAULT_IDEMPOTENCY_KEY_RETENTION_HOURS);
+}
 
 struct ApiKeyContext {
     id: Uuid,
@@ -85,19 +160,66 @@ async fn check_api_key(req: &Request, api_key: Bearer) -> Option<ApiKeyContext>
     }
 }
 
+#[derive(SecurityScheme)]
+#[oai(
+    ty = "bearer",
+    key_name = "Xiler-Accounts-API-Key",
+    key_in = "header",
+    checker = "check_admin_api_key"
+)]
+struct AdminAuthApiKey(ApiKeyContext);
+
+impl Deref for AdminAuthApiKey {
+    type Target = ApiKeyContext;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+async fn check_admin_api_key(req: &Request, api_key: Bearer) -> Option<ApiKeyContext> {
+    let context = check_api_key(req, api_key).await?;
+    let pool = req.data::<Repository>().unwrap();
+
+    match pool.is_admin(&context.id).await {
+        Ok(true) => Some(context),
+        _ => None,
+    }
+}
+
 struct Api;
 
+// Handlers take one argument per extractor, and `new` names the route rather than a constructor.
+#[allow(
+    clippy::too_many_arguments,
+    clippy::new_ret_no_self,
+    clippy::wrong_self_convention
+)]
 #[OpenApi]
 impl Api {
     #[oai(path = "/new", method = "post")]
     async fn new(
         &self,
         pool: Data<&Repository>,
-        rpc: Data<&Arc<Client>>,
+        addresses: Data<&AddressProvider>,
+        fees: Data<&FeeEstimator>,
+        lightning: Data<&Option<Lightning>>,
         auth: AuthApiKey,
+        /// Retrying with the same key returns the original response instead of creating another order.
+        #[oai(name = "Idempotency-Key")]
+        idempotency_key: Header<Option<String>>,
         data: Json<CreatePaymentData>,
     ) -> CreatePaymentResponse {
-        endpoints::new::new(&pool, &rpc, &auth.id, &data).await
+        endpoints::new::new(
+            pool.0,
+            &addresses,
+            &fees,
+            lightning.0.as_ref(),
+            &auth.id,
+            idempotency_key.as_deref(),
+            &data,
+        )
+        .await
     }
 
     #[oai(path = "/status/:id", method = "get")]
@@ -107,7 +229,7 @@ impl Api {
         auth: AuthApiKey,
         id: Path<Uuid>,
     ) -> PaymentStatusResponse {
-        endpoints::status::status(&pool, &auth.id, &id).await
+        endpoints::status::status(pool.0, &auth.id, &id).await
     }
 
     #[oai(path = "/delete/:id", method = "delete")]
@@ -117,22 +239,90 @@ impl Api {
         auth: AuthApiKey,
         id: Path<Uuid>,
     ) -> DeletePaymentResponse {
-        endpoints::delete::delete(&pool, &auth.id, &id).await
+        endpoints::delete::delete(pool.0, &auth.id, &id).await
     }
 
     #[oai(path = "/domains", method = "get")]
     async fn domains(&self, pool: Data<&Repository>, auth: AuthApiKey) -> PaidDomains {
-        endpoints::domains::domains(&pool, &auth.id).await
+        endpoints::domains::domains(pool.0, &auth.id).await
+    }
+
+    #[oai(path = "/payments", method = "get")]
+    async fn payments(
+        &self,
+        pool: Data<&Repository>,
+        auth: AuthApiKey,
+        cursor: Query<Option<Uuid>>,
+        limit: Query<Option<u32>>,
+        order: Query<Option<PaymentsOrder>>,
+        initiated: Query<Option<bool>>,
+        completed: Query<Option<bool>>,
+        expired: Query<Option<bool>>,
+        from: Query<Option<NaiveDateTime>>,
+        to: Query<Option<NaiveDateTime>>,
+    ) -> PaymentsResponse {
+        let filter = PaymentsFilter {
+            initiated: initiated.0,
+            completed: completed.0,
+            expired: expired.0,
+            from: from.0,
+            to: to.0,
+            cursor: cursor.0,
+            ascending: order.0 == Some(PaymentsOrder::Asc),
+            ..Default::default()
+        };
+
+        endpoints::payments::payments(pool.0, &auth.id, filter, limit.0).await
+    }
+
+    #[oai(path = "/logs", method = "get")]
+    async fn logs(
+        &self,
+        pool: Data<&Repository>,
+        auth: AuthApiKey,
+        cursor: Query<Option<Uuid>>,
+        limit: Query<Option<u32>>,
+        log_type: Query<Vec<LogTypes>>,
+        payment_id: Query<Option<Uuid>>,
+    ) -> LogsResponse {
+        let filter = LogsFilter {
+            log_types: log_type.0,
+            payment_id: payment_id.0,
+            cursor: cursor.0,
+            ..Default::default()
+        };
+
+        endpoints::logs::logs(pool.0, &auth.id, filter, limit.0, false).await
+    }
+
+    #[oai(path = "/payments/:id/qr", method = "get")]
+    async fn payment_qr_code(
+        &self,
+        pool: Data<&Repository>,
+        auth: AuthApiKey,
+        id: Path<Uuid>,
+        format: Query<Option<QrCodeFormat>>,
+    ) -> PaymentQrCodeResponse {
+        endpoints::payment_qr::payment_qr_code(
+            pool.0,
+            &auth.id,
+            &id,
+            format.0.unwrap_or(QrCodeFormat::Svg),
+        )
+        .await
     }
 
     #[oai(path = "/pricing", method = "get")]
     async fn pricing(
         &self,
         pool: Data<&Repository>,
+        fees: Data<&FeeEstimator>,
         auth: AuthApiKey,
         amount: Query<u32>,
+        /// Comma separated domains to check against the reserved and blocked lists.
+        domains: Query<Option<String>>,
     ) -> PricingResponse {
-        endpoints::pricing::get_price(&pool, &auth.id, amount.0).await
+        endpoints::pricing::get_price(pool.0, &fees, &auth.id, amount.0, domains.0.as_deref()).await
     }
 
     #[oai(path = "/private-key/:domain", method = "get")]
@@ -142,15 +332,344 @@ impl Api {
         auth: AuthApiKey,
         domain: Path<String>,
     ) -> GetPrivateKeyResponse {
-        endpoints::get_private_key::get_private_key(&pool, &auth.id, &domain.0).await
+        endpoints::get_private_key::get_private_key(pool.0, &auth.id, &domain.0).await
+    }
+
+    #[oai(path = "/domains/:domain/availability", method = "get")]
+    async fn domain_availability(
+        &self,
+        req: &Request,
+        pool: Data<&Repository>,
+        limiter: Data<&RateLimiter>,
+        domain: Path<String>,
+    ) -> DomainAvailabilityResponse {
+        if !limiter.check(req) {
+            return DomainAvailabilityResponse::TooManyRequests(Json("Too many requests".into()));
+        }
+
+        endpoints::lookup::availability(pool.0, &domain.0).await
+    }
+
+    #[oai(path = "/domains/:domain", method = "get")]
+    async fn domain_lookup(
+        &self,
+        req: &Request,
+        pool: Data<&Repository>,
+        limiter: Data<&RateLimiter>,
+        domain: Path<String>,
+    ) -> DomainLookupResponse {
+        if !limiter.check(req) {
+            return DomainLookupResponse::TooManyRequests(Json("Too many requests".into()));
+        }
+
+        endpoints::lookup::lookup(pool.0, &domain.0).await
+    }
+
+    #[oai(path = "/domains/:domain/records", method = "post")]
+    async fn update_records(
+        &self,
+        pool: Data<&Repository>,
+        addresses: Data<&AddressProvider>,
+        fees: Data<&FeeEstimator>,
+        auth: AuthApiKey,
+        domain: Path<String>,
+        data: Json<UpdateRecordsData>,
+    ) -> UpdateRecordsResponse {
+        endpoints::records::update_records(pool.0, &addresses, &fees, &auth.id, &domain.0, &data)
+            .await
+    }
+
+    #[oai(path = "/domains/:domain/custodial-signing", method = "put")]
+    async fn custodial_signing(
+        &self,
+        pool: Data<&Repository>,
+        auth: AuthApiKey,
+        domain: Path<String>,
+        data: Json<CustodialSigningData>,
+    ) -> CustodialSigningResponse {
+        endpoints::records::set_custodial_signing(pool.0, &auth.id, &domain.0, &data).await
+    }
+
+    #[oai(path = "/domains/:domain/transfers", method = "post")]
+    async fn create_transfer(
+        &self,
+        pool: Data<&Repository>,
+        addresses: Data<&AddressProvider>,
+        fees: Data<&FeeEstimator>,
+        auth: AuthApiKey,
+        domain: Path<String>,
+        data: Json<CreateTransferData>,
+    ) -> TransferResponse {
+        endpoints::transfers::create(pool.0, &addresses, &fees, &auth.id, &domain.0, &data).await
+    }
+
+    #[oai(path = "/transfers", method = "get")]
+    async fn transfers(&self, pool: Data<&Repository>, auth: AuthApiKey) -> TransfersResponse {
+        endpoints::transfers::transfers(pool.0, &auth.id).await
+    }
+
+    #[oai(path = "/transfers/:id/accept", method = "post")]
+    async fn accept_transfer(
+        &self,
+        pool: Data<&Repository>,
+        addresses: Data<&AddressProvider>,
+        fees: Data<&FeeEstimator>,
+        auth: AuthApiKey,
+        id: Path<Uuid>,
+        data: Json<AcceptTransferData>,
+    ) -> TransferResponse {
+        endpoints::transfers::accept(pool.0, &addresses, &fees, &auth.id, &id.0, &data).await
+    }
+
+    #[oai(path = "/transfers/:id/reject", method = "post")]
+    async fn reject_transfer(
+        &self,
+        pool: Data<&Repository>,
+        auth: AuthApiKey,
+        id: Path<Uuid>,
+    ) -> TransferResponse {
+        endpoints::transfers::close(pool.0, &auth.id, &id.0, TransferStatus::Rejected).await
+    }
+
+    #[oai(path = "/transfers/:id/cancel", method = "post")]
+    async fn cancel_transfer(
+        &self,
+        pool: Data<&Repository>,
+        auth: AuthApiKey,
+        id: Path<Uuid>,
+    ) -> TransferResponse {
+        endpoints::transfers::close(pool.0, &auth.id, &id.0, TransferStatus::Cancelled).await
+    }
+
+    #[oai(path = "/refunds", method = "post")]
+    async fn create_refund(
+        &self,
+        pool: Data<&Repository>,
+        auth: AuthApiKey,
+        data: Json<CreateRefundData>,
+    ) -> CreateRefundResponse {
+        endpoints::refunds::create(pool.0, &auth.id, &data).await
+    }
+
+    #[oai(path = "/refunds", method = "get")]
+    async fn refunds(&self, pool: Data<&Repository>, auth: AuthApiKey) -> RefundsResponse {
+        endpoints::refunds::refunds(pool.0, &auth.id).await
+    }
+
+    #[oai(path = "/admin/late-payments", method = "get")]
+    async fn admin_late_payments(
+        &self,
+        pool: Data<&Repository>,
+        _auth: AdminAuthApiKey,
+        status: Query<Option<LatePaymentStatus>>,
+    ) -> LatePaymentsResponse {
+        endpoints::admin::late_payments::late_payments(pool.0, status.0).await
+    }
+
+    #[oai(path = "/admin/late-payments/:id/reactivate", method = "post")]
+    async fn admin_reactivate_late_payment(
+        &self,
+        pool: Data<&Repository>,
+        auth: AdminAuthApiKey,
+        id: Path<Uuid>,
+    ) -> LatePaymentActionResponse {
+        endpoints::admin::late_payments::reactivate(pool.0, &auth.id, &id).await
+    }
+
+    #[oai(path = "/admin/late-payments/:id/refund", method = "post")]
+    async fn admin_refund_late_payment(
+        &self,
+        pool: Data<&Repository>,
+        auth: AdminAuthApiKey,
+        id: Path<Uuid>,
+    ) -> LatePaymentActionResponse {
+        endpoints::admin::late_payments::refund(pool.0, &auth.id, &id).await
+    }
+
+    #[oai(path = "/admin/domain-rules", method = "get")]
+    async fn admin_domain_rules(
+        &self,
+        pool: Data<&Repository>,
+        _auth: AdminAuthApiKey,
+        list: Query<Option<DomainList>>,
+    ) -> DomainRulesResponse {
+        endpoints::admin::domain_rules::domain_rules(pool.0, list.0).await
+    }
+
+    #[oai(path = "/admin/domain-rules/:domain", method = "put")]
+    async fn admin_set_domain_rule(
+        &self,
+        pool: Data<&Repository>,
+        auth: AdminAuthApiKey,
+        domain: Path<String>,
+        data: Json<SetDomainRuleData>,
+    ) -> SetDomainRuleResponse {
+        endpoints::admin::domain_rules::set(pool.0, &auth.id, &domain.0, &data).await
+    }
+
+    #[oai(path = "/admin/domain-rules/:domain", method = "delete")]
+    async fn admin_delete_domain_rule(
+        &self,
+        pool: Data<&Repository>,
+        auth: AdminAuthApiKey,
+        domain: Path<String>,
+    ) -> DeleteDomainRuleResponse {
+        endpoints::admin::domain_rules::delete(pool.0, &auth.id, &domain.0).await
+    }
+
+    #[oai(path = "/admin/refunds", method = "get")]
+    async fn admin_refunds(
+        &self,
+        pool: Data<&Repository>,
+        _auth: AdminAuthApiKey,
+        status: Query<Option<RefundStatus>>,
+    ) -> AdminRefundsResponse {
+        endpoints::admin::refunds::refunds(pool.0, status.0).await
+    }
+
+    #[oai(path = "/admin/refunds/:id/approve", method = "post")]
+    async fn admin_approve_refund(
+        &self,
+        pool: Data<&Repository>,
+        wallet: Data<&Wallet>,
+        auth: AdminAuthApiKey,
+        id: Path<Uuid>,
+        data: Json<ApproveRefundData>,
+    ) -> RefundActionResponse {
+        endpoints::admin::refunds::approve(pool.0, wallet.0, &auth.id, &id, &data).await
+    }
+
+    #[oai(path = "/admin/refunds/:id/reject", method = "post")]
+    async fn admin_reject_refund(
+        &self,
+        pool: Data<&Repository>,
+        auth: AdminAuthApiKey,
+        id: Path<Uuid>,
+        data: Json<RejectRefundData>,
+    ) -> RefundActionResponse {
+        endpoints::admin::refunds::reject(pool.0, &auth.id, &id, &data).await
+    }
+
+    #[oai(path = "/admin/accounts/:account_id/logs", method = "get")]
+    async fn admin_account_logs(
+        &self,
+        pool: Data<&Repository>,
+        _auth: AdminAuthApiKey,
+        account_id: Path<Uuid>,
+        cursor: Query<Option<Uuid>>,
+        limit: Query<Option<u32>>,
+        log_type: Query<Vec<LogTypes>>,
+        payment_id: Query<Option<Uuid>>,
+    ) -> LogsResponse {
+        let filter = LogsFilter {
+            log_types: log_type.0,
+            payment_id: payment_id.0,
+            cursor: cursor.0,
+            ..Default::default()
+        };
+
+        endpoints::logs::logs(pool.0, &account_id, filter, limit.0, true).await
+    }
+
+    #[oai(path = "/admin/payments", method = "get")]
+    async fn admin_payments(
+        &self,
+        pool: Data<&Repository>,
+        _auth: AdminAuthApiKey,
+        account_id: Query<Option<Uuid>>,
+        address: Query<Option<String>>,
+        transaction_id: Query<Option<String>>,
+        domain: Query<Option<String>>,
+        limit: Query<Option<u32>>,
+    ) -> AdminPaymentsResponse {
+        let search = PaymentSearch {
+            account_id: account_id.0,
+            address: address.0,
+            transaction_id: transaction_id.0,
+            domain: domain.0,
+            ..Default::default()
+        };
+
+        endpoints::admin::payments::search(pool.0, search, limit.0).await
+    }
+
+    #[oai(path = "/admin/payments/:id", method = "get")]
+    async fn admin_payment_timeline(
+        &self,
+        pool: Data<&Repository>,
+        _auth: AdminAuthApiKey,
+        id: Path<Uuid>,
+    ) -> PaymentTimelineResponse {
+        endpoints::admin::payments::timeline(pool.0, &id).await
+    }
+
+    #[oai(path = "/admin/payments/:id/initiate", method = "post")]
+    async fn admin_initiate_payment(
+        &self,
+        pool: Data<&Repository>,
+        auth: AdminAuthApiKey,
+        id: Path<Uuid>,
+        data: Json<PaymentOverrideData>,
+    ) -> PaymentOverrideResponse {
+        endpoints::admin::payments::initiate(pool.0, &auth.id, &id, &data).await
+    }
+
+    #[oai(path = "/admin/payments/:id/complete", method = "post")]
+    async fn admin_complete_payment(
+        &self,
+        pool: Data<&Repository>,
+        auth: AdminAuthApiKey,
+        id: Path<Uuid>,
+        data: Json<PaymentOverrideData>,
+    ) -> PaymentOverrideResponse {
+        endpoints::admin::payments::complete(pool.0, &auth.id, &id, &data).await
+    }
+
+    #[oai(path = "/admin/payments/:id/cancel", method = "post")]
+    async fn admin_cancel_payment(
+        &self,
+        pool: Data<&Repository>,
+        auth: AdminAuthApiKey,
+        id: Path<Uuid>,
+        data: Json<PaymentOverrideData>,
+    ) -> PaymentOverrideResponse {
+        endpoints::admin::payments::cancel(pool.0, &auth.id, &id, &data).await
+    }
+
+    #[oai(path = "/admin/payments/:id/requeue-inscription", method = "post")]
+    async fn admin_requeue_inscription(
+        &self,
+        pool: Data<&Repository>,
+        auth: AdminAuthApiKey,
+        id: Path<Uuid>,
+        data: Json<PaymentOverrideData>,
+    ) -> PaymentOverrideResponse {
+        endpoints::admin::payments::requeue_inscription(pool.0, &auth.id, &id, &data).await
+    }
+
+    #[oai(path = "/admin/addresses/:address/rescan", method = "post")]
+    async fn admin_rescan_address(
+        &self,
+        pool: Data<&Repository>,
+        addresses: Data<&AddressProvider>,
+        auth: AdminAuthApiKey,
+        address: Path<String>,
+        data: Json<RescanAddressData>,
+    ) -> RescanAddressResponse {
+        endpoints::admin::addresses::rescan(pool.0, addresses.wallet(), &auth.id, &address.0, &data)
+            .await
     }
 }
 
 fn get_rpc() -> Client {
+    get_wallet_rpc(BITCOIN_WALLET_NAME)
+}
+
+fn get_wallet_rpc(wallet: &str) -> Client {
     let rpc_url = format!(
         "http://localhost:{}/wallet/{}",
         CHAIN.default_rpc_port(),
-        BITCOIN_WALLET_NAME
+        wallet
     );
 
     #[cfg(debug_assertions)]
@@ -162,127 +681,131 @@ fn get_rpc() -> Client {
     Client::new(&rpc_url, auth).unwrap()
 }
 
-async fn background_payment_processor() {
+fn ensure_wallet_loaded(rpc: &Client, wallet: &str, watch_only: bool) {
+    if rpc.list_wallets().unwrap().contains(&wallet.to_string()) {
+        return;
+    }
+
+    if let Err(e) = rpc.load_wallet(wallet) {
+        if !watch_only {
+            panic!("Failed to load wallet {}: {}", wallet, e);
+        }
+
+        info!("Creating watch-only wallet {}", wallet);
+        rpc.create_wallet(wallet, Some(true), Some(true), None, None)
+            .unwrap();
+    }
+}
+
+/// Derives receive addresses from `RECEIVE_DESCRIPTOR` into a watch-only wallet when it is set,
+/// otherwise falls back to `getnewaddress` on the hot wallet.
+fn get_address_provider(rpc: &Arc<Client>) -> AddressProvider {
+    match env::var("RECEIVE_DESCRIPTOR") {
+        Ok(descriptor) => {
+            let wallet = env::var("WATCH_ONLY_WALLET_NAME")
+                .unwrap_or(DEFAULT_WATCH_ONLY_WALLET_NAME.to_string());
+            let watch_rpc = get_wallet_rpc(&wallet);
+            ensure_wallet_loaded(&watch_rpc, &wallet, true);
+
+            AddressProvider::watch_only(Arc::new(watch_rpc), &descriptor).unwrap()
+        }
+        Err(_) => AddressProvider::wallet_generated(Wallet::Rpc(RpcWallet::new(rpc.clone()))),
+    }
+}
+
+async fn background_payment_processor(addresses: AddressProvider, lightning: Option<Lightning>) {
     info!("Starting background payment processor");
-    let rpc = get_rpc();
     let pool = Repository::new().await;
     info!("Connected to Bitcoin RPC and database");
 
     loop {
         let watch_addresses = pool
-            .get_to_be_initiated_addresses()
+            .get_watched_addresses()
             .await
             .unwrap()
             .into_iter()
             .map(|address| Address::from_str(&address).unwrap())
-            .map(|address| address.require_network(CHAIN.network()))
-            .flatten()
+            .filter_map(|address| address.require_network(CHAIN.network()).ok())
             .collect::<Vec<Address<NetworkChecked>>>();
 
-        let utxos = rpc
-            .list_unspent(
-                Some(0),
-                Some(100), // Do not update utxo's with more than 100 confirmations
-                Some(watch_addresses.iter().collect::<Vec<_>>().as_slice()),
-                Some(true),
-                None,
-            )
-            .unwrap();
+        // Do not update utxo's with more than 100 confirmations
+        let res =
+            process_onchain_payments(&pool, addresses.wallet(), &watch_addresses, Some(100)).await;
 
-        if utxos.len() > 0 {
-            for utxo in &utxos {
-                let address = match utxo
-                    .address
-                    .clone()
-                    .unwrap()
-                    .require_network(CHAIN.network())
-                {
-                    Ok(address) => address.to_string(),
-                    Err(_) => continue,
-                };
-
-                let amount = utxo.amount.to_btc();
-                let txid = utxo.txid.clone().to_string();
-                let confirmations = utxo.confirmations;
-
-                let is_already_processed =
-                    pool.is_already_processed(&txid, &address).await.unwrap();
-
-                if is_already_processed {
-                    continue;
-                }
+        if let Err(e) = res {
+            error!("Error processing on-chain payments: {}", e);
+        }
 
-                let payment = match pool.get_payment_by_address(&address).await.unwrap() {
-                    Some(payment_id) => payment_id,
-                    None => continue,
-                };
+        // Settled invoices have to be picked up before their order gets expired.
+        if let Some(lightning) = &lightning {
+            process_lightning_payments(&pool, lightning).await;
+        }
 
-                if !payment.initiated {
-                    let res = pool.initiate_payment(&payment.id).await;
+        match pool.expire_old_orders().await {
+            Ok(expired) => {
+                for (payment_id, account_id) in expired {
+                    let event = LogEvent::PaymentExpired { payment_id };
+                    let res = pool.add_log(&account_id, &event).await;
 
                     if let Err(e) = res {
-                        error!("Error initiating payment: {}", e);
-                        continue;
+                        error!("Error adding log: {}", e);
                     }
+                }
+            }
+            Err(e) => error!("Error expiring old orders: {}", e),
+        }
 
-                    let log_message = format!(
-                        "account {}, payment: {} transaction: {}, initiated: ({}BTC)",
-                        payment.account_id, payment.id, txid, payment.amount
-                    );
-                    let res = pool
-                        .add_log(
-                            &payment.account_id,
-                            LogTypes::PaymentReceivedUnconfirmed,
-                            Some(&log_message),
-                        )
-                        .await;
+        match pool.cancel_unpaid_transfers().await {
+            Ok(transfers) => {
+                for transfer in transfers {
+                    let event = LogEvent::DomainTransferCancelled {
+                        transfer_id: transfer.id,
+                        domain: transfer.domain,
+                        closed_by: transfer.from_account_id,
+                    };
+                    let res = pool.add_log(&transfer.from_account_id, &event).await;
 
                     if let Err(e) = res {
                         error!("Error adding log: {}", e);
-                        continue;
                     }
                 }
+            }
+            Err(e) => error!("Error cancelling unpaid external transfers: {}", e),
+        }
 
-                if confirmations < CONFIRMATIONS_REQUIRED {
-                    continue;
-                }
-
-                let res = pool.add_payment_received(&payment.id, amount, &txid).await;
-
-                if let Err(e) = res {
-                    error!("Error adding payment received: {}", e);
-                    continue;
-                }
-
-                info!("Payment {} received {}BTC", payment.id, amount);
-
-                let log_message = format!(
-                    "account {}, payment: {} transaction: {}, received {}BTC",
-                    payment.account_id, payment.id, txid, amount
-                );
-                let res = pool
-                    .add_log(
-                        &payment.account_id,
-                        LogTypes::PaymentReceivedConfirmed,
-                        Some(&log_message),
-                    )
-                    .await;
-
-                if let Err(e) = res {
-                    error!("Error adding log: {}", e);
-                    continue;
+        match pool.retire_transferred_keys().await {
+            Ok(transfers) => {
+                for transfer in transfers {
+                    info!("Retired the sender's key of external transfer {}", transfer);
                 }
             }
+            Err(e) => error!("Error retiring keys of external transfers: {}", e),
         }
 
-        if let Err(e) = pool.cleanup_old_orders().await {
-            error!("Error cleaning up old orders: {}", e);
+        if let Err(e) = pool
+            .delete_expired_idempotency_keys(*IDEMPOTENCY_KEY_RETENTION_HOURS)
+            .await
+        {
+            error!("Error deleting expired idempotency keys: {}", e);
         }
 
         tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
     }
 }
 
+async fn background_sweeper(wallet: Wallet, config: SweepConfig) {
+    info!("Starting background sweeper");
+    let pool = Repository::new().await;
+
+    loop {
+        if let Err(e) = sweep(&wallet, &pool, &config).await {
+            error!("Error sweeping order funds: {}", e);
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(config.interval_secs)).await;
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     color_eyre::install().ok();
@@ -290,16 +813,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt::init();
 
     let repository = Repository::new().await;
+    repository.migrate().await?;
 
-    let rpc = get_rpc();
-    if !rpc
-        .list_wallets()
-        .unwrap()
-        .contains(&BITCOIN_WALLET_NAME.to_string())
-    {
-        rpc.load_wallet(BITCOIN_WALLET_NAME).unwrap();
+    // `pay migrate` only brings the schema up to date, e.g. ahead of a deploy.
+    if env::args().nth(1).as_deref() == Some("migrate") {
+        info!("Database migrations applied");
+        return Ok(());
     }
+
+    let rpc = get_rpc();
+    ensure_wallet_loaded(&rpc, BITCOIN_WALLET_NAME, false);
     let rpc = Arc::new(rpc);
+    let wallet = Wallet::Rpc(RpcWallet::new(rpc.clone()));
+    let addresses = get_address_provider(&rpc);
+    let lightning = Lightning::from_env();
+    let fees = FeeEstimator::new(wallet.clone(), NetworkFeeConfig::from_env());
 
     let api_service = OpenApiService::new(Api, "Xiler Authentication API", "v0.0.1")
         .server("http://localhost:25202")
@@ -324,9 +852,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .nest("/swagger", open_api)
         .with(Cors::new().allow_origins(origins))
         .data(repository)
-        .data(rpc);
+        .data(wallet.clone())
+        .data(addresses.clone())
+        .data(fees)
+        .data(RateLimiter::from_env())
+        .data(lightning.clone());
+
+    tokio::spawn(background_payment_processor(addresses, lightning));
 
-    tokio::spawn(background_payment_processor());
+    // Sweeps spend from the hot wallet, so they are refused when receiving through a descriptor.
+    if let Some(config) = SweepConfig::from_env(&rpc) {
+        tokio::spawn(background_sweeper(wallet, config));
+    }
 
     Server::new(TcpListener::bind("127.0.0.1:25202"))
         .run(routes)
@@ -0,0 +1,65 @@
+use uuid::Uuid;
+
+/// Percent-encodes everything outside of the RFC 3986 unreserved set.
+fn encode_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+/// Formats a BTC amount as a plain decimal, BIP21 does not allow exponents.
+fn format_amount(amount: f64) -> String {
+    let amount = format!("{:.8}", amount);
+    amount
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+pub fn payment_uri(
+    address: &str,
+    amount: f64,
+    label: &str,
+    message: &str,
+    lightning: Option<&str>,
+) -> String {
+    let mut uri = format!(
+        "bitcoin:{}?amount={}&label={}&message={}",
+        address,
+        format_amount(amount),
+        encode_component(label),
+        encode_component(message)
+    );
+
+    if let Some(lightning) = lightning {
+        uri.push_str("&lightning=");
+        uri.push_str(&encode_component(lightning));
+    }
+
+    uri
+}
+
+pub fn order_payment_uri(
+    id: &Uuid,
+    address: &str,
+    amount: f64,
+    domains: &[String],
+    lightning: Option<&str>,
+) -> String {
+    payment_uri(
+        address,
+        amount,
+        &format!("Xiler: {}", domains.join(", ")),
+        &format!("Xiler domain order {}", id),
+        lightning,
+    )
+}
@@ -0,0 +1,276 @@
+use std::{collections::HashSet, fmt};
+
+use unicode_script::{Script, UnicodeScript};
+
+pub const DOMAIN_SUFFIX: &str = ".o";
+
+/// Longest label accepted, measured on the punycode encoded form.
+const MAX_LABEL_LENGTH: usize = 253;
+
+/// Labels that can never be registered, regardless of the lists managed by admins.
+const RESERVED_LABELS: &[&str] = &["localhost", "invalid", "example", "test", "xiler"];
+
+/// Script combinations that are commonly written together and not considered confusable,
+/// following the "highly restrictive" profile of UTS #39.
+const ALLOWED_SCRIPT_SETS: &[&[Script]] = &[
+    &[
+        Script::Latin,
+        Script::Han,
+        Script::Hiragana,
+        Script::Katakana,
+    ],
+    &[Script::Latin, Script::Han, Script::Hangul],
+    &[Script::Latin, Script::Han, Script::Bopomofo],
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DomainNameError {
+    Empty,
+    /// Only a single label followed by the `.o` suffix is supported.
+    Subdomain(String),
+    TooLong(usize),
+    /// The label fails UTS-46 processing (invalid code points, hyphen placement, ...).
+    Invalid(String),
+    MixedScript(String),
+    Reserved(String),
+}
+
+impl fmt::Display for DomainNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomainNameError::Empty => write!(f, "Domain name is empty"),
+            DomainNameError::Subdomain(name) => {
+                write!(f, "Invalid domain: {} (subdomains are not supported)", name)
+            }
+            DomainNameError::TooLong(length) => write!(
+                f,
+                "Domain name is too long ({} characters, at most {})",
+                length, MAX_LABEL_LENGTH
+            ),
+            DomainNameError::Invalid(name) => write!(f, "Invalid domain: {}", name),
+            DomainNameError::MixedScript(name) => write!(
+                f,
+                "Invalid domain: {} (mixes characters of different scripts)",
+                name
+            ),
+            DomainNameError::Reserved(name) => write!(f, "Domain {} is reserved", name),
+        }
+    }
+}
+
+/// A normalized `.o` domain name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DomainName {
+    /// Lowercase ASCII form including the suffix, internationalized labels are punycode
+    /// encoded. This is the form that is stored and inscribed.
+    pub ascii: String,
+    /// The same name with punycode labels decoded, for display.
+    pub unicode: String,
+}
+
+impl DomainName {
+    /// The name without the `.o` suffix.
+    pub fn label(&self) -> &str {
+        self.ascii.trim_end_matches(DOMAIN_SUFFIX)
+    }
+}
+
+impl fmt::Display for DomainName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.ascii)
+    }
+}
+
+fn is_mixed_script(label: &str) -> bool {
+    let scripts = label
+        .chars()
+        .map(|c| c.script())
+        .filter(|script| *script != Script::Common && *script != Script::Inherited)
+        .collect::<HashSet<_>>();
+
+    if scripts.len() <= 1 {
+        return false;
+    }
+
+    !ALLOWED_SCRIPT_SETS
+        .iter()
+        .any(|allowed| scripts.iter().all(|script| allowed.contains(script)))
+}
+
+/// Normalizes user input into a domain name: trims whitespace, case folds, makes the `.o`
+/// suffix optional and applies UTS-46 processing so unicode and punycode input end up as the
+/// same name.
+pub fn normalize(input: &str) -> Result<DomainName, DomainNameError> {
+    let name = input.trim().to_lowercase();
+    let label = name.strip_suffix(DOMAIN_SUFFIX).unwrap_or(&name);
+
+    if label.is_empty() {
+        return Err(DomainNameError::Empty);
+    }
+
+    // Dots are checked after mapping too, as UTS-46 maps full width stops to '.'.
+    if label.contains('.') {
+        return Err(DomainNameError::Subdomain(input.trim().to_string()));
+    }
+
+    let ascii = idna::Config::default()
+        .use_std3_ascii_rules(true)
+        .check_hyphens(true)
+        .to_ascii(label)
+        .map_err(|_| DomainNameError::Invalid(input.trim().to_string()))?;
+
+    if ascii.is_empty() {
+        return Err(DomainNameError::Empty);
+    }
+
+    if ascii.contains('.') {
+        return Err(DomainNameError::Subdomain(input.trim().to_string()));
+    }
+
+    if ascii.len() > MAX_LABEL_LENGTH {
+        return Err(DomainNameError::TooLong(ascii.len()));
+    }
+
+    let (unicode, res) = idna::domain_to_unicode(&ascii);
+
+    if res.is_err() {
+        return Err(DomainNameError::Invalid(input.trim().to_string()));
+    }
+
+    if is_mixed_script(&unicode) {
+        return Err(DomainNameError::MixedScript(unicode));
+    }
+
+    if RESERVED_LABELS.contains(&ascii.as_str()) {
+        return Err(DomainNameError::Reserved(format!(
+            "{}{}",
+            ascii, DOMAIN_SUFFIX
+        )));
+    }
+
+    Ok(DomainName {
+        ascii: format!("{}{}", ascii, DOMAIN_SUFFIX),
+        unicode: format!("{}{}", unicode, DOMAIN_SUFFIX),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ascii(input: &str) -> String {
+        normalize(input).unwrap().ascii
+    }
+
+    #[test]
+    fn unicode_and_punycode_are_the_same_name() {
+        let unicode = normalize("日本.o").unwrap();
+        let punycode = normalize("xn--wgv71a.o").unwrap();
+
+        assert_eq!(unicode, punycode);
+        assert_eq!(unicode.ascii, "xn--wgv71a.o");
+        assert_eq!(unicode.unicode, "日本.o");
+        assert_eq!(unicode.label(), "xn--wgv71a");
+    }
+
+    #[test]
+    fn folds_case_and_trims_whitespace() {
+        assert_eq!(ascii("Alice.o"), "alice.o");
+        assert_eq!(ascii("ALICE.O"), "alice.o");
+        assert_eq!(ascii("  alice.o\n"), "alice.o");
+        assert_eq!(ascii("ÄLICE"), ascii("älice"));
+    }
+
+    #[test]
+    fn the_suffix_is_optional() {
+        assert_eq!(ascii("alice"), "alice.o");
+        assert_eq!(ascii("alice.o"), "alice.o");
+        assert_eq!(normalize(".o"), Err(DomainNameError::Empty));
+        assert_eq!(normalize("   "), Err(DomainNameError::Empty));
+    }
+
+    #[test]
+    fn rejects_subdomains() {
+        assert_eq!(
+            normalize("bob.alice.o"),
+            Err(DomainNameError::Subdomain("bob.alice.o".to_string()))
+        );
+        assert_eq!(
+            normalize("alice.btc"),
+            Err(DomainNameError::Subdomain("alice.btc".to_string()))
+        );
+
+        // UTS-46 maps full width and ideographic stops to '.'.
+        for name in ["bob．alice", "bob。alice"] {
+            assert_eq!(
+                normalize(name),
+                Err(DomainNameError::Subdomain(name.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_labels() {
+        for name in ["-alice", "alice-", "al_ice", "al ice"] {
+            assert_eq!(
+                normalize(name),
+                Err(DomainNameError::Invalid(name.to_string())),
+                "{:?}",
+                name
+            );
+        }
+
+        assert_eq!(
+            normalize(&"a".repeat(MAX_LABEL_LENGTH + 1)),
+            Err(DomainNameError::TooLong(MAX_LABEL_LENGTH + 1))
+        );
+        assert!(normalize(&"a".repeat(MAX_LABEL_LENGTH)).is_ok());
+    }
+
+    #[test]
+    fn rejects_mixed_scripts() {
+        // The first letter is a Cyrillic 'а'.
+        assert_eq!(
+            normalize("аlice"),
+            Err(DomainNameError::MixedScript("аlice".to_string()))
+        );
+        assert_eq!(
+            normalize("한국カナ"),
+            Err(DomainNameError::MixedScript("한국カナ".to_string()))
+        );
+        assert_eq!(
+            normalize("ㄅㄆ한국"),
+            Err(DomainNameError::MixedScript("ㄅㄆ한국".to_string()))
+        );
+    }
+
+    #[test]
+    fn allows_the_east_asian_script_sets() {
+        for name in [
+            "日本ひらがなカタカナ",
+            "abc日本カナ",
+            "한국中国",
+            "abc한국",
+            "ㄅㄆ中文",
+            "alice2000",
+        ] {
+            assert!(normalize(name).is_ok(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_reserved_labels() {
+        for label in RESERVED_LABELS {
+            assert_eq!(
+                normalize(label),
+                Err(DomainNameError::Reserved(format!("{}.o", label)))
+            );
+        }
+
+        assert_eq!(
+            normalize("Example.O"),
+            Err(DomainNameError::Reserved("example.o".to_string()))
+        );
+        assert!(normalize("examples").is_ok());
+    }
+}
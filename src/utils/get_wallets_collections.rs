@@ -65,6 +65,14 @@ pub struct WalletCollections {
 pub async fn get_wallets_collections(
     addresses: &[&str],
 ) -> Result<WalletCollections, reqwest::Error> {
+    // Accounts without linked wallets can't own any collections.
+    if addresses.is_empty() {
+        return Ok(WalletCollections {
+            brc20s: Vec::new(),
+            collections: Vec::new(),
+        });
+    }
+
     let addresses_request = AddressesRequest {
         addresses: addresses.iter().map(|x| x.to_string()).collect(),
     };
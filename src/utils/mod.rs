@@ -1,2 +1,6 @@
+pub mod bip21;
+pub mod domain_name;
 pub mod encryption;
 pub mod get_wallets_collections;
+pub mod qr_code;
+pub mod rate_limit;
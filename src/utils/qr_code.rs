@@ -0,0 +1,27 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageOutputFormat, Luma};
+use qrcode::{render::svg, QrCode, QrResult};
+
+const QR_CODE_MIN_DIMENSIONS: u32 = 256;
+
+pub fn render_png(data: &str) -> QrResult<Vec<u8>> {
+    let image = QrCode::new(data.as_bytes())?
+        .render::<Luma<u8>>()
+        .min_dimensions(QR_CODE_MIN_DIMENSIONS, QR_CODE_MIN_DIMENSIONS)
+        .build();
+
+    let mut png = Cursor::new(Vec::new());
+    DynamicImage::ImageLuma8(image)
+        .write_to(&mut png, ImageOutputFormat::Png)
+        .expect("Encoding a QR code as PNG can not fail");
+
+    Ok(png.into_inner())
+}
+
+pub fn render_svg(data: &str) -> QrResult<String> {
+    Ok(QrCode::new(data.as_bytes())?
+        .render::<svg::Color>()
+        .min_dimensions(QR_CODE_MIN_DIMENSIONS, QR_CODE_MIN_DIMENSIONS)
+        .build())
+}
@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    env,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use poem::Request;
+
+const DEFAULT_PUBLIC_RATE_LIMIT: u32 = 60;
+const DEFAULT_PUBLIC_RATE_LIMIT_WINDOW_SECS: u64 = 60;
+
+/// Fixed window request limiter keyed by client ip, for the unauthenticated endpoints.
+#[derive(Clone)]
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    /// Number of proxies in front of the service that append to `X-Forwarded-For`. The client ip
+    /// is taken this many entries from the right, entries further left are client supplied.
+    /// Zero ignores the header.
+    trusted_proxies: usize,
+    hits: Arc<Mutex<HashMap<IpAddr, (Instant, u32)>>>,
+}
+
+impl RateLimiter {
+    /// Configured through `PUBLIC_RATE_LIMIT` (requests per window),
+    /// `PUBLIC_RATE_LIMIT_WINDOW_SECS` and `TRUSTED_PROXIES`. `TRUST_FORWARDED_FOR=true` is kept
+    /// as a single trusted proxy.
+    pub fn from_env() -> Self {
+        let trusted_proxies = env::var("TRUSTED_PROXIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(
+                if env::var("TRUST_FORWARDED_FOR").ok().as_deref() == Some("true") {
+                    1
+                } else {
+                    0
+                },
+            );
+
+        Self {
+            limit: env::var("PUBLIC_RATE_LIMIT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_PUBLIC_RATE_LIMIT),
+            window: Duration::from_secs(
+                env::var("PUBLIC_RATE_LIMIT_WINDOW_SECS")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(DEFAULT_PUBLIC_RATE_LIMIT_WINDOW_SECS),
+            ),
+            trusted_proxies,
+            hits: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn client_ip(&self, req: &Request) -> Option<IpAddr> {
+        if self.trusted_proxies > 0 {
+            // Every proxy appends the address it received the request from, so only the
+            // rightmost entries can't be forged by the client.
+            let forwarded = req
+                .header("x-forwarded-for")
+                .and_then(|value| value.rsplit(',').nth(self.trusted_proxies - 1))
+                .and_then(|ip| ip.trim().parse().ok());
+
+            if forwarded.is_some() {
+                return forwarded;
+            }
+        }
+
+        req.remote_addr().as_socket_addr().map(|addr| addr.ip())
+    }
+
+    /// Counts the request, returns false once the client went over the limit.
+    pub fn check(&self, req: &Request) -> bool {
+        let ip = match self.client_ip(req) {
+            Some(ip) => ip,
+            None => return true,
+        };
+
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap();
+
+        // Keep the map from growing with clients that went quiet.
+        if hits.len() > 10000 {
+            hits.retain(|_, (start, _)| now.duration_since(*start) < self.window);
+        }
+
+        let entry = hits.entry(ip).or_insert((now, 0));
+
+        if now.duration_since(entry.0) >= self.window {
+            *entry = (now, 0);
+        }
+
+        entry.1 += 1;
+        entry.1 <= self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(trusted_proxies: usize) -> RateLimiter {
+        RateLimiter {
+            limit: 1,
+            window: Duration::from_secs(60),
+            trusted_proxies,
+            hits: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn request(forwarded_for: &str) -> Request {
+        Request::builder()
+            .header("x-forwarded-for", forwarded_for)
+            .finish()
+    }
+
+    #[test]
+    fn takes_the_address_appended_by_the_proxy() {
+        let req = request("10.0.0.1, 203.0.113.7");
+
+        assert_eq!(
+            limiter(1).client_ip(&req),
+            Some("203.0.113.7".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn skips_the_hops_of_trusted_proxies() {
+        let req = request("10.0.0.1, 203.0.113.7, 192.168.1.2");
+
+        assert_eq!(
+            limiter(2).client_ip(&req),
+            Some("203.0.113.7".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn ignores_the_header_without_trusted_proxies() {
+        assert_eq!(limiter(0).client_ip(&request("203.0.113.7")), None);
+    }
+
+    #[test]
+    fn spoofed_entries_share_the_limit_of_the_client() {
+        let limiter = limiter(1);
+
+        assert!(limiter.check(&request("10.0.0.1, 203.0.113.7")));
+        assert!(!limiter.check(&request("10.0.0.2, 203.0.113.7")));
+    }
+}